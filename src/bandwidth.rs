@@ -0,0 +1,160 @@
+//! Bandwidth limiting for file transfers.
+//!
+//! File chunks are throttled with a token bucket per direction so that a
+//! large transfer does not saturate the link. Chat and control messages are
+//! never throttled; the connection writer also sends them ahead of any
+//! queued chunks.
+//!
+//! # Features
+//!
+//! - Separate upload and download limits, in kilobytes per second
+//! - Limits can be changed at runtime with `/limit`
+//! - Up to one second of unused allowance may be spent in a burst
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rust_p2p_chat::bandwidth::RateLimiter;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let limiter = RateLimiter::new(Some(512));
+//!     assert_eq!(limiter.limit(), Some(512));
+//!
+//!     // Waits until 64 KB may be sent at 512 KB/s
+//!     limiter.acquire(64 * 1024).await;
+//!
+//!     limiter.set_limit(None);
+//!     assert_eq!(limiter.limit(), None);
+//! }
+//! ```
+
+use crate::config::Config;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket limiting throughput to a number of kilobytes per second.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Limit in KB/s, `None` when unlimited.
+    kbps: Option<u64>,
+    /// Bytes that may be sent right now; negative while in debt.
+    tokens: f64,
+    /// When `tokens` was last refilled.
+    updated: Instant,
+}
+
+impl Bucket {
+    fn bytes_per_sec(&self) -> Option<f64> {
+        self.kbps.map(|kbps| kbps as f64 * 1024.0)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(rate) = self.bytes_per_sec() {
+            let elapsed = now.duration_since(self.updated).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate).min(rate);
+        }
+        self.updated = now;
+    }
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `kbps` kilobytes per second.
+    ///
+    /// # Arguments
+    ///
+    /// * `kbps` - Limit in KB/s; `None` or `Some(0)` means unlimited
+    pub fn new(kbps: Option<u64>) -> Self {
+        RateLimiter {
+            bucket: Mutex::new(Bucket {
+                kbps: kbps.filter(|&kbps| kbps > 0),
+                tokens: 0.0,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Returns the current limit in KB/s, or `None` if unlimited.
+    pub fn limit(&self) -> Option<u64> {
+        self.bucket.lock().unwrap().kbps
+    }
+
+    /// Changes the limit. Takes effect for the next [`acquire`](Self::acquire).
+    ///
+    /// # Arguments
+    ///
+    /// * `kbps` - New limit in KB/s; `None` or `Some(0)` means unlimited
+    pub fn set_limit(&self, kbps: Option<u64>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill(Instant::now());
+        bucket.kbps = kbps.filter(|&kbps| kbps > 0);
+        bucket.tokens = bucket.tokens.min(0.0);
+    }
+
+    /// Waits until `bytes` may be transferred without exceeding the limit.
+    ///
+    /// Requests larger than the bucket are allowed; the limiter goes into
+    /// debt and the caller waits until it is paid off.
+    pub async fn acquire(&self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        let delay = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let Some(rate) = bucket.bytes_per_sec() else {
+                return;
+            };
+            bucket.refill(Instant::now());
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Upload and download limits for a connection.
+#[derive(Debug)]
+pub struct BandwidthLimits {
+    /// Limits file chunks we send.
+    pub upload: RateLimiter,
+    /// Limits file chunks we receive.
+    pub download: RateLimiter,
+}
+
+impl BandwidthLimits {
+    /// Creates limiters using `max_upload_kbps` and `max_download_kbps`.
+    pub fn from_config(config: &Config) -> Self {
+        BandwidthLimits {
+            upload: RateLimiter::new(config.max_upload_kbps),
+            download: RateLimiter::new(config.max_download_kbps),
+        }
+    }
+}
+
+impl fmt::Display for BandwidthLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Upload: {}, download: {}",
+            describe_limit(self.upload.limit()),
+            describe_limit(self.download.limit())
+        )
+    }
+}
+
+/// Formats a limit for display, e.g. `"512 KB/s"` or `"unlimited"`.
+pub fn describe_limit(kbps: Option<u64>) -> String {
+    match kbps {
+        Some(kbps) => format!("{} KB/s", kbps),
+        None => "unlimited".to_string(),
+    }
+}
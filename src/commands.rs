@@ -20,10 +20,21 @@
 //! | `/help` | `/?` | Show help message with all commands |
 //! | `/info` | | Display connection and configuration info |
 //! | `/peers` | `/list` | List all connected peers |
+//! | `/connect <addr\|contact>` | | Connect to another peer |
+//! | `/disconnect <peer>` | | Close the connection to a peer |
+//! | `/discover` | | List peers found on the local network |
 //! | `/nick <name>` | `/nickname` | Set or change your nickname |
-//! | `/send <file>` | `/file` | Send a file to connected peers |
+//! | `/msg <peer> <text>` | `/pm` | Send a private, encrypted message to one peer |
+//! | `/send <path>` | `/file` | Send a file or directory to connected peers |
 //! | `/autoopen` | `/auto` | Toggle auto-open for media files |
 //! | `/stats` | `/statistics` | Show message reliability statistics |
+//! | `/transfers` | | List file transfers and their progress |
+//! | `/accept <id>` | | Accept a file transfer offered by a peer |
+//! | `/cancel <id>` | `/reject` | Cancel a file transfer or reject an offer |
+//! | `/pause <id>` | | Pause a file transfer |
+//! | `/resume <id>` | | Resume a paused file transfer |
+//! | `/limit [up\|down <kbps\|off>]` | | Show or set transfer bandwidth limits |
+//! | `/contacts [add <name> <addr> [fp]\|rm <name>]` | `/contact` | List, add or remove saved contacts |
 //! | `/quit` | `/exit` | Exit the chat application |
 //!
 //! # Examples
@@ -49,10 +60,14 @@
 //! }
 //! ```
 
+use crate::bandwidth::{self, BandwidthLimits};
 use crate::config::Config;
-use crate::error::Result;
-use crate::peer::PeerManager;
+use crate::contacts::Contacts;
+use crate::error::{ChatError, Result};
+use crate::peer::{Peer, PeerManager};
 use crate::protocol::Command;
+use std::path::PathBuf;
+use tracing::warn;
 
 /// Command handler for processing user commands in the chat application.
 ///
@@ -82,6 +97,8 @@ use crate::protocol::Command;
 pub struct CommandHandler {
     /// Application configuration that can be modified by commands.
     config: Config,
+    /// Contacts file used by `/contacts`, if any.
+    contacts_path: Option<PathBuf>,
 }
 
 impl CommandHandler {
@@ -102,9 +119,58 @@ impl CommandHandler {
     /// let handler = CommandHandler::new(config);
     /// ```
     pub fn new(config: Config) -> Self {
-        CommandHandler { config }
+        let contacts_path = config.contacts_path();
+        CommandHandler {
+            config,
+            contacts_path,
+        }
+    }
+
+    /// Uses the contacts file at `path` instead of the default location.
+    pub fn with_contacts_path(mut self, path: PathBuf) -> Self {
+        self.contacts_path = Some(path);
+        self
+    }
+
+    /// Returns the configuration, including changes made by commands.
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
+    /// Names and aliases of every command, without the leading `/`.
+    ///
+    /// Used for tab completion in the terminal.
+    pub const COMMANDS: &'static [&'static str] = &[
+        "accept",
+        "auto",
+        "autoopen",
+        "cancel",
+        "connect",
+        "contact",
+        "contacts",
+        "disconnect",
+        "discover",
+        "exit",
+        "file",
+        "help",
+        "info",
+        "limit",
+        "list",
+        "msg",
+        "nick",
+        "nickname",
+        "pause",
+        "peers",
+        "pm",
+        "quit",
+        "reject",
+        "resume",
+        "send",
+        "stats",
+        "statistics",
+        "transfers",
+    ];
+
     /// Parses user input and returns a Command if the input is a valid command.
     ///
     /// Commands must start with '/' and may include arguments. This method supports
@@ -160,6 +226,15 @@ impl CommandHandler {
             "help" | "?" => Some(Command::Help),
             "info" => Some(Command::Info),
             "peers" | "list" => Some(Command::ListPeers),
+            "connect" => match parts.as_slice() {
+                [_, target] => Some(Command::Connect(target.to_string())),
+                _ => None,
+            },
+            "disconnect" => match parts.as_slice() {
+                [_, peer] => Some(Command::Disconnect(peer.to_string())),
+                _ => None,
+            },
+            "discover" => Some(Command::Discover),
             "nick" | "nickname" => {
                 if parts.len() > 1 {
                     Some(Command::SetNickname(parts[1..].join(" ")))
@@ -167,6 +242,16 @@ impl CommandHandler {
                     None
                 }
             }
+            "msg" | "pm" => {
+                if parts.len() > 2 {
+                    Some(Command::PrivateMessage(
+                        parts[1].to_string(),
+                        parts[2..].join(" "),
+                    ))
+                } else {
+                    None
+                }
+            }
             "send" | "file" => {
                 if parts.len() > 1 {
                     Some(Command::SendFile(parts[1..].join(" ")))
@@ -176,10 +261,69 @@ impl CommandHandler {
             }
             "autoopen" | "auto" => Some(Command::ToggleAutoOpen),
             "stats" | "statistics" => Some(Command::Stats),
+            "transfers" => Some(Command::ListTransfers),
+            "accept" => Self::parse_transfer_id(&parts).map(Command::AcceptTransfer),
+            "cancel" | "reject" => Self::parse_transfer_id(&parts).map(Command::CancelTransfer),
+            "pause" => Self::parse_transfer_id(&parts).map(Command::PauseTransfer),
+            "resume" => Self::parse_transfer_id(&parts).map(Command::ResumeTransfer),
+            "limit" => Self::parse_limit(&parts),
+            "contacts" | "contact" => Self::parse_contacts(&parts),
+            _ => None,
+        }
+    }
+
+    /// Parses `/limit`, `/limit up <kbps>` and `/limit down <kbps>`.
+    ///
+    /// A rate of `off` or `0` removes the limit.
+    fn parse_limit(parts: &[&str]) -> Option<Command> {
+        match parts {
+            [_] => Some(Command::ShowLimits),
+            [_, direction, rate] => {
+                let rate = match *rate {
+                    "off" | "none" | "0" => None,
+                    rate => Some(rate.parse().ok()?),
+                };
+                match *direction {
+                    "up" | "upload" => Some(Command::SetUploadLimit(rate)),
+                    "down" | "download" => Some(Command::SetDownloadLimit(rate)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses `/contacts`, `/contacts add <name> <addr> [fingerprint]` and
+    /// `/contacts rm <name>`.
+    fn parse_contacts(parts: &[&str]) -> Option<Command> {
+        match parts {
+            [_] | [_, "list"] => Some(Command::ListContacts),
+            [_, "add", name, address] => Some(Command::AddContact(
+                name.to_string(),
+                address.to_string(),
+                None,
+            )),
+            [_, "add", name, address, fingerprint] => Some(Command::AddContact(
+                name.to_string(),
+                address.to_string(),
+                Some(fingerprint.to_string()),
+            )),
+            [_, "rm" | "remove", name] => Some(Command::RemoveContact(name.to_string())),
             _ => None,
         }
     }
 
+    /// Parses the transfer ID argument of `/accept`, `/cancel`, `/pause` and
+    /// `/resume`.
+    ///
+    /// Accepts the ID with or without the leading `#` shown by `/transfers`.
+    fn parse_transfer_id(parts: &[&str]) -> Option<u32> {
+        if parts.len() != 2 {
+            return None;
+        }
+        parts[1].trim_start_matches('#').parse().ok()
+    }
+
     /// Executes a command and returns the result message.
     ///
     /// This method processes the given command, potentially modifying the application
@@ -199,12 +343,20 @@ impl CommandHandler {
     ///
     /// - `ChatError::Io` if configuration saving fails
     /// - `ChatError::Configuration` if configuration is invalid
+    /// - `ChatError::FileTransfer` if no peer has a transfer with the given ID
     ///
     /// # Side Effects
     ///
     /// Some commands modify the application state:
-    /// - `SetNickname`: Updates and saves the configuration
+    /// - `SetNickname`: Announces the nickname to connected peers, then
+    ///   updates and saves the configuration
     /// - `ToggleAutoOpen`: Updates and saves the configuration
+    /// - `SetUploadLimit`, `SetDownloadLimit`: Update and save the
+    ///   configuration; a session applies the new limits from
+    ///   [`config`](Self::config)
+    ///
+    /// A configuration that cannot be saved does not fail these commands;
+    /// the change still applies and the response says it was not saved.
     ///
     /// # Examples
     ///
@@ -245,19 +397,92 @@ impl CommandHandler {
             Command::ListPeers => Ok(self.list_peers(peer_manager).await),
             Command::SetNickname(nick) => {
                 self.config.nickname = Some(nick.clone());
-                self.config.save()?;
-                Ok(format!("Nickname set to: {}", nick))
+                peer_manager.announce_nickname(&nick).await?;
+                Ok(self.save_config(format!("Nickname set to: {}", nick)))
+            }
+            Command::PrivateMessage(name, _) => Ok(format!("Sending private message to {}...", name)),
+            Command::ListContacts => Ok(self.list_contacts()),
+            Command::AddContact(name, address, fingerprint) => {
+                self.load_contacts().add(&name, &address, fingerprint)?;
+                Ok(format!("Saved contact {} ({})", name, address))
+            }
+            Command::RemoveContact(name) => {
+                if self.load_contacts().remove(&name)? {
+                    Ok(format!("Removed contact {}", name))
+                } else {
+                    Ok(format!("No contact named {}", name))
+                }
             }
+            Command::Connect(target) => Ok(format!("Connecting to {}...", target)),
+            Command::Disconnect(name) => {
+                let peer = peer_manager.disconnect(&name).await?;
+                Ok(format!("Disconnected from {}", peer.info.display_name()))
+            }
+            Command::Discover => Ok(if self.config.enable_discovery {
+                "Listening for peers on the local network...".to_string()
+            } else {
+                "LAN discovery is disabled (set enable_discovery = true in config.toml)".to_string()
+            }),
             Command::Quit => Ok("Goodbye!".to_string()),
             Command::SendFile(path) => Ok(format!("Preparing to send file: {}", path)),
             Command::ToggleAutoOpen => {
                 self.config.auto_open_media = !self.config.auto_open_media;
-                self.config.save()?;
-                Ok(format!("Auto-open media: {}", if self.config.auto_open_media { "enabled" } else { "disabled" }))
+                let state = if self.config.auto_open_media { "enabled" } else { "disabled" };
+                Ok(self.save_config(format!("Auto-open media: {}", state)))
             }
             Command::Stats => {
                 Ok("Message reliability statistics:\n  Feature implemented - acknowledgments and retries active\n  Use debug logging to see detailed reliability info".to_string())
             }
+            Command::ListTransfers => Ok(transfer_summary(peer_manager).await),
+            Command::AcceptTransfer(id) => {
+                let peer = transfer_peer(peer_manager, id).await?;
+                let name = peer.transfers.accept(id, &peer.tx).await?;
+                Ok(format!("✓ Accepted transfer #{} ({})", id, name))
+            }
+            Command::CancelTransfer(id) => {
+                let peer = transfer_peer(peer_manager, id).await?;
+                let name = peer.transfers.cancel(id, &peer.tx).await?;
+                Ok(format!("✓ Cancelled transfer #{} ({})", id, name))
+            }
+            Command::PauseTransfer(id) => {
+                let peer = transfer_peer(peer_manager, id).await?;
+                let name = peer.transfers.pause(id, &peer.tx).await?;
+                Ok(format!("⏸ Paused transfer #{} ({})", id, name))
+            }
+            Command::ResumeTransfer(id) => {
+                let peer = transfer_peer(peer_manager, id).await?;
+                let name = peer.transfers.resume(id, &peer.tx).await?;
+                Ok(format!("▶ Resumed transfer #{} ({})", id, name))
+            }
+            Command::ShowLimits => Ok(BandwidthLimits::from_config(&self.config).to_string()),
+            Command::SetUploadLimit(kbps) => {
+                self.config.max_upload_kbps = kbps;
+                Ok(self.save_config(format!(
+                    "Upload limit: {}",
+                    bandwidth::describe_limit(kbps)
+                )))
+            }
+            Command::SetDownloadLimit(kbps) => {
+                self.config.max_download_kbps = kbps;
+                Ok(self.save_config(format!(
+                    "Download limit: {}",
+                    bandwidth::describe_limit(kbps)
+                )))
+            }
+        }
+    }
+
+    /// Saves settings changed by a command and returns `response`.
+    ///
+    /// The change already applies to the running session, so a config file
+    /// that cannot be written is only reported.
+    fn save_config(&self, response: String) -> String {
+        match self.config.save() {
+            Ok(()) => response,
+            Err(e) => {
+                warn!("Failed to save config: {}", e);
+                format!("{}\n⚠ Setting applied but not saved: {}", response, e)
+            }
         }
     }
 
@@ -271,10 +496,21 @@ impl CommandHandler {
   /help, /?          - Show this help message
   /info              - Show connection information
   /peers, /list      - List connected peers
+  /connect <addr>    - Connect to a peer (address or contact name)
+  /disconnect <peer> - Close the connection to a peer
+  /discover          - List peers found on the local network
   /nick <name>       - Set your nickname
-  /send <file>       - Send a file to peer(s)
+  /msg <peer> <text> - Send a private message (peer ID or nickname)
+  /send <path>       - Send a file or directory to peer(s)
   /autoopen, /auto   - Toggle auto-open for media files
   /stats             - Show message reliability statistics
+  /transfers         - List file transfers and their progress
+  /accept <id>       - Accept a file offered by a peer
+  /cancel <id>       - Cancel a file transfer (/reject for an offer)
+  /pause <id>        - Pause a file transfer
+  /resume <id>       - Resume a paused file transfer
+  /limit [up|down N] - Show or set transfer bandwidth limits (KB/s)
+  /contacts          - List contacts (add <name> <addr> [fp], rm <name>)
   /quit, /exit       - Exit the chat
 
 Type normally to send messages to all connected peers."#
@@ -339,4 +575,64 @@ Type normally to send messages to all connected peers."#
         }
         result
     }
+
+    /// Loads the contacts file, picking up changes made elsewhere, such as
+    /// fingerprints recorded when peers connect.
+    fn load_contacts(&self) -> Contacts {
+        match &self.contacts_path {
+            Some(path) => Contacts::load(path),
+            None => Contacts::in_memory(),
+        }
+    }
+
+    /// Returns a formatted list of saved contacts.
+    fn list_contacts(&self) -> String {
+        let contacts = self.load_contacts();
+        if contacts.is_empty() {
+            return "No contacts saved. Add one with /contacts add <name> <address>".to_string();
+        }
+
+        let mut result = format!("Contacts ({}):\n", contacts.len());
+        for (name, contact) in contacts.iter() {
+            let fingerprint = contact.fingerprint.as_deref().unwrap_or("unverified");
+            let last_seen = contact
+                .last_seen
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs as i64, 0))
+                .map(|time| {
+                    time.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_else(|| "never".to_string());
+            result.push_str(&format!(
+                "  - {} at {} [{}], last seen {}\n",
+                name, contact.address, fingerprint, last_seen
+            ));
+        }
+        result
+    }
+}
+
+/// Returns the peer a transfer belongs to.
+async fn transfer_peer(peers: &PeerManager, id: u32) -> Result<Peer> {
+    for peer in peers.peers().await {
+        if peer.transfers.status(id).await.is_some() {
+            return Ok(peer);
+        }
+    }
+    Err(ChatError::FileTransfer(format!("Unknown transfer #{}", id)))
+}
+
+/// Formats the transfers of every peer for the `/transfers` command.
+async fn transfer_summary(peers: &PeerManager) -> String {
+    let mut lines = Vec::new();
+    for peer in peers.peers().await {
+        for status in peer.transfers.list().await {
+            lines.push(format!("  {} ({})\n", status, peer.info.display_name()));
+        }
+    }
+    if lines.is_empty() {
+        return "No file transfers.".to_string();
+    }
+    format!("File transfers ({}):\n{}", lines.len(), lines.concat())
 }
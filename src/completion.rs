@@ -0,0 +1,196 @@
+//! Tab completion for the terminal frontend.
+//!
+//! Completes `/` commands, file paths after `/send`, peer nicknames and
+//! contact names. [`Completions`] follows the session's events to know who is
+//! connected, and rereads the contacts file so that contacts added with
+//! `/contacts add` can be completed right away.
+
+use crate::commands::CommandHandler;
+use crate::contacts::Contacts;
+use crate::events::ChatEvent;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{PathBuf, MAIN_SEPARATOR};
+
+/// Completion candidates for the line being typed.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::completion::Completions;
+///
+/// let completions = Completions::new(None);
+/// let (start, candidates) = completions.complete("/he", 3);
+/// assert_eq!(start, 0);
+/// assert_eq!(candidates, vec!["/help"]);
+/// ```
+#[derive(Debug, Default)]
+pub struct Completions {
+    /// Nicknames of connected peers, by peer ID.
+    peers: BTreeMap<String, String>,
+    /// Contacts file to complete contact names from, if any.
+    contacts_path: Option<PathBuf>,
+}
+
+impl Completions {
+    /// Creates completions reading contact names from `contacts_path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `contacts_path` - Location of the contacts file, or `None` to not
+    ///   complete contact names
+    pub fn new(contacts_path: Option<PathBuf>) -> Self {
+        Completions {
+            peers: BTreeMap::new(),
+            contacts_path,
+        }
+    }
+
+    /// Keeps track of the nicknames of connected peers.
+    ///
+    /// Call this with every event of the session.
+    pub fn update(&mut self, event: &ChatEvent) {
+        match event {
+            ChatEvent::PeerRenamed { id, name, .. } => {
+                self.peers.insert(id.clone(), name.clone());
+            }
+            ChatEvent::PeerDisconnected { id, .. } => {
+                self.peers.remove(id);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the candidates for the word before the cursor.
+    ///
+    /// Which candidates are offered depends on where the word is:
+    ///
+    /// - the command itself: `/` commands and their aliases
+    /// - after `/send` or `/file`: files and directories
+    /// - after `/msg`, `/pm` or `/disconnect`: peer nicknames
+    /// - after `/connect` or `/contacts rm`: contact names
+    /// - after `/contacts` and `/limit`: their subcommands
+    /// - in a message: peer nicknames
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line being typed
+    /// * `pos` - Byte position of the cursor in `line`
+    ///
+    /// # Returns
+    ///
+    /// The byte position where the completed word starts, which the chosen
+    /// candidate replaces up to `pos`, and the sorted candidates.
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = &before[start..];
+        let previous: Vec<&str> = before[..start].split_whitespace().collect();
+
+        let candidates = match previous.as_slice() {
+            [] if word.starts_with('/') => CommandHandler::COMMANDS
+                .iter()
+                .map(|command| format!("/{}", command))
+                .filter(|command| command.starts_with(word))
+                .collect(),
+            // Paths may contain spaces, so everything after the command is
+            // completed as one path
+            ["/send" | "/file", ..] => {
+                let command_end = before.find(char::is_whitespace).unwrap_or(pos);
+                let path_start = pos - before[command_end..].trim_start().len();
+                return (path_start, complete_path(&before[path_start..]));
+            }
+            ["/msg" | "/pm" | "/disconnect"] => self.nicknames(word),
+            ["/connect"] | ["/contacts" | "/contact", "rm" | "remove"] => self.contact_names(word),
+            ["/contacts" | "/contact"] => matching(&["add", "list", "rm"], word),
+            ["/limit"] => matching(&["down", "up"], word),
+            [first, ..] if first.starts_with('/') => Vec::new(),
+            _ if word.is_empty() => Vec::new(),
+            _ => self.nicknames(word),
+        };
+        (start, candidates)
+    }
+
+    /// Returns the nicknames starting with `prefix`, ignoring case like
+    /// `/msg` does.
+    ///
+    /// Nicknames containing spaces are left out, as commands cannot take
+    /// them.
+    fn nicknames(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .peers
+            .values()
+            .filter(|name| !name.contains(char::is_whitespace))
+            .filter(|name| starts_with_ignore_case(name, prefix))
+            .cloned()
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Returns the contact names starting with `prefix`.
+    fn contact_names(&self, prefix: &str) -> Vec<String> {
+        let Some(path) = &self.contacts_path else {
+            return Vec::new();
+        };
+        Contacts::load(path)
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| starts_with_ignore_case(name, prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Returns the files and directories whose path starts with `prefix`.
+///
+/// Directories end with a separator so that completion can continue inside
+/// them. Hidden entries are only offered once their name starts with `.`.
+fn complete_path(prefix: &str) -> Vec<String> {
+    let (dir, name) = match prefix.rfind(['/', MAIN_SEPARATOR]) {
+        Some(i) => prefix.split_at(i + 1),
+        None => ("", prefix),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            if !file_name.starts_with(name)
+                || (file_name.starts_with('.') && !name.starts_with('.'))
+            {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!(
+                "{}{}{}",
+                dir,
+                file_name,
+                if is_dir { "/" } else { "" }
+            ))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Returns the `options` starting with `prefix`.
+fn matching(options: &[&str], prefix: &str) -> Vec<String> {
+    options
+        .iter()
+        .filter(|option| option.starts_with(prefix))
+        .map(|option| option.to_string())
+        .collect()
+}
+
+fn starts_with_ignore_case(name: &str, prefix: &str) -> bool {
+    name.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
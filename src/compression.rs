@@ -0,0 +1,131 @@
+//! Negotiated compression for file transfer chunks.
+//!
+//! Peers advertise the algorithms they support in a
+//! [`Capabilities`](crate::protocol::Capabilities) message when a connection
+//! starts. If both sides share an algorithm, outgoing chunks are compressed
+//! individually and sent as
+//! [`TransferMessage::CompressedChunk`](crate::protocol::TransferMessage::CompressedChunk).
+//!
+//! Only file transfer chunks are compressed. Other message frames are always
+//! sent as-is: chat lines are short, and encrypted ones would not shrink
+//! anyway.
+//!
+//! # Features
+//!
+//! - Deflate compression (via `flate2`), negotiated per connection
+//! - Chunks are only sent compressed when that actually saves space
+//! - Already-compressed media types are skipped without trying
+//! - Decompression is bounded to protect against decompression bombs
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rust_p2p_chat::compression;
+//! use rust_p2p_chat::protocol::Compression;
+//!
+//! let data = b"hello hello hello hello hello hello".repeat(100);
+//! let packed = compression::compress(Compression::Deflate, &data).unwrap();
+//! assert!(packed.len() < data.len());
+//!
+//! let unpacked = compression::decompress(Compression::Deflate, &packed, data.len()).unwrap();
+//! assert_eq!(unpacked, data);
+//! ```
+
+use crate::error::{ChatError, Result};
+use crate::file_transfer::FileTransfer;
+use crate::protocol::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+
+/// Algorithms supported by this build, in order of preference.
+pub const SUPPORTED: &[Compression] = &[Compression::Deflate];
+
+/// Media types from the default media list that are stored uncompressed and
+/// still benefit from compression.
+const COMPRESSIBLE_MEDIA: &[&str] = &["txt", "svg", "bmp", "wav", "doc"];
+
+/// Picks the first of our preferred algorithms that the peer also supports.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::compression;
+/// use rust_p2p_chat::protocol::Compression;
+///
+/// assert_eq!(
+///     compression::negotiate(compression::SUPPORTED, &[Compression::Deflate]),
+///     Some(Compression::Deflate)
+/// );
+/// assert_eq!(compression::negotiate(compression::SUPPORTED, &[]), None);
+/// ```
+pub fn negotiate(local: &[Compression], remote: &[Compression]) -> Option<Compression> {
+    local
+        .iter()
+        .copied()
+        .find(|algorithm| remote.contains(algorithm))
+}
+
+/// Returns `false` for files that are already compressed.
+///
+/// Files whose extension appears in `media_extensions` (images, video,
+/// audio, office documents) are assumed to be compressed, except for the
+/// few plain formats in the default list such as `txt` and `bmp`.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::compression;
+///
+/// let media = vec!["jpg".to_string(), "txt".to_string()];
+/// assert!(!compression::should_compress("photo.JPG", &media));
+/// assert!(compression::should_compress("notes.txt", &media));
+/// assert!(compression::should_compress("server.log", &media));
+/// ```
+pub fn should_compress(filename: &str, media_extensions: &[String]) -> bool {
+    if !FileTransfer::is_media_file(filename, media_extensions) {
+        return true;
+    }
+    let extension = filename.rsplit('.').next().unwrap_or_default();
+    COMPRESSIBLE_MEDIA
+        .iter()
+        .any(|compressible| compressible.eq_ignore_ascii_case(extension))
+}
+
+/// Compresses `data` with the given algorithm.
+///
+/// # Errors
+///
+/// - `ChatError::Io` if the encoder fails
+pub fn compress(algorithm: Compression, data: &[u8]) -> Result<Vec<u8>> {
+    match algorithm {
+        Compression::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+/// Decompresses `data`, refusing to produce more than `max_len` bytes.
+///
+/// # Errors
+///
+/// - `ChatError::InvalidMessage` if the data is corrupt or expands beyond
+///   `max_len`
+pub fn decompress(algorithm: Compression, data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let read = match algorithm {
+        Compression::Deflate => DeflateDecoder::new(data)
+            .take(max_len as u64 + 1)
+            .read_to_end(&mut output),
+    };
+    read.map_err(|e| ChatError::InvalidMessage(format!("Corrupt compressed data: {}", e)))?;
+    if output.len() > max_len {
+        return Err(ChatError::InvalidMessage(format!(
+            "Compressed data expands beyond {} bytes",
+            max_len
+        )));
+    }
+    Ok(output)
+}
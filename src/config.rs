@@ -11,6 +11,7 @@
 //! - Automatic fallback to sensible defaults
 //! - File path resolution for downloads and history
 //! - Media file extension configuration
+//! - Bandwidth limits for file transfers
 //!
 //! # Examples
 //!
@@ -27,10 +28,18 @@
 //! ```
 
 use crate::error::{ChatError, Result};
+use crate::peer::MAX_NICKNAME_LEN;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Log levels accepted in `log_level`.
+pub const LOG_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
 
 /// Application configuration structure.
 ///
@@ -74,6 +83,11 @@ pub struct Config {
     /// Valid range: 1024-65535 (privileged ports require elevated permissions).
     pub default_port: u16,
 
+    /// IP address to listen on, e.g. `192.168.1.5`, `::1` or `[::1]`.
+    /// `0.0.0.0` listens on all IPv4 interfaces, `::` on IPv4 and IPv6.
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+
     /// Size of the message buffer in bytes.
     /// Larger buffers can handle bigger messages but use more memory.
     pub buffer_size: usize,
@@ -90,16 +104,32 @@ pub struct Config {
     /// Uses exponential backoff starting from this value.
     pub reconnect_delay_secs: u64,
 
+    /// Seconds to wait for an outbound connection to be established.
+    /// Unroutable addresses fail after this long instead of hanging.
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+
+    /// Seconds a new peer has to complete the encryption handshake.
+    /// Peers that stall are disconnected. Only applies when encryption is enabled.
+    #[serde(default = "default_handshake_timeout_secs")]
+    pub handshake_timeout_secs: u64,
+
     /// Whether to enable end-to-end encryption by default.
     /// When true, all messages are encrypted using RSA + AES-256-GCM.
     pub enable_encryption: bool,
 
+    /// Whether to offer compression of file transfer chunks to peers.
+    /// Only used when the peer supports it too; media files are never compressed.
+    #[serde(default = "default_true")]
+    pub enable_compression: bool,
+
     /// Logging level for the application.
     /// Valid values: "trace", "debug", "info", "warn", "error"
     pub log_level: String,
 
     /// Whether to save chat history to a file.
     /// History includes messages, file transfers, and connection events.
+    /// Lines typed in the terminal are kept too.
     pub save_history: bool,
 
     /// Custom path for the chat history file.
@@ -110,6 +140,31 @@ pub struct Config {
     /// Files larger than this limit will be rejected.
     pub max_file_size_mb: u64,
 
+    /// Whether to accept incoming files and directories without asking.
+    /// When false, offers wait for `/accept <id>` or `/reject <id>`.
+    #[serde(default = "default_true")]
+    pub auto_accept_transfers: bool,
+
+    /// Maximum rate for sending file data, in kilobytes per second.
+    /// If None, uploads are unlimited. Chat messages are never throttled.
+    #[serde(default)]
+    pub max_upload_kbps: Option<u64>,
+
+    /// Maximum rate for receiving file data, in kilobytes per second.
+    /// If None, downloads are unlimited.
+    #[serde(default)]
+    pub max_download_kbps: Option<u64>,
+
+    /// Whether to announce this node and listen for other nodes on the LAN.
+    /// Announcements carry the nickname, listening port and identity fingerprint.
+    #[serde(default)]
+    pub enable_discovery: bool,
+
+    /// Multicast (or broadcast) address used for LAN discovery.
+    /// Every node on the same address and port sees the others.
+    #[serde(default = "default_discovery_address")]
+    pub discovery_address: String,
+
     /// Custom directory for downloaded files.
     /// If None, uses the system's Downloads folder.
     pub download_dir: Option<PathBuf>,
@@ -121,6 +176,11 @@ pub struct Config {
     /// File extensions that are considered "media" for auto-opening.
     /// Extensions are matched case-insensitively.
     pub media_extensions: Vec<String>,
+
+    /// Whether the GUI shows desktop notifications for new messages and
+    /// incoming files while its window is in the background.
+    #[serde(default = "default_true")]
+    pub desktop_notifications: bool,
 }
 
 impl Default for Config {
@@ -128,15 +188,24 @@ impl Default for Config {
         Config {
             nickname: None,
             default_port: 8080,
+            bind_address: default_bind_address(),
             buffer_size: 8192,
             heartbeat_interval_secs: 30,
             reconnect_attempts: 3,
             reconnect_delay_secs: 5,
+            connect_timeout_secs: default_connect_timeout_secs(),
+            handshake_timeout_secs: default_handshake_timeout_secs(),
             enable_encryption: true,
+            enable_compression: true,
             log_level: "info".to_string(),
             save_history: true,
             history_file: None,
             max_file_size_mb: 100,
+            auto_accept_transfers: true,
+            max_upload_kbps: None,
+            max_download_kbps: None,
+            enable_discovery: false,
+            discovery_address: default_discovery_address(),
             download_dir: None,
             auto_open_media: true,
             media_extensions: vec![
@@ -160,10 +229,31 @@ impl Default for Config {
                 "docx".to_string(),
                 "txt".to_string(),
             ],
+            desktop_notifications: true,
         }
     }
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_handshake_timeout_secs() -> u64 {
+    15
+}
+
+fn default_bind_address() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_discovery_address() -> String {
+    "239.255.77.77:7777".to_string()
+}
+
 impl Config {
     /// Loads configuration from the config file, or creates default if not found.
     ///
@@ -240,6 +330,98 @@ impl Config {
         Ok(())
     }
 
+    /// Checks that every setting has a usable value.
+    ///
+    /// [`load`](Self::load) accepts any well-formed file; frontends that let
+    /// the user edit settings call this before applying or saving them.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` describing the first invalid setting
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_p2p_chat::Config;
+    ///
+    /// let mut config = Config::default();
+    /// assert!(config.validate().is_ok());
+    ///
+    /// config.log_level = "loud".to_string();
+    /// assert!(config.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(ChatError::Configuration(message));
+
+        if let Some(nickname) = &self.nickname {
+            if nickname.trim().is_empty() || nickname.chars().any(char::is_control) {
+                return invalid(format!("Invalid nickname '{}'", nickname));
+            }
+            if nickname.chars().count() > MAX_NICKNAME_LEN {
+                return invalid(format!(
+                    "Nickname is longer than {} characters",
+                    MAX_NICKNAME_LEN
+                ));
+            }
+        }
+        self.listen_address(self.default_port)?;
+        if !(1024..=1024 * 1024).contains(&self.buffer_size) {
+            return invalid("Buffer size must be between 1 KB and 1 MB".to_string());
+        }
+        for (name, secs) in [
+            ("Heartbeat interval", self.heartbeat_interval_secs),
+            ("Connect timeout", self.connect_timeout_secs),
+            ("Handshake timeout", self.handshake_timeout_secs),
+        ] {
+            if secs == 0 {
+                return invalid(format!("{} must be at least 1 second", name));
+            }
+        }
+        if self.reconnect_attempts > 0 && self.reconnect_delay_secs == 0 {
+            return invalid("Reconnect delay must be at least 1 second".to_string());
+        }
+        if !LOG_LEVELS.contains(&self.log_level.to_lowercase().as_str()) {
+            return invalid(format!(
+                "Invalid log level '{}' - use one of {}",
+                self.log_level,
+                LOG_LEVELS.join(", ")
+            ));
+        }
+        if self.max_file_size_mb == 0 {
+            return invalid("Maximum file size must be at least 1 MB".to_string());
+        }
+        if self.max_upload_kbps == Some(0) || self.max_download_kbps == Some(0) {
+            return invalid("Bandwidth limits must be at least 1 KB/s".to_string());
+        }
+        if self.discovery_address.parse::<SocketAddr>().is_err() {
+            return invalid(format!(
+                "Invalid discovery address '{}' - use IP:PORT",
+                self.discovery_address
+            ));
+        }
+        if let Some(dir) = &self.download_dir {
+            if dir.exists() && !dir.is_dir() {
+                return invalid(format!("{} is not a directory", dir.display()));
+            }
+        }
+        if let Some(file) = &self.history_file {
+            if file.is_dir() {
+                return invalid(format!("{} is a directory", file.display()));
+            }
+        }
+        if let Some(extension) = self
+            .media_extensions
+            .iter()
+            .find(|ext| ext.is_empty() || ext.contains(|c: char| c == '.' || c.is_whitespace()))
+        {
+            return invalid(format!(
+                "Invalid media extension '{}' - use extensions like jpg, without the dot",
+                extension
+            ));
+        }
+        Ok(())
+    }
+
     /// Returns the platform-specific path for the configuration file.
     ///
     /// Uses the `directories` crate to find the appropriate config directory
@@ -285,6 +467,105 @@ impl Config {
             .map(|dirs| dirs.data_dir().join("chat_history.json"))
     }
 
+    /// Returns the path where lines typed in the terminal are remembered,
+    /// for recalling them with the arrow keys in later sessions.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if `save_history` is off or if platform directories
+    /// cannot be determined.
+    pub fn input_history_path(&self) -> Option<PathBuf> {
+        if !self.save_history {
+            return None;
+        }
+        ProjectDirs::from("com", "rustchat", "p2p-chat")
+            .map(|dirs| dirs.data_dir().join("input_history.txt"))
+    }
+
+    /// Returns the path of the log file written while the full-screen
+    /// interface owns the terminal, in the platform-specific data directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if platform directories cannot be determined.
+    pub fn log_path() -> Option<PathBuf> {
+        ProjectDirs::from("com", "rustchat", "p2p-chat")
+            .map(|dirs| dirs.data_dir().join("rust-p2p-chat.log"))
+    }
+
+    /// Returns the socket address to listen on for `port`.
+    ///
+    /// IPv6 addresses may be given with or without brackets.
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - Port to listen on
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` if `bind_address` is not an IP address
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_p2p_chat::Config;
+    ///
+    /// let mut config = Config::default();
+    /// config.bind_address = "::1".to_string();
+    /// let addr = config.listen_address(8080).unwrap();
+    /// assert_eq!(addr.to_string(), "[::1]:8080");
+    /// ```
+    pub fn listen_address(&self, port: u16) -> Result<SocketAddr> {
+        let address = self.bind_address.trim();
+        let address = address
+            .strip_prefix('[')
+            .and_then(|a| a.strip_suffix(']'))
+            .unwrap_or(address);
+        let ip: IpAddr = address.parse().map_err(|_| {
+            ChatError::Configuration(format!(
+                "Invalid bind address '{}' - use an IP address such as 0.0.0.0 or ::",
+                self.bind_address
+            ))
+        })?;
+        Ok(SocketAddr::new(ip, port))
+    }
+
+    /// Returns the path of the content cache used to skip repeated transfers.
+    ///
+    /// The cache maps SHA-256 hashes to files previously sent or received and
+    /// lives in the platform-specific data directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if platform directories cannot be determined.
+    pub fn content_cache_path(&self) -> Option<PathBuf> {
+        ProjectDirs::from("com", "rustchat", "p2p-chat")
+            .map(|dirs| dirs.data_dir().join("content_cache.json"))
+    }
+
+    /// Returns the path of the contacts file, next to `config.toml`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if platform directories cannot be determined.
+    pub fn contacts_path(&self) -> Option<PathBuf> {
+        ProjectDirs::from("com", "rustchat", "p2p-chat")
+            .map(|dirs| dirs.config_dir().join("contacts.toml"))
+    }
+
+    /// Returns the path of the identity key, next to `config.toml`.
+    ///
+    /// The identity key is reused across sessions so that peers see the
+    /// same fingerprint every time.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if platform directories cannot be determined.
+    pub fn identity_path(&self) -> Option<PathBuf> {
+        ProjectDirs::from("com", "rustchat", "p2p-chat")
+            .map(|dirs| dirs.config_dir().join("identity.pem"))
+    }
+
     /// Returns the directory where downloaded files should be saved.
     ///
     /// If a custom download directory is configured, returns that path.
@@ -326,3 +607,61 @@ impl Config {
         std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
     }
 }
+
+/// An inclusive range of ports to try when listening, e.g. `8080-8090`.
+///
+/// The first free port in the range is used, so several nodes on one
+/// machine can share a command line.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::config::PortRange;
+///
+/// let range: PortRange = "8080-8090".parse().unwrap();
+/// assert_eq!(range.ports().count(), 11);
+/// assert_eq!(range.to_string(), "8080-8090");
+///
+/// assert!("8090-8080".parse::<PortRange>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    /// First port to try.
+    pub start: u16,
+    /// Last port to try.
+    pub end: u16,
+}
+
+impl PortRange {
+    /// Returns the ports in the range, in the order they are tried.
+    pub fn ports(&self) -> RangeInclusive<u16> {
+        self.start..=self.end
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = ChatError;
+
+    /// Parses `START-END`, or a single port.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            ChatError::Configuration(format!(
+                "Invalid port range '{}' - use START-END (e.g., 8080-8090)",
+                s
+            ))
+        };
+        let (start, end) = s.split_once('-').unwrap_or((s, s));
+        let start: u16 = start.trim().parse().map_err(|_| invalid())?;
+        let end: u16 = end.trim().parse().map_err(|_| invalid())?;
+        if start == 0 || start > end {
+            return Err(invalid());
+        }
+        Ok(PortRange { start, end })
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
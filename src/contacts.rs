@@ -0,0 +1,312 @@
+//! Address book of named contacts.
+//!
+//! Contacts map a short name to a peer's address and, once known, the
+//! fingerprint of its identity key. They are stored in `contacts.toml` next
+//! to `config.toml`, so `--connect alice` can be used instead of typing an
+//! address every time.
+//!
+//! # Features
+//!
+//! - Persistent TOML storage alongside the configuration file
+//! - Case-insensitive lookup by name
+//! - Identity fingerprints recorded on first contact and checked afterwards
+//! - Last-seen times updated whenever a contact connects
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rust_p2p_chat::contacts::Contacts;
+//!
+//! let mut contacts = Contacts::in_memory();
+//! contacts
+//!     .add("alice", "192.168.1.100:8080", None)
+//!     .unwrap();
+//!
+//! assert_eq!(contacts.resolve("Alice"), "192.168.1.100:8080");
+//! assert_eq!(contacts.resolve("10.0.0.5:9000"), "10.0.0.5:9000");
+//! ```
+
+use crate::config::Config;
+use crate::error::{ChatError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// A saved peer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Contact {
+    /// Address to connect to, e.g. `192.168.1.100:8080`.
+    pub address: String,
+    /// Fingerprint of the contact's identity key, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// When the contact was last connected, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+}
+
+/// Result of matching a connected peer against the contacts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContactIdentity {
+    /// The peer's fingerprint matches the contact with this name.
+    Verified(String),
+    /// The peer is at a contact's address and its fingerprint was recorded
+    /// for the first time.
+    Learned(String),
+    /// The peer is at a contact's address but its fingerprint differs from
+    /// the one recorded. The contact is left unchanged.
+    Changed {
+        /// Name of the contact.
+        name: String,
+        /// The fingerprint recorded for the contact.
+        expected: String,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ContactsFile {
+    #[serde(default)]
+    contacts: BTreeMap<String, Contact>,
+}
+
+/// Named contacts, optionally persisted to a TOML file.
+#[derive(Debug, Default)]
+pub struct Contacts {
+    /// Where the contacts are persisted, if anywhere.
+    path: Option<PathBuf>,
+    /// Contacts by name.
+    entries: BTreeMap<String, Contact>,
+}
+
+impl Contacts {
+    /// Creates an address book that is never written to disk.
+    pub fn in_memory() -> Self {
+        Contacts::default()
+    }
+
+    /// Loads the contacts stored at `path`.
+    ///
+    /// A missing file results in an empty address book; a corrupt one is
+    /// logged and ignored. Changes are saved back to `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the TOML contacts file
+    pub fn load(path: &Path) -> Self {
+        let entries = match fs::read_to_string(path) {
+            Ok(contents) => match toml::from_str::<ContactsFile>(&contents) {
+                Ok(file) => file.contacts,
+                Err(e) => {
+                    warn!("Ignoring corrupt contacts file {}: {}", path.display(), e);
+                    BTreeMap::new()
+                }
+            },
+            Err(_) => BTreeMap::new(),
+        };
+        Contacts {
+            path: Some(path.to_path_buf()),
+            entries,
+        }
+    }
+
+    /// Loads the contacts file belonging to `config`.
+    ///
+    /// Falls back to an in-memory address book if the platform directories
+    /// cannot be determined.
+    pub fn load_for(config: &Config) -> Self {
+        config
+            .contacts_path()
+            .map(|path| Contacts::load(&path))
+            .unwrap_or_else(Contacts::in_memory)
+    }
+
+    /// Adds a contact, or updates the address of an existing one.
+    ///
+    /// When updating, a recorded fingerprint and last-seen time are kept
+    /// unless a new fingerprint is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name to refer to the contact by; may not contain spaces
+    /// * `address` - Address to connect to
+    /// * `fingerprint` - Expected identity fingerprint, if known
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` if the name or address is invalid, or
+    ///   the contacts file cannot be written
+    pub fn add(&mut self, name: &str, address: &str, fingerprint: Option<String>) -> Result<()> {
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(ChatError::Configuration(format!(
+                "Invalid contact name '{}'",
+                name
+            )));
+        }
+        if address.is_empty() {
+            return Err(ChatError::Configuration(
+                "Contact address cannot be empty".to_string(),
+            ));
+        }
+
+        let key = self.key(name).unwrap_or_else(|| name.to_string());
+        let contact = self.entries.entry(key).or_insert_with(|| Contact {
+            address: String::new(),
+            fingerprint: None,
+            last_seen: None,
+        });
+        contact.address = address.to_string();
+        if fingerprint.is_some() {
+            contact.fingerprint = fingerprint;
+        }
+        self.save()
+    }
+
+    /// Removes a contact.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a contact with that name existed.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` if the contacts file cannot be written
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let Some(key) = self.key(name) else {
+            return Ok(false);
+        };
+        self.entries.remove(&key);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Returns the contact with `name`, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&Contact> {
+        self.entries.get(&self.key(name)?)
+    }
+
+    /// Returns the address to connect to for `target`.
+    ///
+    /// If `target` names a contact its address is returned, otherwise
+    /// `target` is assumed to be an address already.
+    pub fn resolve(&self, target: &str) -> String {
+        match self.get(target) {
+            Some(contact) => contact.address.clone(),
+            None => target.to_string(),
+        }
+    }
+
+    /// Returns all contacts, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Contact)> {
+        self.entries.iter()
+    }
+
+    /// Returns the number of contacts.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no contacts.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Matches a connected peer against the contacts and records the visit.
+    ///
+    /// A contact with the same fingerprint is looked up first. Otherwise a
+    /// contact with the address we dialed is used: if it has no fingerprint
+    /// yet, the peer's fingerprint is recorded (trust on first use).
+    /// The last-seen time is updated unless the fingerprint has changed.
+    ///
+    /// A peer that connected to us comes from an ephemeral port rather than
+    /// the address saved for it, so it is only recognised by fingerprint; a
+    /// contact's fingerprint is learned the first time we connect to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - Address we dialed to reach the peer, or `None` if the
+    ///   peer connected to us
+    /// * `fingerprint` - Fingerprint of the peer's identity key
+    ///
+    /// # Returns
+    ///
+    /// How the peer relates to the contacts, or `None` if it is not a contact.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` if the contacts file cannot be written
+    pub fn record_seen(
+        &mut self,
+        address: Option<&str>,
+        fingerprint: &str,
+    ) -> Result<Option<ContactIdentity>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        if let Some((name, contact)) = self
+            .entries
+            .iter_mut()
+            .find(|(_, c)| c.fingerprint.as_deref() == Some(fingerprint))
+        {
+            contact.last_seen = Some(now);
+            let name = name.clone();
+            self.save()?;
+            return Ok(Some(ContactIdentity::Verified(name)));
+        }
+
+        let Some((name, contact)) = self
+            .entries
+            .iter_mut()
+            .find(|(_, c)| Some(c.address.as_str()) == address)
+        else {
+            return Ok(None);
+        };
+        let name = name.clone();
+        let identity = match &contact.fingerprint {
+            Some(expected) => ContactIdentity::Changed {
+                name,
+                expected: expected.clone(),
+            },
+            None => {
+                contact.fingerprint = Some(fingerprint.to_string());
+                contact.last_seen = Some(now);
+                self.save()?;
+                ContactIdentity::Learned(name)
+            }
+        };
+        Ok(Some(identity))
+    }
+
+    /// Returns the stored name matching `name` case-insensitively.
+    fn key(&self, name: &str) -> Option<String> {
+        if self.entries.contains_key(name) {
+            return Some(name.to_string());
+        }
+        self.entries
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                ChatError::Configuration(format!("Failed to create contacts directory: {}", e))
+            })?;
+        }
+        let file = ContactsFile {
+            contacts: self.entries.clone(),
+        };
+        let contents = toml::to_string_pretty(&file)
+            .map_err(|e| ChatError::Configuration(format!("Failed to encode contacts: {}", e)))?;
+        fs::write(path, contents)
+            .map_err(|e| ChatError::Configuration(format!("Failed to write contacts: {}", e)))
+    }
+}
@@ -0,0 +1,183 @@
+//! Content-addressed cache of files that were sent or received.
+//!
+//! Every file that completes a transfer is recorded under its SHA-256 hash.
+//! When a peer offers a file whose hash is already known, the
+//! [`TransferManager`](crate::transfer::TransferManager) copies the local file
+//! instead of transferring the data again.
+//!
+//! # Features
+//!
+//! - Persistent JSON storage in the platform data directory
+//! - Saving is split from updating, so the file can be written without
+//!   holding the cache (see [`ContentCache::snapshot`])
+//! - Entries for files that no longer exist are dropped on load
+//! - Stale entries are tolerated; callers verify hashes before reuse
+//! - Falls back to an in-memory cache if no path is available
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rust_p2p_chat::content_cache::ContentCache;
+//! use std::path::PathBuf;
+//!
+//! let mut cache = ContentCache::in_memory();
+//! cache.insert("abc123".to_string(), PathBuf::from("build/app.tar.gz"));
+//! assert_eq!(cache.get("abc123"), Some(&PathBuf::from("build/app.tar.gz")));
+//! ```
+
+use crate::error::{ChatError, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Maps SHA-256 hashes to local files with that content.
+#[derive(Debug, Default)]
+pub struct ContentCache {
+    /// Where the cache is persisted, if anywhere.
+    path: Option<PathBuf>,
+    /// Known files by content hash.
+    entries: HashMap<String, PathBuf>,
+    /// Number of changes made to `entries`.
+    changes: u64,
+    /// Value of `changes` at the last snapshot.
+    snapshotted: u64,
+    /// Value of `changes` in the cache file, shared by all snapshots so that
+    /// an older one never overwrites a newer one.
+    written: Arc<Mutex<u64>>,
+}
+
+/// The contents of a [`ContentCache`] waiting to be written to disk.
+#[derive(Debug)]
+pub struct Snapshot {
+    path: PathBuf,
+    contents: String,
+    changes: u64,
+    written: Arc<Mutex<u64>>,
+}
+
+impl ContentCache {
+    /// Creates a cache that is never written to disk.
+    pub fn in_memory() -> Self {
+        ContentCache::default()
+    }
+
+    /// Loads the cache stored at `path`.
+    ///
+    /// A missing or unreadable cache file results in an empty cache, since
+    /// the cache is only an optimization. Entries whose files no longer exist
+    /// are dropped. Changes are saved back to `path` by [`snapshot`](Self::snapshot).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the JSON cache file
+    pub fn load(path: &Path) -> Self {
+        let mut entries: HashMap<String, PathBuf> = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt content cache {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        let recorded = entries.len();
+        entries.retain(|_, file| file.exists());
+        ContentCache {
+            path: Some(path.to_path_buf()),
+            changes: (recorded - entries.len()) as u64,
+            entries,
+            ..ContentCache::default()
+        }
+    }
+
+    /// Returns the file recorded for `hash`, if any.
+    ///
+    /// The file may have been moved or modified since it was recorded.
+    pub fn get(&self, hash: &str) -> Option<&PathBuf> {
+        self.entries.get(hash)
+    }
+
+    /// Records that `path` has the content identified by `hash`.
+    pub fn insert(&mut self, hash: String, path: PathBuf) {
+        if self.entries.get(&hash) != Some(&path) {
+            self.entries.insert(hash, path);
+            self.changes += 1;
+        }
+    }
+
+    /// Forgets the file recorded for `hash`.
+    pub fn remove(&mut self, hash: &str) {
+        if self.entries.remove(hash).is_some() {
+            self.changes += 1;
+        }
+    }
+
+    /// Returns the number of recorded files.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no files are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Takes the changes made since the last snapshot, to be written with
+    /// [`Snapshot::save`] once the cache itself is no longer locked.
+    ///
+    /// # Returns
+    ///
+    /// `None` if nothing changed or the cache is not persisted.
+    pub fn snapshot(&mut self) -> Option<Snapshot> {
+        let path = self.path.clone()?;
+        if self.changes == self.snapshotted {
+            return None;
+        }
+        let contents = match serde_json::to_string_pretty(&self.entries) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Failed to encode content cache: {}", e);
+                return None;
+            }
+        };
+        self.snapshotted = self.changes;
+        Some(Snapshot {
+            path,
+            contents,
+            changes: self.changes,
+            written: self.written.clone(),
+        })
+    }
+}
+
+impl Snapshot {
+    /// Writes the snapshot on the blocking thread pool.
+    ///
+    /// Nothing is written if a newer snapshot of the same cache has already
+    /// been saved.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the cache file cannot be written
+    pub async fn save(self) -> Result<()> {
+        tokio::task::spawn_blocking(move || self.write())
+            .await
+            .map_err(|e| ChatError::FileTransfer(format!("Failed to write cache: {}", e)))?
+    }
+
+    fn write(&self) -> Result<()> {
+        let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        if *written >= self.changes {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                ChatError::FileTransfer(format!("Failed to create cache directory: {}", e))
+            })?;
+        }
+        fs::write(&self.path, &self.contents)
+            .map_err(|e| ChatError::FileTransfer(format!("Failed to write cache: {}", e)))?;
+        *written = self.changes;
+        Ok(())
+    }
+}
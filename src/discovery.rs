@@ -0,0 +1,301 @@
+//! Peer discovery on the local network.
+//!
+//! When enabled, every node periodically sends a small UDP announcement with
+//! its nickname, listening port and identity fingerprint to a multicast (or
+//! broadcast) address, and listens for the announcements of other nodes.
+//! Nodes found this way are listed by `/discover` and can be connected to
+//! with `/connect <nickname>`.
+//!
+//! Discovery is off by default and controlled by `enable_discovery` and
+//! `discovery_address` in [`Config`].
+//!
+//! # Features
+//!
+//! - IPv4 and IPv6 multicast, or broadcast on networks without multicast
+//! - Several nodes on one machine can share the discovery address
+//! - Peers that stop announcing are forgotten after [`PEER_TIMEOUT`]
+//! - Our own announcements are recognised by fingerprint and ignored
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rust_p2p_chat::discovery::{Announcement, Discovery};
+//! use rust_p2p_chat::Config;
+//!
+//! # async fn example() -> rust_p2p_chat::Result<()> {
+//! let mut config = Config::default();
+//! config.enable_discovery = true;
+//!
+//! let announcement = Announcement {
+//!     nickname: Some("Alice".to_string()),
+//!     port: 8080,
+//!     fingerprint: "0a1b:2c3d:4e5f:6a7b:8c9d:0e1f:2a3b:4c5d".to_string(),
+//! };
+//! let discovery = Discovery::from_config(&config, announcement)?;
+//! discovery.announce().await?;
+//!
+//! for peer in discovery.peers() {
+//!     println!("{} at {}", peer.display_name(), peer.address);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::Config;
+use crate::error::{ChatError, Result};
+use crate::peer::MAX_NICKNAME_LEN;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// How often a node announces itself.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a peer is listed after its last announcement.
+pub const PEER_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Prefix of every announcement, so unrelated traffic on the port is ignored.
+const MAGIC: &[u8; 6] = b"P2PCD1";
+
+/// Largest datagram we accept.
+const MAX_PACKET_SIZE: usize = 512;
+
+/// What a node tells the local network about itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Announcement {
+    /// The node's nickname, if it has one.
+    pub nickname: Option<String>,
+    /// Port the node accepts chat connections on.
+    pub port: u16,
+    /// Fingerprint of the node's identity key.
+    pub fingerprint: String,
+}
+
+impl Announcement {
+    /// Encodes the announcement as a datagram.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Protocol` if the announcement cannot be serialized
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut packet = MAGIC.to_vec();
+        bincode::serialize_into(&mut packet, self)
+            .map_err(|e| ChatError::Protocol(format!("Failed to encode announcement: {}", e)))?;
+        Ok(packet)
+    }
+
+    /// Decodes a datagram, returning `None` if it is not an announcement.
+    pub fn decode(packet: &[u8]) -> Option<Self> {
+        let payload = packet.strip_prefix(MAGIC.as_slice())?;
+        bincode::deserialize(payload).ok()
+    }
+}
+
+/// A node found on the local network.
+#[derive(Debug, Clone)]
+pub struct DiscoveredPeer {
+    /// The node's nickname, if it announced one.
+    pub nickname: Option<String>,
+    /// Address to connect to: the sender's IP and the announced port.
+    pub address: SocketAddr,
+    /// Fingerprint of the node's identity key.
+    pub fingerprint: String,
+    /// When the last announcement arrived.
+    pub last_seen: Instant,
+}
+
+impl DiscoveredPeer {
+    /// Returns the nickname, or the address if the node has none.
+    pub fn display_name(&self) -> String {
+        self.nickname
+            .clone()
+            .unwrap_or_else(|| self.address.to_string())
+    }
+}
+
+/// Announces this node and collects the announcements of others.
+#[derive(Debug)]
+pub struct Discovery {
+    socket: UdpSocket,
+    /// Where announcements are sent.
+    target: SocketAddr,
+    announcement: Mutex<Announcement>,
+    /// Discovered peers by fingerprint.
+    peers: Mutex<HashMap<String, DiscoveredPeer>>,
+}
+
+impl Discovery {
+    /// Creates a discovery service listening on `listen` and announcing to
+    /// `target`.
+    ///
+    /// The socket allows address reuse so several nodes on one machine can
+    /// listen on the same port. If `target` is a multicast address the group
+    /// is joined on all interfaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `listen` - Local address to receive announcements on
+    /// * `target` - Multicast, broadcast or unicast address to announce to
+    /// * `announcement` - What to announce about this node
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::BindFailed` if the socket cannot be set up
+    pub fn bind(
+        listen: SocketAddr,
+        target: SocketAddr,
+        announcement: Announcement,
+    ) -> Result<Self> {
+        let bind_failed = |e| ChatError::BindFailed(listen.to_string(), e);
+        let socket = Socket::new(
+            Domain::for_address(listen),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )
+        .map_err(bind_failed)?;
+        socket.set_reuse_address(true).map_err(bind_failed)?;
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(true).map_err(bind_failed)?;
+        socket.set_nonblocking(true).map_err(bind_failed)?;
+        socket.bind(&listen.into()).map_err(bind_failed)?;
+
+        match target.ip() {
+            IpAddr::V4(group) if group.is_multicast() => socket
+                .join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)
+                .map_err(bind_failed)?,
+            IpAddr::V6(group) if group.is_multicast() => {
+                socket.join_multicast_v6(&group, 0).map_err(bind_failed)?
+            }
+            IpAddr::V4(_) => socket.set_broadcast(true).map_err(bind_failed)?,
+            IpAddr::V6(_) => {}
+        }
+
+        let socket = UdpSocket::from_std(socket.into()).map_err(bind_failed)?;
+        Ok(Discovery {
+            socket,
+            target,
+            announcement: Mutex::new(announcement),
+            peers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Creates a discovery service for the address in `config`.
+    ///
+    /// Listens on the discovery port on all interfaces and announces to
+    /// `config.discovery_address`.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` if the discovery address is invalid
+    /// - `ChatError::BindFailed` if the socket cannot be set up
+    pub fn from_config(config: &Config, announcement: Announcement) -> Result<Self> {
+        let target: SocketAddr = config.discovery_address.parse().map_err(|_| {
+            ChatError::Configuration(format!(
+                "Invalid discovery address '{}'",
+                config.discovery_address
+            ))
+        })?;
+        let any = match target {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        Discovery::bind(SocketAddr::new(any, target.port()), target, announcement)
+    }
+
+    /// Returns the address the service receives announcements on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Sends our announcement once.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Io` if the datagram cannot be sent
+    pub async fn announce(&self) -> Result<()> {
+        let packet = self.announcement.lock().unwrap().encode()?;
+        self.socket.send_to(&packet, self.target).await?;
+        Ok(())
+    }
+
+    /// Changes the nickname included in future announcements.
+    pub fn set_nickname(&self, nickname: Option<String>) {
+        self.announcement.lock().unwrap().nickname = nickname;
+    }
+
+    /// Waits for the next announcement from another node.
+    ///
+    /// Datagrams that are not announcements, and our own announcements, are
+    /// skipped.
+    ///
+    /// # Returns
+    ///
+    /// The peer that announced itself, and whether it was not listed before.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Io` if receiving fails
+    pub async fn receive(&self) -> Result<(DiscoveredPeer, bool)> {
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        loop {
+            let (len, from) = self.socket.recv_from(&mut buffer).await?;
+            if let Some(found) = self.handle(&buffer[..len], from) {
+                return Ok(found);
+            }
+        }
+    }
+
+    /// Returns the peers heard from within [`PEER_TIMEOUT`], sorted by name.
+    pub fn peers(&self) -> Vec<DiscoveredPeer> {
+        let mut peers = self.peers.lock().unwrap();
+        peers.retain(|_, peer| peer.last_seen.elapsed() < PEER_TIMEOUT);
+        let mut found: Vec<DiscoveredPeer> = peers.values().cloned().collect();
+        found.sort_by_key(|peer| peer.display_name().to_lowercase());
+        found
+    }
+
+    /// Finds a discovered peer by nickname (ignoring case) or fingerprint.
+    pub fn find(&self, name: &str) -> Option<DiscoveredPeer> {
+        self.peers().into_iter().find(|peer| {
+            peer.fingerprint == name
+                || peer
+                    .nickname
+                    .as_deref()
+                    .is_some_and(|nick| nick.eq_ignore_ascii_case(name))
+        })
+    }
+
+    fn handle(&self, packet: &[u8], from: SocketAddr) -> Option<(DiscoveredPeer, bool)> {
+        let announcement = Announcement::decode(packet)?;
+        if announcement.fingerprint == self.announcement.lock().unwrap().fingerprint {
+            return None;
+        }
+
+        let nickname = announcement.nickname.and_then(|nick| {
+            let nick: String = nick
+                .chars()
+                .filter(|c| !c.is_control())
+                .take(MAX_NICKNAME_LEN)
+                .collect();
+            let nick = nick.trim();
+            (!nick.is_empty()).then(|| nick.to_string())
+        });
+        let peer = DiscoveredPeer {
+            nickname,
+            address: SocketAddr::new(from.ip(), announcement.port),
+            fingerprint: announcement.fingerprint,
+            last_seen: Instant::now(),
+        };
+
+        let mut peers = self.peers.lock().unwrap();
+        let is_new = peers
+            .get(&peer.fingerprint)
+            .is_none_or(|known| known.last_seen.elapsed() >= PEER_TIMEOUT);
+        peers.insert(peer.fingerprint.clone(), peer.clone());
+        Some((peer, is_new))
+    }
+}
@@ -5,8 +5,13 @@ use aes_gcm::{
 };
 use base64::{engine::general_purpose, Engine as _};
 use rand::rngs::OsRng;
-use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
+use rsa::pkcs8::{
+    DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding,
+};
 use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 use tokio_rustls::rustls::{self, Certificate, PrivateKey};
 use tokio_rustls::{TlsAcceptor, TlsConnector};
@@ -70,26 +75,88 @@ pub struct E2EEncryption {
 
     // Cipher for encryption/decryption
     cipher: Option<Aes256Gcm>,
+
+    // Fresh random challenge the peer must sign to prove its identity
+    challenge: [u8; 32],
+
+    // Peer's challenge, which we sign to prove ours
+    peer_challenge: Option<Vec<u8>>,
+
+    // Set once the peer has proven it holds its private key
+    peer_verified: bool,
 }
 
 impl E2EEncryption {
     /// Create new encryption handler with 1024-bit RSA keys
     pub fn new() -> Result<Self> {
-        let mut rng = OsRng;
+        Ok(Self::with_identity(Self::generate_identity()?))
+    }
 
-        // Generate 1024-bit RSA keypair
-        let bits = 1024;
-        let private_key = RsaPrivateKey::new(&mut rng, bits)
-            .map_err(|e| ChatError::Encryption(format!("Failed to generate RSA key: {}", e)))?;
+    /// Create encryption handler using an existing identity key
+    pub fn with_identity(private_key: RsaPrivateKey) -> Self {
+        use rand::RngCore;
         let public_key = RsaPublicKey::from(&private_key);
-
-        Ok(Self {
+        let mut challenge = [0u8; 32];
+        OsRng.fill_bytes(&mut challenge);
+        Self {
             private_key,
             public_key,
             peer_public_key: None,
             aes_key: None,
             cipher: None,
-        })
+            challenge,
+            peer_challenge: None,
+            peer_verified: false,
+        }
+    }
+
+    /// Generate a new 1024-bit RSA identity key
+    pub fn generate_identity() -> Result<RsaPrivateKey> {
+        let mut rng = OsRng;
+        let bits = 1024;
+        RsaPrivateKey::new(&mut rng, bits)
+            .map_err(|e| ChatError::Encryption(format!("Failed to generate RSA key: {}", e)))
+    }
+
+    /// Load the identity key stored at `path`, creating it if missing.
+    ///
+    /// Keeping the same key across sessions gives peers a stable
+    /// fingerprint to recognise us by. The key is stored as PKCS#8 PEM.
+    /// A key file that exists but cannot be read is an error and is left
+    /// untouched.
+    pub fn load_or_create_identity(path: &Path) -> Result<RsaPrivateKey> {
+        match fs::read_to_string(path) {
+            Ok(pem) => {
+                return RsaPrivateKey::from_pkcs8_pem(&pem).map_err(|e| {
+                    ChatError::Encryption(format!("Failed to parse identity key: {}", e))
+                });
+            }
+            // Only a missing key is replaced; an unreadable one is reported
+            // rather than overwritten, which would change our fingerprint.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(ChatError::Configuration(format!(
+                    "Failed to read identity key {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        }
+
+        let private_key = Self::generate_identity()?;
+        let pem = private_key
+            .to_pkcs8_pem(LineEnding::LF)
+            .map_err(|e| ChatError::Encryption(format!("Failed to encode identity key: {}", e)))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, pem.as_bytes())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(private_key)
     }
 
     /// Get our public key as base64-encoded string for exchange
@@ -101,7 +168,21 @@ impl E2EEncryption {
         Ok(general_purpose::STANDARD.encode(public_key_der.as_bytes()))
     }
 
+    /// Get the fingerprint of our public key
+    pub fn fingerprint(&self) -> Result<String> {
+        fingerprint(&self.get_public_key_base64()?)
+    }
+
+    /// Get the fingerprint of the peer's public key, once it is known
+    pub fn peer_fingerprint(&self) -> Option<String> {
+        let der = self.peer_public_key.as_ref()?.to_public_key_der().ok()?;
+        Some(fingerprint_der(der.as_bytes()))
+    }
+
     /// Set peer's public key from base64-encoded string
+    ///
+    /// The peer is not verified until it has signed our challenge with
+    /// this key, see [`E2EEncryption::verify_identity_proof`].
     pub fn set_peer_public_key(&mut self, key_base64: &str) -> Result<()> {
         let key_bytes = general_purpose::STANDARD
             .decode(key_base64)
@@ -111,10 +192,124 @@ impl E2EEncryption {
             Some(RsaPublicKey::from_public_key_der(&key_bytes).map_err(|e| {
                 ChatError::Encryption(format!("Failed to parse public key: {}", e))
             })?);
+        self.peer_verified = false;
+
+        Ok(())
+    }
+
+    /// Get our challenge as base64-encoded string for exchange
+    pub fn challenge_base64(&self) -> String {
+        general_purpose::STANDARD.encode(self.challenge)
+    }
+
+    /// Set the peer's challenge from base64-encoded string
+    pub fn set_peer_challenge(&mut self, challenge_base64: &str) -> Result<()> {
+        let challenge = general_purpose::STANDARD
+            .decode(challenge_base64)
+            .map_err(|e| ChatError::Encryption(format!("Failed to decode challenge: {}", e)))?;
+        if challenge.len() != self.challenge.len() {
+            return Err(ChatError::Encryption("Invalid challenge size".to_string()));
+        }
+        self.peer_challenge = Some(challenge);
+        Ok(())
+    }
+
+    /// Whether we pick the session key, which the side with the greater
+    /// public key does. Peers sharing an identity key compare challenges.
+    pub fn picks_session_key(&self) -> Result<bool> {
+        let peer_key = self
+            .peer_public_key
+            .as_ref()
+            .ok_or_else(|| ChatError::Encryption("Peer public key not set".to_string()))?
+            .to_public_key_der()
+            .map_err(|e| ChatError::Encryption(format!("Failed to encode public key: {}", e)))?;
+        let peer_key = general_purpose::STANDARD.encode(peer_key.as_bytes());
+        let peer_challenge = self
+            .peer_challenge
+            .as_deref()
+            .ok_or_else(|| ChatError::Encryption("Peer challenge not set".to_string()))?;
+        Ok((self.get_public_key_base64()?, &self.challenge[..]) > (peer_key, peer_challenge))
+    }
+
+    /// Sign the handshake transcript with our identity key.
+    ///
+    /// The signature proves to the peer that we hold the private key behind
+    /// the public key we sent, for this connection and this session key.
+    pub fn identity_proof(&self) -> Result<String> {
+        use rsa::sha2::Sha256;
+        use rsa::signature::{SignatureEncoding, Signer};
+
+        let transcript = self.transcript(&self.challenge)?;
+        let signing_key = rsa::pkcs1v15::SigningKey::<Sha256>::new(self.private_key.clone());
+        Ok(general_purpose::STANDARD.encode(signing_key.sign(&transcript).to_vec()))
+    }
+
+    /// Check the peer's signature over the handshake transcript.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Encryption` if the handshake is incomplete or the
+    ///   signature was not made with the peer's key over this transcript
+    pub fn verify_identity_proof(&mut self, signature_base64: &str) -> Result<()> {
+        use rsa::sha2::Sha256;
+        use rsa::signature::Verifier;
+
+        let peer_challenge = self
+            .peer_challenge
+            .clone()
+            .ok_or_else(|| ChatError::Encryption("Handshake not complete".to_string()))?;
+        let transcript = self.transcript(&peer_challenge)?;
+        let peer_key = self
+            .peer_public_key
+            .as_ref()
+            .ok_or_else(|| ChatError::Encryption("Peer public key not set".to_string()))?;
+        let signature_bytes = general_purpose::STANDARD
+            .decode(signature_base64)
+            .map_err(|e| ChatError::Encryption(format!("Failed to decode signature: {}", e)))?;
+        let signature = rsa::pkcs1v15::Signature::try_from(signature_bytes.as_slice())
+            .map_err(|e| ChatError::Encryption(format!("Invalid signature format: {}", e)))?;
 
+        rsa::pkcs1v15::VerifyingKey::<Sha256>::new(peer_key.clone())
+            .verify(&transcript, &signature)
+            .map_err(|_| ChatError::Encryption("Identity proof does not match".to_string()))?;
+        self.peer_verified = true;
         Ok(())
     }
 
+    /// Both public keys and challenges, in key order so both sides build
+    /// the same bytes, followed by the hash of the session key and the
+    /// challenge of the side that signs it
+    fn transcript(&self, signer: &[u8]) -> Result<Vec<u8>> {
+        let incomplete = || ChatError::Encryption("Handshake not complete".to_string());
+        let peer_key = self.peer_public_key.as_ref().ok_or_else(incomplete)?;
+        let peer_challenge = self.peer_challenge.as_ref().ok_or_else(incomplete)?;
+        let aes_key = self.aes_key.as_ref().ok_or_else(incomplete)?;
+
+        let encode = |key: &RsaPublicKey| {
+            key.to_public_key_der()
+                .map(|der| der.as_bytes().to_vec())
+                .map_err(|e| ChatError::Encryption(format!("Failed to encode public key: {}", e)))
+        };
+        let ours = (encode(&self.public_key)?, self.challenge.to_vec());
+        let theirs = (encode(peer_key)?, peer_challenge.clone());
+        let (first, second) = if ours <= theirs {
+            (ours, theirs)
+        } else {
+            (theirs, ours)
+        };
+
+        let mut transcript = b"rust-p2p-chat identity proof v1".to_vec();
+        for part in [&first.0, &second.0, &first.1, &second.1] {
+            transcript.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            transcript.extend_from_slice(part);
+        }
+        transcript.extend_from_slice(&Sha256::digest(aes_key));
+        // Names the signer, so that a peer with our key cannot send our own
+        // proof back to us
+        transcript.extend_from_slice(signer);
+        Ok(transcript)
+    }
+
     /// Generate and set shared AES key
     pub fn generate_shared_key(&mut self) -> Result<String> {
         // Generate random 256-bit AES key
@@ -125,6 +320,7 @@ impl E2EEncryption {
         // Store the key
         self.aes_key = Some(*Key::<Aes256Gcm>::from_slice(&key_bytes));
         self.cipher = Some(Aes256Gcm::new(self.aes_key.as_ref().unwrap()));
+        self.peer_verified = false;
 
         // Encrypt the key with peer's public key
         let peer_key = self
@@ -159,6 +355,7 @@ impl E2EEncryption {
 
         self.aes_key = Some(*Key::<Aes256Gcm>::from_slice(&key_array));
         self.cipher = Some(Aes256Gcm::new(self.aes_key.as_ref().unwrap()));
+        self.peer_verified = false;
 
         Ok(())
     }
@@ -222,6 +419,11 @@ impl E2EEncryption {
         self.cipher.is_some()
     }
 
+    /// Check if encryption is ready and the peer has proven its identity
+    pub fn is_verified(&self) -> bool {
+        self.is_ready() && self.peer_verified
+    }
+
     /// Generate signature for a message
     pub fn sign_message(&self, message: &str) -> Result<String> {
         use rsa::sha2::Sha256;
@@ -255,6 +457,84 @@ impl E2EEncryption {
     }
 }
 
+/// Returns a short, human-comparable fingerprint of a public key.
+///
+/// The fingerprint is the first 128 bits of the SHA-256 hash of the key's
+/// DER encoding, as eight colon-separated groups of hex digits, e.g.
+/// `3f9a:12bc:...`.
+///
+/// # Arguments
+///
+/// * `public_key_base64` - Key as returned by
+///   [`E2EEncryption::get_public_key_base64`]
+///
+/// # Errors
+///
+/// - `ChatError::Encryption` if the key is not valid base64
+pub fn fingerprint(public_key_base64: &str) -> Result<String> {
+    let der = general_purpose::STANDARD
+        .decode(public_key_base64)
+        .map_err(|e| ChatError::Encryption(format!("Failed to decode public key: {}", e)))?;
+    Ok(fingerprint_der(&der))
+}
+
+fn fingerprint_der(der: &[u8]) -> String {
+    let hash = Sha256::digest(der);
+    hash[..16]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Computes the safety number two peers compare to verify each other.
+///
+/// The number is derived from both key fingerprints, in sorted order, so
+/// both sides compute the same value. It is rendered as twelve groups of
+/// five digits that can be read out over another channel; if they match,
+/// neither connection was intercepted.
+///
+/// # Arguments
+///
+/// * `ours` - Fingerprint of our public key
+/// * `theirs` - Fingerprint of the peer's public key
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::encryption::safety_number;
+///
+/// let a = "3f9a:12bc:0000:0000:0000:0000:0000:0001";
+/// let b = "77de:4410:0000:0000:0000:0000:0000:0002";
+/// assert_eq!(safety_number(a, b), safety_number(b, a));
+/// assert_eq!(safety_number(a, b).split(' ').count(), 12);
+/// ```
+pub fn safety_number(ours: &str, theirs: &str) -> String {
+    let (first, second) = if ours <= theirs {
+        (ours, theirs)
+    } else {
+        (theirs, ours)
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(first.as_bytes());
+    hasher.update(b"|");
+    hasher.update(second.as_bytes());
+    let hash = hasher.finalize();
+    // Twelve groups from 5-digit reductions of 20-bit chunks of the hash
+    let bits = hash.iter().fold(Vec::with_capacity(256), |mut bits, byte| {
+        bits.extend((0..8).rev().map(|i| (byte >> i) & 1));
+        bits
+    });
+    bits.chunks(20)
+        .take(12)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u32, |acc, bit| (acc << 1) | *bit as u32);
+            format!("{:05}", value % 100_000)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,7 +64,7 @@ use std::io;
 ///     "127.0.0.1:80".to_string(),
 ///     io::Error::from(io::ErrorKind::PermissionDenied)
 /// );
-/// println!("{}", error); // "Cannot use port 127.0.0.1:80 - permission denied (try a port above 1024)"
+/// println!("{}", error); // "Cannot use 127.0.0.1:80 - permission denied (try a port above 1024)"
 ///
 /// // Simple peer disconnection
 /// let error = ChatError::PeerDisconnected;
@@ -161,11 +161,15 @@ impl fmt::Display for ChatError {
             ChatError::PeerDisconnected => write!(f, "Your chat partner disconnected"),
             ChatError::BindFailed(addr, e) => match e.kind() {
                 io::ErrorKind::AddrInUse => {
-                    write!(f, "Port {} is already in use - try a different port", addr)
+                    write!(
+                        f,
+                        "Address {} is already in use - try a different port, or port 0 for any free one",
+                        addr
+                    )
                 }
                 io::ErrorKind::PermissionDenied => write!(
                     f,
-                    "Cannot use port {} - permission denied (try a port above 1024)",
+                    "Cannot use {} - permission denied (try a port above 1024)",
                     addr
                 ),
                 _ => write!(f, "Cannot start server on {}: {}", addr, e),
@@ -176,6 +180,11 @@ impl fmt::Display for ChatError {
                     "Cannot reach {} - peer may not be running or firewall blocking",
                     addr
                 ),
+                io::ErrorKind::TimedOut if e.to_string().contains("handshake") => write!(
+                    f,
+                    "Peer at {} did not complete the encryption handshake in time - it may be an incompatible client",
+                    addr
+                ),
                 io::ErrorKind::TimedOut => write!(
                     f,
                     "Connection to {} timed out - check IP address and network",
@@ -183,7 +192,7 @@ impl fmt::Display for ChatError {
                 ),
                 io::ErrorKind::InvalidInput => write!(
                     f,
-                    "Invalid address '{}' - use format IP:PORT (e.g., 192.168.1.100:8080)",
+                    "Invalid address '{}' - use format IP:PORT (e.g., 192.168.1.100:8080 or [::1]:8080)",
                     addr
                 ),
                 _ => write!(f, "Cannot connect to {}: {}", addr, e),
@@ -0,0 +1,140 @@
+//! Events reported by a running chat session.
+//!
+//! The session publishes a [`ChatEvent`] for everything a frontend needs to
+//! show: the listening address, peers joining and leaving, their identities,
+//! incoming messages, encryption and file transfers. The session itself never
+//! writes to the terminal; the terminal UI and the GUI both render these
+//! events. Frontends receive them from a
+//! [`ChatSession`](crate::session::ChatSession).
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rust_p2p_chat::events::ChatEvent;
+//! use rust_p2p_chat::session::ChatSession;
+//! use rust_p2p_chat::{Config, P2PChat};
+//!
+//! #[tokio::main]
+//! async fn main() -> rust_p2p_chat::Result<()> {
+//!     let chat = P2PChat::new(Config::default())?;
+//!     let mut session = ChatSession::start(chat, 8080, None);
+//!
+//!     while let Some(event) = session.next_event().await {
+//!         if let ChatEvent::MessageReceived { from, text, .. } = event {
+//!             println!("{}: {}", from, text);
+//!         }
+//!     }
+//!     session.wait().await
+//! }
+//! ```
+
+use crate::contacts::ContactIdentity;
+use crate::discovery::DiscoveredPeer;
+use crate::transfer::{TransferEvent, TransferStatus};
+use std::net::SocketAddr;
+
+/// Number of events buffered for each subscriber before old ones are dropped.
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// Something that happened in a chat session.
+#[derive(Debug, Clone)]
+pub enum ChatEvent {
+    /// The session accepts connections on this address.
+    Listening(SocketAddr),
+    /// Fingerprint of our own identity key.
+    LocalIdentity(String),
+    /// We are dialing a peer at this address.
+    Connecting(String),
+    /// A peer connected.
+    PeerConnected {
+        /// Peer ID, as used by `/msg` and `/disconnect`.
+        id: String,
+        /// Address of the connection.
+        address: SocketAddr,
+        /// Whether we dialed the peer, rather than accepted its connection.
+        outgoing: bool,
+    },
+    /// A peer announced a nickname.
+    PeerRenamed {
+        /// Peer ID.
+        id: String,
+        /// The name the peer was shown with before.
+        previous: String,
+        /// The name now used for the peer.
+        name: String,
+    },
+    /// A peer disconnected.
+    PeerDisconnected {
+        /// Peer ID.
+        id: String,
+        /// The peer's display name.
+        name: String,
+        /// `true` if the peer said goodbye, `false` if the connection was
+        /// lost or we closed it.
+        left: bool,
+    },
+    /// A peer sent its identity key.
+    PeerIdentity {
+        /// Peer ID.
+        id: String,
+        /// The peer's display name.
+        name: String,
+        /// Fingerprint of the peer's identity key.
+        fingerprint: String,
+        /// How the key matches our contacts, if the peer is a contact.
+        contact: Option<ContactIdentity>,
+    },
+    /// The encryption handshake with a peer completed.
+    EncryptionEnabled {
+        /// Peer ID.
+        id: String,
+        /// The peer's display name.
+        name: String,
+    },
+    /// A chat message arrived.
+    MessageReceived {
+        /// Display name of the sender. A relayed message is shown as
+        /// `via <relay> (from <origin>, unverified)`, because the relay
+        /// names the origin.
+        from: String,
+        /// The message text.
+        text: String,
+        /// Whether the message was end-to-end encrypted. Relays read the
+        /// messages they forward, so this is `false` for relayed messages.
+        encrypted: bool,
+        /// Whether the message was sent only to us with `/msg`.
+        private: bool,
+    },
+    /// A chat message we typed was sent.
+    MessageSent {
+        /// The peer a private message went to, or `None` if it went to all.
+        to: Option<String>,
+        /// The message text.
+        text: String,
+        /// Whether every peer it went to received it encrypted.
+        encrypted: bool,
+    },
+    /// Progress of a file transfer from a peer.
+    Transfer {
+        /// Display name of the peer.
+        from: String,
+        /// What happened.
+        event: TransferEvent,
+    },
+    /// Snapshot of a transfer in either direction, published while its
+    /// progress or state changes, including once it has finished.
+    TransferProgress {
+        /// Display name of the peer on the other end.
+        peer: String,
+        /// The transfer's current status.
+        status: TransferStatus,
+    },
+    /// A node was found on the local network for the first time.
+    PeerDiscovered(DiscoveredPeer),
+    /// Informational text, such as the response to a command.
+    Notice(String),
+    /// Something the user should look at, but not a failure.
+    Warning(String),
+    /// Something went wrong.
+    Error(String),
+}
@@ -44,15 +44,14 @@ use crate::error::{ChatError, Result};
 use crate::protocol::{FileInfo, Message, MessageType, StatusUpdate};
 use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-/// Chunk size for file transfer operations.
-/// Currently unused but reserved for future streaming implementations.
-#[allow(dead_code)]
-const CHUNK_SIZE: usize = 8192;
+/// Chunk size for streamed file transfer operations.
+/// Each chunk travels as one `TransferMessage::Chunk` frame.
+pub const CHUNK_SIZE: usize = 64 * 1024;
 
 /// File transfer manager with integrity verification and size limits.
 ///
@@ -260,7 +259,7 @@ impl FileTransfer {
             ChatError::FileTransfer(format!("Failed to create download directory: {}", e))
         })?;
 
-        let file_path = download_dir.join(&file_info.name);
+        let file_path = download_dir.join(Self::sanitize_file_name(&file_info.name)?);
         let mut file = File::create(&file_path).await?;
         file.write_all(&file_info.data).await?;
         file.flush().await?;
@@ -268,6 +267,114 @@ impl FileTransfer {
         Ok(file_path)
     }
 
+    /// Computes the SHA-256 hash of a file without loading it into memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file to hash
+    ///
+    /// # Returns
+    ///
+    /// Returns the lowercase hex digest, matching the format of `FileInfo::hash`.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Io` if the file cannot be opened or read
+    pub async fn hash_file(path: &Path) -> Result<String> {
+        let mut file = File::open(path).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        loop {
+            let n = file.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Reduces a peer-supplied file name to a safe, bare file name.
+    ///
+    /// Peers control the names of the files they send, so any directory
+    /// components are stripped to prevent writing outside the download
+    /// directory (e.g. `../../.bashrc` becomes `.bashrc`).
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if no usable file name remains
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_p2p_chat::file_transfer::FileTransfer;
+    ///
+    /// assert_eq!(FileTransfer::sanitize_file_name("photo.jpg").unwrap(), "photo.jpg");
+    /// assert_eq!(FileTransfer::sanitize_file_name("../../etc/passwd").unwrap(), "passwd");
+    /// assert!(FileTransfer::sanitize_file_name("..").is_err());
+    /// ```
+    pub fn sanitize_file_name(name: &str) -> Result<String> {
+        let normalized = name.replace('\\', "/");
+        let file_name = Path::new(&normalized)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if file_name.is_empty() || file_name == "." || file_name == ".." {
+            return Err(ChatError::FileTransfer(format!(
+                "Invalid file name: {}",
+                name
+            )));
+        }
+        Ok(file_name)
+    }
+
+    /// Validates a peer-supplied relative path from a directory manifest.
+    ///
+    /// Unlike [`sanitize_file_name`](Self::sanitize_file_name), directory
+    /// components are kept so the tree can be recreated, but every component
+    /// must be a plain name. Absolute paths, `.` and `..` components and empty
+    /// segments are rejected outright rather than stripped.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the path could escape the target directory
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_p2p_chat::file_transfer::FileTransfer;
+    /// use std::path::PathBuf;
+    ///
+    /// assert_eq!(
+    ///     FileTransfer::sanitize_relative_path("2024/photo.jpg").unwrap(),
+    ///     PathBuf::from("2024").join("photo.jpg")
+    /// );
+    /// assert!(FileTransfer::sanitize_relative_path("../outside.txt").is_err());
+    /// assert!(FileTransfer::sanitize_relative_path("/etc/passwd").is_err());
+    /// ```
+    pub fn sanitize_relative_path(path: &str) -> Result<PathBuf> {
+        let invalid = || ChatError::FileTransfer(format!("Invalid path in manifest: {}", path));
+        let normalized = path.replace('\\', "/");
+        if normalized.is_empty() || normalized.starts_with('/') {
+            return Err(invalid());
+        }
+
+        let mut result = PathBuf::new();
+        for component in normalized.split('/') {
+            if component.is_empty() || component == "." || component == ".." {
+                return Err(invalid());
+            }
+            // Reject anything the platform would not treat as a plain name,
+            // such as Windows drive prefixes.
+            let mut parsed = Path::new(component).components();
+            match (parsed.next(), parsed.next()) {
+                (Some(Component::Normal(_)), None) => result.push(component),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(result)
+    }
+
     /// Creates a progress message for file transfer status updates.
     ///
     /// This utility method creates a message that can be sent to inform
@@ -1,14 +1,34 @@
-use crate::{config::Config, ChatError, P2PChat, Result};
+use crate::config::{Config, LOG_LEVELS};
+use crate::contacts::ContactIdentity;
+use crate::encryption::safety_number;
+use crate::events::ChatEvent;
+use crate::file_transfer::FileTransfer;
+use crate::history::{ChatHistory, HistoryEntry};
+use crate::protocol::Command;
+use crate::session::ChatSession;
+use crate::transfer::{TransferDirection, TransferEvent, TransferState, TransferStatus};
+use crate::{ChatError, P2PChat, Result};
 use eframe::egui;
-use egui::{Context, RichText, Ui, Response, Sense};
+use egui::{Context, RichText, Sense, Ui};
+use futures::FutureExt;
+use poll_promise::Promise;
 use std::collections::VecDeque;
-use std::sync::mpsc;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::runtime::Runtime;
-use tracing::{error, info};
-use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+/// Window title, prefixed with the unread count while there is one.
+const WINDOW_TITLE: &str = "Rust P2P Chat - Drag & Drop Files";
+
+/// Number of messages kept in each conversation.
+const MAX_MESSAGES: usize = 1000;
+
+/// Received files with these extensions are shown as thumbnails.
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "bmp", "webp"];
 
 // Helper function to load application icon
 fn load_icon() -> egui::IconData {
@@ -26,6 +46,8 @@ pub struct ChatMessage {
     pub timestamp: SystemTime,
     pub is_encrypted: bool,
     pub is_file: bool,
+    /// A received image shown below the text.
+    pub image: Option<PathBuf>,
 }
 
 impl Default for ChatMessage {
@@ -36,16 +58,254 @@ impl Default for ChatMessage {
             timestamp: SystemTime::now(),
             is_encrypted: false,
             is_file: false,
+            image: None,
+        }
+    }
+}
+
+/// A file transfer shown in the transfers panel.
+struct TransferRow {
+    /// The session the transfer belongs to.
+    backend: usize,
+    /// The peer on the other end.
+    peer: String,
+    status: TransferStatus,
+    /// Bytes per second, smoothed over the last updates.
+    speed: f64,
+    updated_at: Instant,
+}
+
+/// A running chat session.
+struct Backend {
+    /// Identifies the session to its conversations and transfers.
+    id: usize,
+    session: ChatSession,
+    runtime: Runtime,
+    // Address the session actually listens on, which may differ from listen_port
+    listening_on: Option<SocketAddr>,
+    // Fingerprint of our identity key, once the session has reported it
+    fingerprint: Option<String>,
+    // Connected peers, in the order they connected
+    peers: Vec<PeerState>,
+    status: ConnectionStatus,
+}
+
+/// A connected peer and what is known about the security of its connection.
+struct PeerState {
+    id: String,
+    name: String,
+    address: SocketAddr,
+    /// Fingerprint of the peer's identity key, once received.
+    fingerprint: Option<String>,
+    /// Whether the encryption handshake has completed.
+    encrypted: bool,
+    /// The contact the peer was matched with, if any.
+    contact: Option<String>,
+    /// Whether the key matches one that was recorded for the contact.
+    verified: bool,
+    /// The fingerprint recorded for the contact, if the peer's key differs.
+    key_changed: Option<String>,
+    /// Whether the key change warning is still shown above the messages.
+    warning: bool,
+}
+
+impl PeerState {
+    fn new(id: String, name: String, address: SocketAddr) -> Self {
+        Self {
+            id,
+            name,
+            address,
+            fingerprint: None,
+            encrypted: false,
+            contact: None,
+            verified: false,
+            key_changed: None,
+            warning: false,
+        }
+    }
+
+    /// Returns the name of the peer's private conversation in the history:
+    /// the contact it was matched with, or else its identity fingerprint.
+    ///
+    /// Display names are not unique, so `None` is returned until the peer's
+    /// identity is known.
+    fn conversation_key(&self) -> Option<String> {
+        match (&self.contact, &self.fingerprint) {
+            (Some(contact), Some(_)) if self.key_changed.is_none() => Some(contact.clone()),
+            (_, Some(fingerprint)) => Some(fingerprint.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns how secure the connection is; `encryption` tells whether
+    /// the session encrypts at all.
+    fn security(&self, encryption: bool) -> Security {
+        if self.key_changed.is_some() {
+            Security::KeyChanged
+        } else if self.encrypted && self.verified {
+            Security::Verified
+        } else if self.encrypted {
+            Security::Encrypted
+        } else if encryption {
+            Security::Securing
+        } else {
+            Security::Unencrypted
         }
     }
+
+    /// Explains the peer's security indicator.
+    fn describe(&self, encryption: bool) -> String {
+        match self.security(encryption) {
+            Security::KeyChanged => format!("{}'s identity key has changed!", self.name),
+            Security::Unencrypted => format!("Messages to {} are not encrypted", self.name),
+            Security::Securing => format!("Setting up encryption with {}...", self.name),
+            Security::Encrypted => format!("Encrypted, but {} is not verified", self.name),
+            Security::Verified => format!("Encrypted, and {} is verified", self.name),
+        }
+    }
+}
+
+/// Security of a connection, from least to most secure.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Security {
+    /// The peer's key differs from the one recorded for the contact.
+    KeyChanged,
+    Unencrypted,
+    /// The encryption handshake has not completed yet.
+    Securing,
+    /// Encrypted with a key that has not been verified.
+    Encrypted,
+    /// Encrypted with a key recorded for the contact.
+    Verified,
+}
+
+impl Security {
+    fn icon(self) -> &'static str {
+        match self {
+            Security::KeyChanged => "⚠",
+            Security::Unencrypted => "🔓",
+            Security::Securing => "⏳",
+            Security::Encrypted => "🔒",
+            Security::Verified => "✔",
+        }
+    }
+
+    fn color(self) -> egui::Color32 {
+        match self {
+            Security::KeyChanged => egui::Color32::RED,
+            Security::Unencrypted => egui::Color32::from_rgb(230, 130, 50),
+            Security::Securing => egui::Color32::YELLOW,
+            Security::Encrypted | Security::Verified => egui::Color32::GREEN,
+        }
+    }
+}
+
+/// A conversation in the sidebar: everyone in a session, or one peer.
+struct Conversation {
+    /// Name of the conversation in the history. Group conversations are
+    /// named after their session with a leading `#`, e.g. `#10.0.0.2:8080`,
+    /// private ones after the peer's identity (see
+    /// [`PeerState::conversation_key`]).
+    key: String,
+    /// The peer that messages go to privately, or `None` for a group.
+    peer: Option<String>,
+    /// Whether messages are saved to the history, which they are not for
+    /// peers whose identity is unknown.
+    saved: bool,
+    /// The session the conversation belongs to while it is running.
+    backend: Option<usize>,
+    /// How the last session ended, shown while none is running.
+    status: ConnectionStatus,
+    messages: VecDeque<ChatMessage>,
+    /// Messages received since the conversation was last looked at.
+    unread: usize,
+}
+
+/// Settings being edited in the settings window.
+///
+/// Values that are not edited directly are kept as text and turned back
+/// into a [`Config`] by [`to_config`](Self::to_config).
+struct SettingsDraft {
+    config: Config,
+    nickname: String,
+    /// Comma-separated media extensions.
+    media_extensions: String,
+    /// Custom history file; empty for the default.
+    history_file: String,
+    /// Download folder being picked.
+    folder_dialog: Option<Promise<Option<PathBuf>>>,
+    /// Outcome of the last apply or save.
+    notice: Option<String>,
+}
+
+impl SettingsDraft {
+    fn new(config: &Config) -> Self {
+        Self {
+            nickname: config.nickname.clone().unwrap_or_default(),
+            media_extensions: config.media_extensions.join(", "),
+            history_file: config
+                .history_file
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            config: config.clone(),
+            folder_dialog: None,
+            notice: None,
+        }
+    }
+
+    /// Returns the edited settings if they are all valid.
+    fn to_config(&self) -> Result<Config> {
+        let mut config = self.config.clone();
+        config.nickname = Some(self.nickname.trim().to_string()).filter(|n| !n.is_empty());
+        config.media_extensions = self
+            .media_extensions
+            .split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_string())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        config.history_file = Some(PathBuf::from(self.history_file.trim()))
+            .filter(|path| !path.as_os_str().is_empty());
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Takes the download folder once the folder dialog closes.
+    fn poll_folder_dialog(&mut self) {
+        let Some(dialog) = self.folder_dialog.take() else {
+            return;
+        };
+        match dialog.try_take() {
+            Ok(Some(folder)) => self.config.download_dir = Some(folder),
+            Ok(None) => {}
+            Err(dialog) => self.folder_dialog = Some(dialog),
+        }
+    }
+}
+
+/// A button clicked in the settings window.
+enum SettingsAction {
+    Apply,
+    Save,
+    Revert,
+    Defaults,
+}
+
+/// Where a message shown in a conversation comes from.
+#[derive(PartialEq)]
+enum Origin {
+    /// Status and command output, which is not saved.
+    System,
+    Sent,
+    Received,
 }
 
 pub struct P2PChatApp {
     config: Config,
-    messages: Arc<Mutex<VecDeque<ChatMessage>>>,
+    history: ChatHistory,
     current_message: String,
-    connection_status: ConnectionStatus,
-    show_settings: bool,
+    // Settings being edited while the settings window is open
+    settings: Option<SettingsDraft>,
 
     // Connection settings
     listen_port: String,
@@ -53,47 +313,102 @@ pub struct P2PChatApp {
     nickname: String,
     enable_encryption: bool,
 
-    // Chat backend communication
-    message_sender: Option<mpsc::Sender<String>>,
-    runtime: Option<Arc<Runtime>>,
+    // Chat backend communication, and the ID of the next session
+    backends: Vec<Backend>,
+    next_backend: usize,
+
+    // Conversations in the sidebar and the index of the one shown
+    conversations: Vec<Conversation>,
+    active: Option<usize>,
+
+    // Peer whose identity is being verified, as (session, peer ID)
+    verifying: Option<(usize, String)>,
+
+    // File transfers, oldest first, and the offers to answer as
+    // (session, transfer ID)
+    transfers: Vec<TransferRow>,
+    offers: VecDeque<(usize, u32)>,
 
     // UI state
     auto_scroll: bool,
     show_timestamps: bool,
-    
+    show_conversations: bool,
+    show_transfers: bool,
+    notifications: bool,
+
+    // Whether the window has focus, and what arrived while it had not
+    focused: bool,
+    unread: usize,
+    title_unread: usize,
+
     // Drag and drop state
     dropped_files: Arc<Mutex<Vec<egui::DroppedFile>>>,
+    // Files being picked in the attach dialog
+    file_dialog: Option<Promise<Vec<PathBuf>>>,
 }
 
-#[derive(PartialEq, Default)]
+#[derive(PartialEq, Clone, Default)]
 enum ConnectionStatus {
     #[default]
     Disconnected,
     Connecting,
     Connected(String),
-    #[allow(dead_code)]
     Error(String),
 }
 
 impl Default for P2PChatApp {
     fn default() -> Self {
-        let config = Config::default();
-        Self {
+        let config = Config::load().unwrap_or_else(|e| {
+            warn!("Using default settings: {}", e);
+            Config::default()
+        });
+        let mut app = Self {
             listen_port: config.default_port.to_string(),
             peer_address: String::new(),
             nickname: config.nickname.clone().unwrap_or_else(|| "You".to_string()),
             enable_encryption: config.enable_encryption,
+            notifications: config.desktop_notifications,
+            history: ChatHistory::load_for(&config),
             config,
-            messages: Arc::new(Mutex::new(VecDeque::new())),
             current_message: String::new(),
-            connection_status: ConnectionStatus::default(),
-            show_settings: false,
-            message_sender: None,
-            runtime: None,
+            settings: None,
+            backends: Vec::new(),
+            next_backend: 0,
+            conversations: Vec::new(),
+            active: None,
+            verifying: None,
+            transfers: Vec::new(),
+            offers: VecDeque::new(),
             auto_scroll: true,
             show_timestamps: true,
+            show_conversations: true,
+            show_transfers: true,
+            focused: true,
+            unread: 0,
+            title_unread: 0,
             dropped_files: Arc::new(Mutex::new(Vec::new())),
+            file_dialog: None,
+        };
+
+        // Earlier conversations can be read before connecting. Private ones
+        // are named after whoever last wrote in them, as their key is an
+        // identity
+        let keys: Vec<String> = app.history.conversations().cloned().collect();
+        for key in keys {
+            let peer = (!key.starts_with('#')).then(|| {
+                app.history
+                    .entries(&key)
+                    .iter()
+                    .rev()
+                    .map(|entry| &entry.sender)
+                    .find(|sender| **sender != app.nickname)
+                    .unwrap_or(&key)
+                    .clone()
+            });
+            app.open_conversation(key, peer, None);
         }
+        app.active = (!app.conversations.is_empty()).then_some(0);
+        app
     }
 }
 
@@ -102,99 +417,255 @@ impl P2PChatApp {
         Self::default()
     }
 
-    fn add_message(&mut self, text: String, sender: String, is_encrypted: bool, is_file: bool) {
+    fn add_message(
+        &mut self,
+        index: usize,
+        text: String,
+        sender: String,
+        is_encrypted: bool,
+        is_file: bool,
+    ) {
         let message = ChatMessage {
             text,
             sender,
             timestamp: SystemTime::now(),
             is_encrypted,
             is_file,
+            image: None,
         };
+        self.push_message(index, message, Origin::System);
+    }
 
-        if let Ok(mut messages) = self.messages.lock() {
-            messages.push_back(message);
-            // Keep only last 1000 messages
-            if messages.len() > 1000 {
-                messages.pop_front();
+    /// Shows a message in a conversation.
+    ///
+    /// Messages from people are saved to the history. Received ones count as
+    /// unread unless the conversation is on screen.
+    fn push_message(&mut self, index: usize, message: ChatMessage, origin: Origin) {
+        let on_screen = self.active == Some(index) && self.focused;
+        let conversation = &mut self.conversations[index];
+        if origin != Origin::System && conversation.saved {
+            let entry = HistoryEntry {
+                sender: message.sender.clone(),
+                text: message.text.clone(),
+                timestamp: message
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+                encrypted: message.is_encrypted,
+                is_file: message.is_file,
+            };
+            if let Err(e) = self.history.push(&conversation.key, entry) {
+                warn!("Failed to save history: {}", e);
             }
         }
+        if origin == Origin::Received && !on_screen {
+            conversation.unread += 1;
+        }
+
+        conversation.messages.push_back(message);
+        // Keep only last 1000 messages
+        if conversation.messages.len() > MAX_MESSAGES {
+            conversation.messages.pop_front();
+        }
     }
 
-    fn connect_to_peer(&mut self, peer_addr: Option<String>) {
-        if self.runtime.is_some() {
-            self.disconnect();
+    /// Adds a conversation to the sidebar with the messages saved for it.
+    fn open_conversation(
+        &mut self,
+        key: String,
+        peer: Option<String>,
+        backend: Option<usize>,
+    ) -> usize {
+        let messages = self
+            .history
+            .entries(&key)
+            .iter()
+            .map(|entry| ChatMessage {
+                text: entry.text.clone(),
+                sender: entry.sender.clone(),
+                timestamp: UNIX_EPOCH + Duration::from_secs(entry.timestamp),
+                is_encrypted: entry.encrypted,
+                is_file: entry.is_file,
+                image: None,
+            })
+            .collect();
+        self.conversations.push(Conversation {
+            key,
+            peer,
+            saved: true,
+            backend,
+            status: ConnectionStatus::Disconnected,
+            messages,
+            unread: 0,
+        });
+        self.conversations.len() - 1
+    }
+
+    /// Returns the index of a session's conversation with `peer`, or of its
+    /// group conversation if `peer` is `None`.
+    ///
+    /// An earlier conversation with the same peer is continued if there is
+    /// one, otherwise a new conversation is opened. Peers are told apart by
+    /// their identity rather than their name, so the conversation of a peer
+    /// whose identity is unknown is neither continued later nor saved.
+    fn conversation(&mut self, backend: usize, peer: Option<&str>) -> usize {
+        if let Some(index) = self
+            .conversations
+            .iter()
+            .position(|c| c.backend == Some(backend) && c.peer.as_deref() == peer)
+        {
+            return index;
         }
+        let Some(peer) = peer else {
+            // Sessions are started with a group conversation, so this is
+            // only reached if it was lost somehow
+            return self.open_conversation(format!("#session {}", backend), None, Some(backend));
+        };
 
-        self.connection_status = ConnectionStatus::Connecting;
+        let key = self
+            .backends
+            .iter()
+            .find(|b| b.id == backend)
+            .and_then(|b| b.peers.iter().find(|state| state.name == peer))
+            .and_then(PeerState::conversation_key);
+        let Some(key) = key else {
+            self.conversations.push(Conversation {
+                key: peer.to_string(),
+                peer: Some(peer.to_string()),
+                saved: false,
+                backend: Some(backend),
+                status: ConnectionStatus::Disconnected,
+                messages: VecDeque::new(),
+                unread: 0,
+            });
+            return self.conversations.len() - 1;
+        };
+        if let Some(index) = self
+            .conversations
+            .iter()
+            .position(|c| c.backend.is_none() && c.peer.is_some() && c.key == key)
+        {
+            let conversation = &mut self.conversations[index];
+            conversation.backend = Some(backend);
+            conversation.peer = Some(peer.to_string());
+            return index;
+        }
+        self.open_conversation(key, Some(peer.to_string()), Some(backend))
+    }
 
+    /// Starts a new session that listens, and connects to `peer_addr` if
+    /// given. Other sessions keep running.
+    fn connect_to_peer(&mut self, peer_addr: Option<String>) {
         let port: u16 = self.listen_port.parse().unwrap_or(8080);
-        let config = self.config.clone();
-        let messages = self.messages.clone();
-
-        // Create message channel
-        let (tx, _rx) = mpsc::channel();
-        self.message_sender = Some(tx);
-
-        // Create runtime and spawn chat task
-        let runtime = Arc::new(Runtime::new().expect("Failed to create Tokio runtime"));
-        let rt_clone = runtime.clone();
-        self.runtime = Some(runtime);
-
-        let peer_addr_clone = peer_addr.clone();
-        thread::spawn(move || {
-            rt_clone.block_on(async {
-                let mut chat = match P2PChat::new(config) {
-                    Ok(chat) => chat,
-                    Err(e) => {
-                        error!("Failed to create chat: {}", e);
-                        return;
-                    }
-                };
+        let key = match &peer_addr {
+            Some(address) => format!("#{}", address),
+            None => format!("#port {}", port),
+        };
 
-                // Add system message
-                if let Ok(mut msgs) = messages.lock() {
-                    msgs.push_back(ChatMessage {
-                        text: if let Some(ref addr) = peer_addr_clone {
-                            format!("Connecting to peer at {}...", addr)
-                        } else {
-                            format!("Listening on port {}...", port)
-                        },
-                        sender: "System".to_string(),
-                        timestamp: SystemTime::now(),
-                        is_encrypted: false,
-                        is_file: false,
-                    });
-                }
+        // Continue the conversation of an earlier session with the same
+        // peer or port, or just show it if that session is still running
+        let index = match self
+            .conversations
+            .iter()
+            .position(|c| c.peer.is_none() && c.key == key)
+        {
+            Some(index) if self.conversations[index].backend.is_some() => {
+                self.select_conversation(index);
+                return;
+            }
+            Some(index) => index,
+            None => self.open_conversation(key, None, None),
+        };
+        self.select_conversation(index);
+
+        // Another session may listen on the port already; one that only
+        // dials out can listen on any free port instead
+        let in_use = self
+            .backends
+            .iter()
+            .any(|backend| backend.listening_on.map(|addr| addr.port()) == Some(port));
+        let port = if in_use && peer_addr.is_some() {
+            0
+        } else {
+            port
+        };
 
-                // Start chat (this will block)
-                if let Err(e) = chat.start(port, peer_addr_clone).await {
-                    error!("Chat error: {}", e);
-                    if let Ok(mut msgs) = messages.lock() {
-                        msgs.push_back(ChatMessage {
-                            text: format!("Connection failed: {}", e),
-                            sender: "System".to_string(),
-                            timestamp: SystemTime::now(),
-                            is_encrypted: false,
-                            is_file: false,
-                        });
-                    }
-                }
-            });
+        let chat = match P2PChat::new(self.session_config()) {
+            Ok(chat) => chat,
+            Err(e) => {
+                error!("Failed to create chat: {}", e);
+                self.conversations[index].status = ConnectionStatus::Error(e.to_string());
+                return;
+            }
+        };
+
+        // The session runs on the runtime's worker threads
+        let runtime = match Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                error!("Failed to create Tokio runtime: {}", e);
+                self.conversations[index].status =
+                    ConnectionStatus::Error(format!("Failed to start session: {}", e));
+                return;
+            }
+        };
+        let session = {
+            let _guard = runtime.enter();
+            ChatSession::start(chat, port, peer_addr)
+        };
+        let id = self.next_backend;
+        self.next_backend += 1;
+        self.backends.push(Backend {
+            id,
+            session,
+            runtime,
+            listening_on: None,
+            fingerprint: None,
+            peers: Vec::new(),
+            status: ConnectionStatus::Connecting,
         });
+        self.conversations[index].backend = Some(id);
+    }
 
-        // Update status
-        if let Some(ref addr) = peer_addr {
-            self.connection_status = ConnectionStatus::Connected(addr.clone());
-        } else {
-            self.connection_status = ConnectionStatus::Connected(format!("Listening on {}", port));
+    /// Ends a session and stops its runtime without blocking the UI.
+    ///
+    /// The session says goodbye to its peers on a thread of its own, which
+    /// then shuts the runtime down. Join the returned thread to wait for it.
+    fn stop_backend(&mut self, id: usize) -> Option<thread::JoinHandle<()>> {
+        let position = self.backends.iter().position(|backend| backend.id == id)?;
+        let backend = self.backends.remove(position);
+        self.detach_backend(id, ConnectionStatus::Disconnected);
+        Some(shut_down(Some(backend.session), backend.runtime))
+    }
+
+    /// Marks the conversations and transfers of a session as ended.
+    fn detach_backend(&mut self, id: usize, status: ConnectionStatus) {
+        for conversation in &mut self.conversations {
+            if conversation.backend == Some(id) {
+                conversation.backend = None;
+                conversation.status = status.clone();
+            }
+        }
+        // Transfer IDs only mean something within a session
+        self.offers.retain(|(backend, _)| *backend != id);
+        for row in &mut self.transfers {
+            if row.backend == id && !row.status.state.is_finished() {
+                row.status.state = TransferState::Cancelled;
+                row.speed = 0.0;
+            }
         }
     }
 
+    /// Ends the session of the conversation on screen.
     fn disconnect(&mut self) {
-        self.message_sender = None;
-        self.runtime = None;
-        self.connection_status = ConnectionStatus::Disconnected;
+        let Some(id) = self.active_backend() else {
+            return;
+        };
+        let index = self.conversation(id, None);
+        self.stop_backend(id);
         self.add_message(
+            index,
             "Disconnected".to_string(),
             "System".to_string(),
             false,
@@ -202,47 +673,574 @@ impl P2PChatApp {
         );
     }
 
+    /// Returns the session of the conversation on screen, if it is running.
+    fn active_backend(&self) -> Option<usize> {
+        self.active
+            .and_then(|index| self.conversations[index].backend)
+    }
+
+    fn backend_mut(&mut self, id: usize) -> Option<&mut Backend> {
+        self.backends.iter_mut().find(|backend| backend.id == id)
+    }
+
+    fn peer_mut(&mut self, backend: usize, peer: &str) -> Option<&mut PeerState> {
+        self.backend_mut(backend)?
+            .peers
+            .iter_mut()
+            .find(|state| state.id == peer)
+    }
+
+    fn select_conversation(&mut self, index: usize) {
+        self.active = Some(index);
+        self.conversations[index].unread = 0;
+    }
+
+    /// Applies the events published by the sessions since the last frame.
+    fn poll_backends(&mut self) {
+        let mut events = Vec::new();
+        let mut finished = Vec::new();
+        for backend in &mut self.backends {
+            while let Some(event) = backend.session.try_next_event() {
+                events.push((backend.id, event));
+            }
+            if backend.session.is_finished() {
+                finished.push(backend.id);
+            }
+        }
+        for (id, event) in events {
+            self.handle_event(id, event);
+        }
+        for id in finished {
+            self.end_backend(id);
+        }
+    }
+
+    /// Cleans up after a session that has finished by itself.
+    fn end_backend(&mut self, id: usize) {
+        let Some(position) = self.backends.iter().position(|backend| backend.id == id) else {
+            return;
+        };
+        let index = self.conversation(id, None);
+        let backend = self.backends.remove(position);
+        // The session has finished, so this does not wait
+        let result = backend.session.wait().now_or_never().unwrap_or(Ok(()));
+        shut_down(None, backend.runtime);
+        match result {
+            Ok(()) => {
+                self.detach_backend(id, ConnectionStatus::Disconnected);
+                self.add_message(
+                    index,
+                    "Session ended".to_string(),
+                    "System".to_string(),
+                    false,
+                    false,
+                );
+            }
+            Err(e) => {
+                error!("Chat error: {}", e);
+                self.detach_backend(id, ConnectionStatus::Error(e.to_string()));
+                self.add_message(
+                    index,
+                    format!("Connection failed: {}", e),
+                    "System".to_string(),
+                    false,
+                    false,
+                );
+            }
+        }
+    }
+
+    fn handle_event(&mut self, id: usize, event: ChatEvent) {
+        let system = |text: String| (text, "System".to_string(), false, false);
+        // Messages go to the session's group conversation unless they are
+        // private
+        let mut index = self.conversation(id, None);
+        let mut origin = Origin::System;
+        let (text, sender, is_encrypted, is_file) =
+            match event {
+                ChatEvent::Listening(addr) => {
+                    if let Some(backend) = self.backend_mut(id) {
+                        backend.listening_on = Some(addr);
+                    }
+                    self.update_status(id);
+                    system(format!("Listening on port {}", addr.port()))
+                }
+                ChatEvent::LocalIdentity(fingerprint) => {
+                    if let Some(backend) = self.backend_mut(id) {
+                        backend.fingerprint = Some(fingerprint.clone());
+                    }
+                    system(format!("Your identity: {}", fingerprint))
+                }
+                ChatEvent::Connecting(address) => system(format!("Connecting to {}...", address)),
+                ChatEvent::PeerConnected {
+                    id: peer, address, ..
+                } => {
+                    if let Some(backend) = self.backend_mut(id) {
+                        let name = address.to_string();
+                        backend.peers.push(PeerState::new(peer, name, address));
+                    }
+                    self.update_status(id);
+                    system(format!("Connected to {}", address))
+                }
+                ChatEvent::PeerRenamed {
+                    id: peer,
+                    previous,
+                    name,
+                } => {
+                    if let Some(state) = self.peer_mut(id, &peer) {
+                        state.name = name.clone();
+                    }
+                    // Private messages now go to the new name
+                    if let Some(conversation) = self.conversations.iter_mut().find(|c| {
+                        c.backend == Some(id) && c.peer.as_deref() == Some(previous.as_str())
+                    }) {
+                        conversation.peer = Some(name.clone());
+                    }
+                    self.update_status(id);
+                    system(format!("{} is now known as {}", previous, name))
+                }
+                ChatEvent::PeerDisconnected {
+                    id: peer,
+                    name,
+                    left,
+                } => {
+                    if let Some(backend) = self.backend_mut(id) {
+                        backend.peers.retain(|state| state.id != peer);
+                    }
+                    if self.verifying.as_ref() == Some(&(id, peer)) {
+                        self.verifying = None;
+                    }
+                    // Their private conversation is kept to read later
+                    for conversation in &mut self.conversations {
+                        let private = conversation.peer.as_deref() == Some(name.as_str());
+                        if conversation.backend == Some(id) && private {
+                            conversation.backend = None;
+                        }
+                    }
+                    self.update_status(id);
+                    if left {
+                        system(format!("{} left the chat", name))
+                    } else {
+                        system(format!("{} disconnected", name))
+                    }
+                }
+                ChatEvent::PeerIdentity {
+                    id: peer,
+                    name,
+                    fingerprint,
+                    contact,
+                } => {
+                    if let Some(state) = self.peer_mut(id, &peer) {
+                        state.fingerprint = Some(fingerprint.clone());
+                        state.verified = matches!(contact, Some(ContactIdentity::Verified(_)));
+                        state.key_changed = None;
+                        state.warning = false;
+                        state.contact = match &contact {
+                            Some(ContactIdentity::Verified(contact))
+                            | Some(ContactIdentity::Learned(contact))
+                            | Some(ContactIdentity::Changed { name: contact, .. }) => {
+                                Some(contact.clone())
+                            }
+                            None => None,
+                        };
+                        if let Some(ContactIdentity::Changed { expected, .. }) = &contact {
+                            state.key_changed = Some(expected.clone());
+                            state.warning = true;
+                        }
+                    }
+                    system(match contact {
+                        Some(ContactIdentity::Verified(contact)) => {
+                            format!("{} is your contact {} ({})", name, contact, fingerprint)
+                        }
+                        Some(ContactIdentity::Learned(contact)) => {
+                            format!("Recorded identity of contact {}: {}", contact, fingerprint)
+                        }
+                        Some(ContactIdentity::Changed {
+                            name: contact,
+                            expected,
+                        }) => {
+                            self.alert(
+                                format!("The identity of {} has changed", contact),
+                                "Verify it before trusting the conversation".to_string(),
+                            );
+                            format!(
+                            "WARNING: the identity of contact {} has changed! Expected {}, got {}",
+                            contact, expected, fingerprint
+                        )
+                        }
+                        None => format!("{} identity: {}", name, fingerprint),
+                    })
+                }
+                ChatEvent::EncryptionEnabled { id: peer, name } => {
+                    if let Some(state) = self.peer_mut(id, &peer) {
+                        state.encrypted = true;
+                    }
+                    (
+                        format!("End-to-end encryption enabled with {}", name),
+                        "System".to_string(),
+                        true,
+                        false,
+                    )
+                }
+                ChatEvent::MessageReceived {
+                    from,
+                    text,
+                    encrypted,
+                    private,
+                } => {
+                    self.alert(format!("Message from {}", from), text.clone());
+                    if private {
+                        index = self.conversation(id, Some(&from));
+                    }
+                    origin = Origin::Received;
+                    (text, from, encrypted, false)
+                }
+                ChatEvent::MessageSent {
+                    to,
+                    text,
+                    encrypted,
+                } => {
+                    if let Some(to) = &to {
+                        index = self.conversation(id, Some(to));
+                    }
+                    origin = Origin::Sent;
+                    (text, self.nickname.clone(), encrypted, false)
+                }
+                ChatEvent::Transfer { from, event } => {
+                    let text = match event {
+                        TransferEvent::Started(status) => {
+                            let text = match status.files {
+                                Some(files) => format!(
+                                    "Receiving directory: {} ({} files, {} bytes) [#{}]",
+                                    status.name, files, status.size, status.id
+                                ),
+                                None => format!(
+                                    "Receiving file: {} ({} bytes) [#{}]",
+                                    status.name, status.size, status.id
+                                ),
+                            };
+                            self.alert(format!("{} is sending a file", from), status.name.clone());
+                            self.update_transfer(id, from.clone(), status);
+                            text
+                        }
+                        TransferEvent::Offered(status) => {
+                            let text =
+                                format!("Offers {} ({})", status.name, format_bytes(status.size));
+                            self.alert(
+                                format!("{} wants to send a file", from),
+                                status.name.clone(),
+                            );
+                            self.offers.push_back((id, status.id));
+                            self.update_transfer(id, from.clone(), status);
+                            text
+                        }
+                        TransferEvent::Completed {
+                            id: transfer, path, ..
+                        } => {
+                        self.finish_transfer(id, transfer, TransferState::Completed);
+                        let message = ChatMessage {
+                            text: format!("File received: {}", path.display()),
+                            sender: from,
+                            is_file: true,
+                            image: is_image(&path).then_some(path),
+                            ..Default::default()
+                        };
+                        self.push_message(index, message, Origin::Received);
+                        return;
+                    }
+                        TransferEvent::Paused { id, name } => {
+                            format!("Paused transfer #{} ({})", id, name)
+                        }
+                        TransferEvent::Resumed { id, name } => {
+                            format!("Resumed transfer #{} ({})", id, name)
+                        }
+                        TransferEvent::Cancelled { id: transfer, name } => {
+                            self.finish_transfer(id, transfer, TransferState::Cancelled);
+                            format!("Cancelled transfer #{} ({})", transfer, name)
+                        }
+                        TransferEvent::Failed {
+                            id: transfer,
+                            name,
+                            reason,
+                        } => {
+                        self.finish_transfer(id, transfer, TransferState::Failed);
+                        format!("Transfer #{} ({}) failed: {}", transfer, name, reason)
+                    }
+                    TransferEvent::Progress | TransferEvent::Ignored => return,
+                };
+                (text, from, false, true)
+            }
+            ChatEvent::TransferProgress { peer, status } => {
+                self.update_transfer(id, peer, status);
+                return;
+            }
+            ChatEvent::PeerDiscovered(peer) => system(format!(
+                "Discovered {} at {}",
+                peer.display_name(),
+                peer.address
+            )),
+            ChatEvent::Notice(text) | ChatEvent::Warning(text) => system(text),
+            ChatEvent::Error(text) => system(format!("Error: {}", text)),
+        };
+        let message = ChatMessage {
+            text,
+            sender,
+            timestamp: SystemTime::now(),
+            is_encrypted,
+            is_file,
+            image: None,
+        };
+        self.push_message(index, message, origin);
+    }
+
+    /// Counts something that arrived while the window is in the background,
+    /// and shows a desktop notification for it if those are enabled.
+    fn alert(&mut self, summary: String, body: String) {
+        if self.focused {
+            return;
+        }
+        self.unread += 1;
+        if self.notifications {
+            show_notification(summary, body);
+        }
+    }
+
+    /// Tracks window focus and shows the unread count in the title.
+    ///
+    /// The count is cleared once the user is back in the window.
+    fn update_unread(&mut self, ctx: &Context) {
+        let (focused, active) = ctx.input(|i| {
+            let active = !i.events.is_empty() || i.pointer.is_moving();
+            (i.viewport().focused.unwrap_or(true), active)
+        });
+        self.focused = focused;
+        if focused && active {
+            self.unread = 0;
+            if let Some(index) = self.active {
+                self.conversations[index].unread = 0;
+            }
+        }
+        if self.unread != self.title_unread {
+            self.title_unread = self.unread;
+            let title = match self.unread {
+                0 => WINDOW_TITLE.to_string(),
+                unread => format!("({}) {}", unread, WINDOW_TITLE),
+            };
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title));
+        }
+    }
+
+    /// Records the latest status of a transfer for the transfers panel.
+    fn update_transfer(&mut self, backend: usize, peer: String, status: TransferStatus) {
+        if status.state != TransferState::Waiting {
+            self.offers.retain(|offer| *offer != (backend, status.id));
+        }
+        let now = Instant::now();
+        match self
+            .transfers
+            .iter_mut()
+            .find(|row| row.backend == backend && row.status.id == status.id)
+        {
+            Some(row) => {
+                let elapsed = now.duration_since(row.updated_at).as_secs_f64();
+                if elapsed > 0.0 && status.transferred >= row.status.transferred {
+                    let rate = (status.transferred - row.status.transferred) as f64 / elapsed;
+                    row.speed = if row.speed > 0.0 {
+                        (row.speed + rate) / 2.0
+                    } else {
+                        rate
+                    };
+                }
+                row.peer = peer;
+                row.status = status;
+                row.updated_at = now;
+            }
+            None => self.transfers.push(TransferRow {
+                backend,
+                peer,
+                status,
+                speed: 0.0,
+                updated_at: now,
+            }),
+        }
+    }
+
+    /// Marks a transfer finished before its next progress update arrives.
+    fn finish_transfer(&mut self, backend: usize, id: u32, state: TransferState) {
+        self.offers.retain(|offer| *offer != (backend, id));
+        if let Some(row) = self
+            .transfers
+            .iter_mut()
+            .find(|row| row.backend == backend && row.status.id == id)
+        {
+            if state == TransferState::Completed {
+                row.status.transferred = row.status.size;
+            }
+            row.status.state = state;
+            row.speed = 0.0;
+        }
+    }
+
+    /// Shows a session's connected peers, or its listening address if there
+    /// are none.
+    fn update_status(&mut self, id: usize) {
+        let Some(backend) = self.backend_mut(id) else {
+            return;
+        };
+        backend.status = match (backend.peers.as_slice(), backend.listening_on) {
+            ([peer], _) => ConnectionStatus::Connected(peer.name.clone()),
+            ([], Some(addr)) => ConnectionStatus::Connected(format!("Listening on {}", addr)),
+            ([], None) => ConnectionStatus::Connecting,
+            (peers, _) => ConnectionStatus::Connected(format!("{} peers", peers.len())),
+        };
+    }
+
+    /// Returns the status of the conversation on screen.
+    fn connection_status(&self) -> ConnectionStatus {
+        let Some(conversation) = self.active.map(|index| &self.conversations[index]) else {
+            return ConnectionStatus::Disconnected;
+        };
+        match self
+            .backends
+            .iter()
+            .find(|backend| Some(backend.id) == conversation.backend)
+        {
+            Some(backend) => backend.status.clone(),
+            None => conversation.status.clone(),
+        }
+    }
+
+    /// Returns the connected peers a conversation's messages go to.
+    fn conversation_peers(&self, conversation: &Conversation) -> Vec<&PeerState> {
+        let backend = self
+            .backends
+            .iter()
+            .find(|backend| Some(backend.id) == conversation.backend);
+        let Some(backend) = backend else {
+            return Vec::new();
+        };
+        backend
+            .peers
+            .iter()
+            .filter(|peer| {
+                conversation
+                    .peer
+                    .as_ref()
+                    .is_none_or(|name| *name == peer.name)
+            })
+            .collect()
+    }
+
+    /// Returns the security of a conversation with a description of each
+    /// peer's, or `None` if no peer is connected.
+    ///
+    /// A group is only as secure as its least secure peer.
+    fn conversation_security(&self, conversation: &Conversation) -> Option<(Security, String)> {
+        let encryption = self.config.enable_encryption;
+        let peers = self.conversation_peers(conversation);
+        let security = peers.iter().map(|peer| peer.security(encryption)).min()?;
+        let details = peers
+            .iter()
+            .map(|peer| peer.describe(encryption))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some((security, details))
+    }
+
+    /// Returns the name of a conversation shown in the sidebar.
+    fn conversation_title(&self, conversation: &Conversation) -> String {
+        if let Some(peer) = &conversation.peer {
+            return peer.clone();
+        }
+        // A running group is named after its peers
+        match self
+            .backends
+            .iter()
+            .find(|backend| Some(backend.id) == conversation.backend)
+        {
+            Some(backend) if !backend.peers.is_empty() => backend
+                .peers
+                .iter()
+                .map(|peer| peer.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => conversation.key.trim_start_matches('#').to_string(),
+        }
+    }
+
     fn send_message(&mut self) {
         if !self.current_message.trim().is_empty() {
             let message = self.current_message.clone();
 
-            // Add to our message list
-            self.add_message(
-                message.clone(),
-                self.nickname.clone(),
-                self.enable_encryption,
-                false,
-            );
-
-            // Send through channel (in a real implementation, this would go to the chat backend)
-            if let Some(ref sender) = self.message_sender {
-                let _ = sender.send(message);
+            // Sent messages are shown when the backend reports them
+            if let Some(index) = self.active {
+                let conversation = &self.conversations[index];
+                let backend = self
+                    .backends
+                    .iter()
+                    .find(|b| Some(b.id) == conversation.backend);
+                if let Some(backend) = backend {
+                    let _ = match &conversation.peer {
+                        // Commands work in private conversations too
+                        Some(peer) if !message.starts_with('/') => {
+                            backend.session.send_private(peer.clone(), message)
+                        }
+                        _ => backend.session.send_line(message),
+                    };
+                }
             }
 
             self.current_message.clear();
         }
     }
-    
+
+    /// Runs a command in a session, which reports the outcome as events.
+    fn send_command(&mut self, backend: usize, command: Command) {
+        if let Some(backend) = self.backends.iter().find(|b| b.id == backend) {
+            let _ = backend.session.command(command);
+        }
+    }
+
     fn send_file(&mut self, path: PathBuf) {
-        let filename = path.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("file")
-            .to_string();
-            
-        // Add to message list
-        self.add_message(
-            format!("Sending file: {}", filename),
-            self.nickname.clone(),
-            self.enable_encryption,
-            true,
-        );
-        
-        // In a real implementation, this would send the file through the chat backend
-        if let Some(ref sender) = self.message_sender {
-            let _ = sender.send(format!("/send {}", path.display()));
+        // The backend reports the transfer, or that no peer is connected
+        let backend = self.active_backend();
+        if let Some(backend) = self.backends.iter().find(|b| Some(b.id) == backend) {
+            let _ = backend.session.send_file(&path);
+        }
+    }
+
+    /// Opens the native file dialog without blocking the UI.
+    fn pick_files(&mut self) {
+        if self.file_dialog.is_some() {
+            return;
+        }
+        let dialog = rfd::AsyncFileDialog::new()
+            .set_title("Attach files")
+            .pick_files();
+        self.file_dialog = Some(Promise::spawn_thread("file_dialog", move || {
+            futures::executor::block_on(dialog)
+                .unwrap_or_default()
+                .iter()
+                .map(|file| file.path().to_path_buf())
+                .collect()
+        }));
+    }
+
+    /// Sends the files picked in the attach dialog once it closes.
+    fn poll_file_dialog(&mut self) {
+        let Some(dialog) = self.file_dialog.take() else {
+            return;
+        };
+        match dialog.try_take() {
+            Ok(paths) => {
+                for path in paths {
+                    self.send_file(path);
+                }
+            }
+            Err(dialog) => self.file_dialog = Some(dialog),
         }
     }
-    
+
     fn handle_dropped_files(&mut self, ctx: &Context) {
         // Check for newly dropped files
         ctx.input(|i| {
@@ -251,13 +1249,13 @@ impl P2PChatApp {
                 dropped.extend(i.raw.dropped_files.clone());
             }
         });
-        
+
         // Process dropped files
         let files_to_process: Vec<egui::DroppedFile> = {
             let mut dropped = self.dropped_files.lock().unwrap();
             dropped.drain(..).collect()
         };
-        
+
         for file in files_to_process {
             if let Some(path) = &file.path {
                 self.send_file(path.clone());
@@ -267,9 +1265,24 @@ impl P2PChatApp {
 }
 
 impl eframe::App for P2PChatApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Let peers see us leave before the process exits
+        let ids: Vec<usize> = self.backends.iter().map(|backend| backend.id).collect();
+        let handles: Vec<_> = ids
+            .into_iter()
+            .filter_map(|id| self.stop_backend(id))
+            .collect();
+        for handle in handles {
+            let _ = handle.join();
+        }
+    }
+
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        self.update_unread(ctx);
         // Handle dropped files
         self.handle_dropped_files(ctx);
+        self.poll_file_dialog();
+        self.poll_backends();
         // Top menu bar
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
@@ -284,22 +1297,32 @@ impl eframe::App for P2PChatApp {
                         self.connect_to_peer(None);
                         ui.close_menu();
                     }
-                    if ui.button("Disconnect").clicked() {
+                    if ui
+                        .button("Disconnect")
+                        .on_hover_text("End the session shown")
+                        .clicked()
+                    {
                         self.disconnect();
                         ui.close_menu();
                     }
                 });
 
+                ui.menu_button("View", |ui| {
+                    ui.checkbox(&mut self.show_conversations, "Conversations");
+                    ui.checkbox(&mut self.show_transfers, "Transfers panel");
+                });
+
                 ui.menu_button("Settings", |ui| {
                     if ui.button("Open Settings").clicked() {
-                        self.show_settings = true;
+                        self.settings = Some(SettingsDraft::new(&self.config));
                         ui.close_menu();
                     }
                 });
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     // Connection status indicator
-                    let (status_text, status_color) = match &self.connection_status {
+                    let connection_status = self.connection_status();
+                    let (status_text, status_color) = match &connection_status {
                         ConnectionStatus::Disconnected => ("Disconnected", egui::Color32::RED),
                         ConnectionStatus::Connecting => ("Connecting...", egui::Color32::YELLOW),
                         ConnectionStatus::Connected(addr) => (addr.as_str(), egui::Color32::GREEN),
@@ -307,21 +1330,55 @@ impl eframe::App for P2PChatApp {
                     };
 
                     ui.colored_label(status_color, status_text);
+
+                    // Unread badge
+                    if self.unread > 0 {
+                        ui.label(
+                            RichText::new(format!(" {} new ", self.unread))
+                                .color(egui::Color32::WHITE)
+                                .background_color(egui::Color32::from_rgb(200, 60, 60))
+                                .strong(),
+                        );
+                    }
                 });
             });
         });
 
         // Settings window
-        let mut show_settings = self.show_settings;
+        // Closing the window discards settings that were not applied
+        let mut show_settings = self.settings.is_some();
         if show_settings {
             egui::Window::new("Settings")
                 .open(&mut show_settings)
-                .default_width(400.0)
+                .default_width(460.0)
                 .show(ctx, |ui| {
                     self.show_settings_ui(ui);
                 });
         }
-        self.show_settings = show_settings;
+        if !show_settings {
+            self.settings = None;
+        }
+
+        self.show_offer_dialog(ctx);
+        self.show_verify_dialog(ctx);
+
+        if self.show_conversations {
+            egui::SidePanel::left("conversations_panel")
+                .resizable(true)
+                .default_width(180.0)
+                .show(ctx, |ui| {
+                    self.show_conversations_ui(ui);
+                });
+        }
+
+        if self.show_transfers && !self.transfers.is_empty() {
+            egui::SidePanel::right("transfers_panel")
+                .resizable(true)
+                .default_width(260.0)
+                .show(ctx, |ui| {
+                    self.show_transfers_ui(ui);
+                });
+        }
 
         // Main chat area with drag and drop support
         let response = egui::CentralPanel::default()
@@ -338,7 +1395,11 @@ impl eframe::App for P2PChatApp {
                         ui.label("Peer Address:");
                         ui.text_edit_singleline(&mut self.peer_address);
 
-                        if ui.button("Connect").clicked() {
+                            if ui
+                                .button("Connect")
+                                .on_hover_text("Start a new session")
+                                .clicked()
+                            {
                             if !self.peer_address.is_empty() {
                                 self.connect_to_peer(Some(self.peer_address.clone()));
                             } else {
@@ -350,18 +1411,24 @@ impl eframe::App for P2PChatApp {
 
                 ui.separator();
 
+                    self.show_key_change_warnings(ui);
+
                 // Chat messages area
                 let available_height = ui.available_height() - 60.0; // Reserve space for input
                 egui::ScrollArea::vertical()
                     .max_height(available_height)
                     .auto_shrink([false, false])
                     .stick_to_bottom(self.auto_scroll)
-                    .show(ui, |ui| {
-                        if let Ok(messages) = self.messages.lock() {
-                            for msg in messages.iter() {
+                    .show(ui, |ui| match self.active {
+                        Some(index) => {
+                            for msg in &self.conversations[index].messages {
                                 self.show_message(ui, msg);
                             }
                         }
+                        None => {
+                            let hint = "Start listening or connect to a peer to begin";
+                            ui.label(RichText::new(hint).color(egui::Color32::GRAY));
+                        }
                     });
 
                 ui.separator();
@@ -382,26 +1449,21 @@ impl eframe::App for P2PChatApp {
                     }
 
                     // File attachment button
-                    if ui.button("📎").on_hover_text("Attach file").clicked() {
-                        // Open file dialog
-                        if let Some(path) = rfd::FileDialog::new().pick_file() {
-                            self.send_file(path);
-                        }
+                    let attach = egui::Button::new("📎 Attach file");
+                    if ui
+                        .add_enabled(self.file_dialog.is_none(), attach)
+                        .on_hover_text("Send one or more files")
+                        .clicked()
+                    {
+                        self.pick_files();
                     }
 
-                    // Encryption indicator
-                    let lock_symbol = if self.enable_encryption {
-                        "🔒"
-                    } else {
-                        "🔓"
-                    };
-                    ui.label(lock_symbol);
+                    self.show_security_indicator(ui);
                 });
             });
             
             // Create an invisible drop target that covers the entire panel
-            let response = ui.allocate_response(ui.available_size(), Sense::hover());
-            response
+            ui.allocate_response(ui.available_size(), Sense::hover())
         })
         .inner;
         
@@ -478,63 +1540,789 @@ impl P2PChatApp {
                 ui.label(RichText::new("🔒").color(egui::Color32::GREEN));
             }
         });
+
+        // Thumbnail of a received image; clicking opens it
+        if let Some(path) = &msg.image {
+            let image = egui::Image::new(format!("file://{}", path.display()))
+                .max_size(egui::vec2(240.0, 160.0))
+                .sense(Sense::click());
+            if ui.add(image).on_hover_text("Open").clicked() {
+                if let Err(e) = FileTransfer::open_file(path) {
+                    error!("Failed to open {}: {}", path.display(), e);
+                }
+            }
+        }
     }
 
-    fn show_settings_ui(&mut self, ui: &mut Ui) {
-        ui.group(|ui| {
-            ui.label("General Settings");
+    /// Asks whether to accept the oldest unanswered file offer.
+    fn show_offer_dialog(&mut self, ctx: &Context) {
+        let Some(&(backend, id)) = self.offers.front() else {
+            return;
+        };
+        let Some(row) = self
+            .transfers
+            .iter()
+            .find(|row| row.backend == backend && row.status.id == id)
+        else {
+            self.offers.pop_front();
+            return;
+        };
 
-            ui.horizontal(|ui| {
-                ui.label("Nickname:");
-                ui.text_edit_singleline(&mut self.nickname);
+        let status = &row.status;
+        let what = match status.files {
+            Some(files) => format!(
+                "the directory {} ({} files, {})",
+                status.name,
+                files,
+                format_bytes(status.size)
+            ),
+            None => format!("{} ({})", status.name, format_bytes(status.size)),
+        };
+        let mut answer = None;
+        egui::Window::new("Incoming file")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("{} wants to send you {}", row.peer, what));
+                ui.horizontal(|ui| {
+                    if ui.button("Accept").clicked() {
+                        answer = Some(Command::AcceptTransfer(id));
+                    }
+                    if ui.button("Reject").clicked() {
+                        answer = Some(Command::CancelTransfer(id));
+                    }
+                });
             });
 
-            ui.checkbox(&mut self.enable_encryption, "Enable encryption");
-            ui.checkbox(&mut self.auto_scroll, "Auto-scroll messages");
-            ui.checkbox(&mut self.show_timestamps, "Show timestamps");
-        });
+        if let Some(command) = answer {
+            self.offers.pop_front();
+            self.send_command(backend, command);
+        }
+    }
 
-        ui.separator();
+    /// Shows both identity fingerprints and the safety number to compare
+    /// with the peer being verified.
+    fn show_verify_dialog(&mut self, ctx: &Context) {
+        let Some((backend_id, peer_id)) = self.verifying.clone() else {
+            return;
+        };
+        let backend = self
+            .backends
+            .iter()
+            .find(|backend| backend.id == backend_id);
+        let Some((backend, peer)) =
+            backend.and_then(|b| Some((b, b.peers.iter().find(|peer| peer.id == peer_id)?)))
+        else {
+            self.verifying = None;
+            return;
+        };
+
+        let mut open = true;
+        let mut verified = false;
+        egui::Window::new("Verify identity")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                let (Some(ours), Some(theirs)) = (&backend.fingerprint, &peer.fingerprint) else {
+                    ui.label(format!("Waiting for {}'s identity...", peer.name));
+                    return;
+                };
+                ui.label(format!(
+                    "Compare this safety number with {} in person or on a call. It is \
+                     the same on both sides unless someone is intercepting the connection.",
+                    peer.name
+                ));
+                ui.add_space(6.0);
+                let number = safety_number(ours, theirs);
+                let groups: Vec<&str> = number.split(' ').collect();
+                for row in groups.chunks(4) {
+                    ui.label(
+                        RichText::new(row.join("  "))
+                            .monospace()
+                            .size(18.0)
+                            .strong(),
+                    );
+                }
+                ui.add_space(6.0);
+                egui::Grid::new("fingerprints")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Your fingerprint:");
+                        ui.monospace(ours);
+                        ui.end_row();
+                        ui.label(format!("{}'s fingerprint:", peer.name));
+                        ui.monospace(theirs);
+                        ui.end_row();
+                        if let Some(expected) = &peer.key_changed {
+                            ui.colored_label(egui::Color32::RED, "Previously recorded:");
+                            ui.monospace(expected);
+                            ui.end_row();
+                        }
+                    });
+                ui.add_space(6.0);
+                if peer.verified {
+                    ui.colored_label(egui::Color32::GREEN, "✔ Verified");
+                } else if ui
+                    .button("✔ Mark as verified")
+                    .on_hover_text("Record the key in your contacts")
+                    .clicked()
+                {
+                    verified = true;
+                }
+            });
 
-        ui.group(|ui| {
-            ui.label("Connection Settings");
+        if verified {
+            self.mark_verified(backend_id, &peer_id);
+        }
+        if !open || verified {
+            self.verifying = None;
+        }
+    }
 
-            ui.horizontal(|ui| {
-                ui.label("Default Port:");
-                ui.text_edit_singleline(&mut self.listen_port);
+    /// Records a peer's key in the contacts once the safety number has been
+    /// compared, so later connections with the same key show as verified.
+    fn mark_verified(&mut self, backend: usize, peer: &str) {
+        let Some(state) = self.peer_mut(backend, peer) else {
+            return;
+        };
+        let Some(fingerprint) = state.fingerprint.clone() else {
+            return;
+        };
+        // Contact names cannot contain whitespace
+        let name = state
+            .contact
+            .clone()
+            .unwrap_or_else(|| state.name.split_whitespace().collect::<Vec<_>>().join("_"));
+        let address = state.address.to_string();
+        state.contact = Some(name.clone());
+        state.verified = true;
+        state.key_changed = None;
+        state.warning = false;
+        self.send_command(
+            backend,
+            Command::AddContact(name, address, Some(fingerprint)),
+        );
+    }
+
+    /// Shows how secure the conversation on screen is. Clicking it verifies
+    /// the peer, or picks the peer to verify in a group.
+    fn show_security_indicator(&mut self, ui: &mut Ui) {
+        let Some(conversation) = self.active.map(|index| &self.conversations[index]) else {
+            return;
+        };
+        let (Some(backend), Some((security, details))) = (
+            conversation.backend,
+            self.conversation_security(conversation),
+        ) else {
+            return;
+        };
+        let encryption = self.config.enable_encryption;
+        let peers: Vec<(String, String, Security)> = self
+            .conversation_peers(conversation)
+            .into_iter()
+            .map(|peer| {
+                (
+                    peer.id.clone(),
+                    peer.name.clone(),
+                    peer.security(encryption),
+                )
+            })
+            .collect();
+
+        let label = RichText::new(security.icon()).color(security.color());
+        let mut verify = None;
+        if let [(id, ..)] = peers.as_slice() {
+            let hover = format!("{}\nClick to verify", details);
+            if ui.button(label).on_hover_text(hover).clicked() {
+                verify = Some(id.clone());
+            }
+        } else {
+            let menu = ui.menu_button(label, |ui| {
+                for (id, name, security) in &peers {
+                    let text = format!("{} Verify {}...", security.icon(), name);
+                    if ui.button(text).clicked() {
+                        verify = Some(id.clone());
+                        ui.close_menu();
+                    }
+                }
             });
-        });
+            menu.response.on_hover_text(details);
+        }
+        if let Some(peer) = verify {
+            self.verifying = Some((backend, peer));
+        }
+    }
 
+    /// Warns about peers of the conversation on screen whose identity key
+    /// differs from the one recorded for their contact.
+    fn show_key_change_warnings(&mut self, ui: &mut Ui) {
+        let Some(conversation) = self.active.map(|index| &self.conversations[index]) else {
+            return;
+        };
+        let Some(backend) = conversation.backend else {
+            return;
+        };
+        let changed: Vec<(String, String, String, String)> = self
+            .conversation_peers(conversation)
+            .into_iter()
+            .filter(|peer| peer.warning)
+            .filter_map(|peer| {
+                let contact = peer.contact.clone().unwrap_or_else(|| peer.name.clone());
+                let expected = peer.key_changed.clone()?;
+                Some((
+                    peer.id.clone(),
+                    contact,
+                    expected,
+                    peer.fingerprint.clone()?,
+                ))
+            })
+            .collect();
+
+        let mut verify = None;
+        let mut dismissed = None;
+        for (id, contact, expected, fingerprint) in changed {
+            egui::Frame::none()
+                .fill(egui::Color32::from_rgb(150, 20, 20))
+                .inner_margin(8.0)
+                .rounding(4.0)
+                .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    let title = format!("⚠ The identity of {} has changed!", contact);
+                    ui.label(
+                        RichText::new(title)
+                            .color(egui::Color32::WHITE)
+                            .strong()
+                            .size(16.0),
+                    );
+                    ui.label(
+                        RichText::new(format!(
+                            "Expected {}, got {}. Someone may be intercepting the \
+                             conversation, or {} may have reinstalled. Verify the safety \
+                             number before sharing anything sensitive.",
+                            expected, fingerprint, contact
+                        ))
+                        .color(egui::Color32::WHITE),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Verify...").clicked() {
+                            verify = Some(id.clone());
+                        }
+                        if ui.button("Dismiss").clicked() {
+                            dismissed = Some(id.clone());
+                        }
+                    });
+                });
+            ui.add_space(4.0);
+        }
+
+        if let Some(peer) = verify {
+            self.verifying = Some((backend, peer));
+        }
+        if let Some(state) = dismissed.and_then(|peer| self.peer_mut(backend, &peer)) {
+            state.warning = false;
+        }
+    }
+
+    /// Lists each session's group conversation with its peers below it,
+    /// followed by conversations whose session has ended.
+    fn show_conversations_ui(&mut self, ui: &mut Ui) {
+        ui.heading("Conversations");
         ui.separator();
 
+        // Peers of running sessions get a private conversation once picked
+        let mut selected = None;
+        let mut private = None;
+        let mut cleared = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (index, conversation) in self.conversations.iter().enumerate() {
+                // Private conversations are listed under their session
+                if conversation.peer.is_some() {
+                    continue;
+                }
+                self.show_conversation_row(ui, index, &mut selected, &mut cleared);
+
+                let backend = self
+                    .backends
+                    .iter()
+                    .find(|b| Some(b.id) == conversation.backend);
+                let Some(backend) = backend else {
+                    continue;
+                };
+                ui.indent(("peers", index), |ui| {
+                    for peer in &backend.peers {
+                        let existing = self.conversations.iter().position(|c| {
+                            c.backend == Some(backend.id)
+                                && c.peer.as_deref() == Some(peer.name.as_str())
+                        });
+                        match existing {
+                            Some(index) => {
+                                self.show_conversation_row(ui, index, &mut selected, &mut cleared);
+                            }
+                            None => {
+                                ui.horizontal(|ui| {
+                                    let label = RichText::new(format!("👤 {}", peer.name));
+                                    let response = ui.selectable_label(false, label);
+                                    if response.on_hover_text("Message privately").clicked() {
+                                        private = Some((backend.id, peer.name.clone()));
+                                    }
+                                    let encryption = self.config.enable_encryption;
+                                    let security = peer.security(encryption);
+                                    ui.label(
+                                        RichText::new(security.icon()).color(security.color()),
+                                    )
+                                    .on_hover_text(peer.describe(encryption));
+                                });
+                            }
+                        }
+                    }
+                });
+            }
+
+            // Earlier private conversations
+            let offline: Vec<usize> = (0..self.conversations.len())
+                .filter(|&index| {
+                    let conversation = &self.conversations[index];
+                    conversation.peer.is_some() && conversation.backend.is_none()
+                })
+                .collect();
+            if !offline.is_empty() {
+                ui.separator();
+                for index in offline {
+                    self.show_conversation_row(ui, index, &mut selected, &mut cleared);
+                }
+            }
+        });
+
+        if let Some((backend, name)) = private {
+            selected = Some(self.conversation(backend, Some(&name)));
+        }
+        if let Some(index) = selected {
+            self.select_conversation(index);
+        }
+        if let Some(index) = cleared {
+            let conversation = &mut self.conversations[index];
+            conversation.messages.clear();
+            if conversation.saved {
+                if let Err(e) = self.history.clear(&conversation.key) {
+                    warn!("Failed to clear history: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Shows one conversation in the sidebar with its unread count.
+    fn show_conversation_row(
+        &self,
+        ui: &mut Ui,
+        index: usize,
+        selected: &mut Option<usize>,
+        cleared: &mut Option<usize>,
+    ) {
+        let conversation = &self.conversations[index];
+        let icon = if conversation.peer.is_some() {
+            "👤"
+        } else {
+            "💬"
+        };
+        let title = format!("{} {}", icon, self.conversation_title(conversation));
+        let mut title = RichText::new(title);
+        if conversation.backend.is_none() {
+            title = title.color(egui::Color32::GRAY);
+        }
+
         ui.horizontal(|ui| {
-            if ui.button("Save Settings").clicked() {
-                // Update config
-                self.config.nickname = Some(self.nickname.clone());
-                self.config.enable_encryption = self.enable_encryption;
-                if let Ok(port) = self.listen_port.parse::<u16>() {
-                    self.config.default_port = port;
+            let response = ui.selectable_label(self.active == Some(index), title);
+            if response.clicked() {
+                *selected = Some(index);
+            }
+            response.context_menu(|ui| {
+                if ui.button("Clear history").clicked() {
+                    *cleared = Some(index);
+                    ui.close_menu();
                 }
+            });
+            // Private conversations show how secure their peer is
+            if let Some((security, details)) = conversation
+                .peer
+                .as_ref()
+                .and_then(|_| self.conversation_security(conversation))
+            {
+                ui.label(RichText::new(security.icon()).color(security.color()))
+                    .on_hover_text(details);
+            }
+            if conversation.unread > 0 {
+                ui.label(
+                    RichText::new(format!(" {} ", conversation.unread))
+                        .color(egui::Color32::WHITE)
+                        .background_color(egui::Color32::from_rgb(200, 60, 60))
+                        .small(),
+                );
+            }
+        });
+    }
 
-                // Save to file
-                if let Err(e) = self.config.save() {
-                    error!("Failed to save config: {}", e);
-                } else {
-                    info!("Settings saved successfully");
+    fn show_transfers_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("Transfers");
+            if ui.small_button("Clear finished").clicked() {
+                self.transfers.retain(|row| !row.status.state.is_finished());
+            }
+        });
+        ui.separator();
+
+        let mut commands = Vec::new();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            // Newest first
+            for row in self.transfers.iter().rev() {
+                commands.extend(Self::show_transfer(ui, row).map(|command| (row.backend, command)));
+                ui.separator();
+            }
+        });
+        for (backend, command) in commands {
+            self.send_command(backend, command);
+        }
+    }
+
+    /// Shows one transfer and returns the command of a clicked button.
+    fn show_transfer(ui: &mut Ui, row: &TransferRow) -> Option<Command> {
+        let status = &row.status;
+        let (arrow, preposition) = match status.direction {
+            TransferDirection::Outgoing => ("⬆", "to"),
+            TransferDirection::Incoming => ("⬇", "from"),
+        };
+        ui.label(RichText::new(format!("{} {}", arrow, status.name)).strong());
+        ui.label(
+            RichText::new(format!(
+                "{} {} · {}",
+                preposition,
+                row.peer,
+                format_bytes(status.size)
+            ))
+            .color(egui::Color32::GRAY)
+            .size(10.0),
+        );
+
+        let progress = match status.state {
+            TransferState::Active if row.speed > 0.0 => {
+                format!(
+                    "{:.0}% · {}/s",
+                    status.percent(),
+                    format_bytes(row.speed as u64)
+                )
+            }
+            TransferState::Active => format!("{:.0}%", status.percent()),
+            state => format!("{:.0}% · {}", status.percent(), state),
+        };
+        ui.add(egui::ProgressBar::new(status.percent() as f32 / 100.0).text(progress));
+
+        let offered = status.state == TransferState::Waiting
+            && status.direction == TransferDirection::Incoming;
+        let id = status.id;
+        let mut buttons = Vec::new();
+        if offered {
+            buttons.push(("Accept", Command::AcceptTransfer(id)));
+            buttons.push(("Reject", Command::CancelTransfer(id)));
+        } else if !status.state.is_finished() {
+            match status.state {
+                TransferState::Active => buttons.push(("⏸ Pause", Command::PauseTransfer(id))),
+                TransferState::Paused => buttons.push(("▶ Resume", Command::ResumeTransfer(id))),
+                _ => {}
+            }
+            buttons.push(("✖ Cancel", Command::CancelTransfer(id)));
+        }
+
+        let mut command = None;
+        ui.horizontal(|ui| {
+            for (label, action) in buttons {
+                if ui.small_button(label).clicked() {
+                    command = Some(action);
                 }
             }
+        });
+        command
+    }
 
+    fn show_settings_ui(&mut self, ui: &mut Ui) {
+        let Some(draft) = self.settings.as_mut() else {
+            return;
+        };
+        draft.poll_folder_dialog();
+        let config = &mut draft.config;
+
+        egui::ScrollArea::vertical()
+            .max_height(440.0)
+            .show(ui, |ui| {
+                ui.group(|ui| {
+                    ui.label("General Settings");
+                    egui::Grid::new("general_settings")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Nickname:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut draft.nickname).hint_text("You"),
+                            );
+                            ui.end_row();
+
+                            ui.label("Log level:")
+                                .on_hover_text("Takes effect after restarting the application");
+                            egui::ComboBox::from_id_source("log_level")
+                                .selected_text(config.log_level.as_str())
+                                .show_ui(ui, |ui| {
+                                    for level in LOG_LEVELS {
+                                        let value = level.to_string();
+                                        ui.selectable_value(&mut config.log_level, value, level);
+                                    }
+                                });
+                            ui.end_row();
+                        });
+
+                    ui.checkbox(&mut self.auto_scroll, "Auto-scroll messages");
+                    ui.checkbox(&mut self.show_timestamps, "Show timestamps");
+                    ui.checkbox(&mut config.desktop_notifications, "Desktop notifications")
+                    .on_hover_text(
+                        "Notify about new messages and files while the window is in the background",
+                    );
+                });
+
+                ui.group(|ui| {
+                    ui.label("Connection Settings");
+                    let note =
+                        "Applies to new connections (port, address and discovery: new sessions)";
+                    ui.label(RichText::new(note).color(egui::Color32::GRAY).small());
+                    egui::Grid::new("connection_settings")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Default Port:");
+                            ui.add(egui::DragValue::new(&mut config.default_port))
+                                .on_hover_text("0 listens on any free port");
+                            ui.end_row();
+
+                            ui.label("Bind address:");
+                            ui.text_edit_singleline(&mut config.bind_address)
+                                .on_hover_text(
+                                    "0.0.0.0 for all IPv4 interfaces, :: for IPv4 and IPv6",
+                                );
+                            ui.end_row();
+
+                            ui.label("Connect timeout:");
+                            seconds_ui(ui, &mut config.connect_timeout_secs, 300);
+                            ui.end_row();
+
+                            ui.label("Handshake timeout:");
+                            seconds_ui(ui, &mut config.handshake_timeout_secs, 300);
+                            ui.end_row();
+
+                            ui.label("Heartbeat interval:");
+                            seconds_ui(ui, &mut config.heartbeat_interval_secs, 3600);
+                            ui.end_row();
+
+                            ui.label("Reconnect attempts:");
+                            ui.add(
+                                egui::DragValue::new(&mut config.reconnect_attempts).range(0..=100),
+                            );
+                            ui.end_row();
+
+                            ui.label("Reconnect delay:");
+                            seconds_ui(ui, &mut config.reconnect_delay_secs, 3600);
+                            ui.end_row();
+
+                            ui.label("Buffer size:");
+                            ui.add(
+                                egui::DragValue::new(&mut config.buffer_size)
+                                    .range(1024..=1024 * 1024)
+                                    .speed(64)
+                                    .suffix(" bytes"),
+                            );
+                            ui.end_row();
+                        });
+
+                    ui.checkbox(&mut config.enable_encryption, "Enable encryption");
+                    ui.checkbox(&mut config.enable_compression, "Compress file transfers");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut config.enable_discovery, "LAN discovery on");
+                        ui.add_enabled(
+                            config.enable_discovery,
+                            egui::TextEdit::singleline(&mut config.discovery_address)
+                                .desired_width(160.0),
+                        );
+                    });
+                });
+
+                ui.group(|ui| {
+                    ui.label("File Transfers");
+                    egui::Grid::new("transfer_settings")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Download folder:");
+                            ui.horizontal(|ui| {
+                                // The default folder is shown greyed out
+                                let folder = match &config.download_dir {
+                                    Some(dir) => RichText::new(dir.display().to_string()),
+                                    None => {
+                                        let dir = Config::default().download_path();
+                                        RichText::new(dir.display().to_string())
+                                            .color(egui::Color32::GRAY)
+                                    }
+                                };
+                                ui.label(folder);
+                                let browse = egui::Button::new("Browse…");
+                                if ui
+                                    .add_enabled(draft.folder_dialog.is_none(), browse)
+                                    .clicked()
+                                {
+                                    draft.folder_dialog = Some(pick_folder());
+                                }
+                                if config.download_dir.is_some()
+                                    && ui.small_button("Default").clicked()
+                                {
+                                    config.download_dir = None;
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label("Max file size:");
+                            ui.add(
+                                egui::DragValue::new(&mut config.max_file_size_mb)
+                                    .range(1..=100_000)
+                                    .suffix(" MB"),
+                            );
+                            ui.end_row();
+
+                            ui.label("Upload limit:");
+                            limit_ui(ui, &mut config.max_upload_kbps);
+                            ui.end_row();
+
+                            ui.label("Download limit:");
+                            limit_ui(ui, &mut config.max_download_kbps);
+                            ui.end_row();
+
+                            ui.label("Media extensions:");
+                            ui.text_edit_singleline(&mut draft.media_extensions)
+                                .on_hover_text("Comma-separated, e.g. jpg, png, mp4");
+                            ui.end_row();
+                        });
+                    ui.checkbox(
+                        &mut config.auto_open_media,
+                        "Open received media automatically",
+                    );
+                });
+
+                ui.group(|ui| {
+                    ui.label("History");
+                    ui.checkbox(
+                        &mut config.save_history,
+                        "Save messages of each conversation",
+                    );
+                    ui.horizontal(|ui| {
+                        ui.label("History file:");
+                        let default = Config::default().history_path().unwrap_or_default();
+                        ui.add_enabled(
+                            config.save_history,
+                            egui::TextEdit::singleline(&mut draft.history_file)
+                                .hint_text(default.display().to_string()),
+                        );
+                    });
+                });
+            });
+
+        ui.separator();
+
+        // Settings are checked as they are edited
+        let edited = draft.to_config();
+        if let Err(e) = &edited {
+            ui.colored_label(egui::Color32::RED, e.to_string());
+        } else if let Some(notice) = &draft.notice {
+            ui.colored_label(egui::Color32::GREEN, notice);
+        }
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            let valid = edited.is_ok();
+            let apply = ui.add_enabled(valid, egui::Button::new("Apply"));
+            if apply.on_hover_text("Use without saving").clicked() {
+                action = Some(SettingsAction::Apply);
+            }
+            if ui
+                .add_enabled(valid, egui::Button::new("Save Settings"))
+                .clicked()
+            {
+                action = Some(SettingsAction::Save);
+            }
+            if ui.button("Revert").clicked() {
+                action = Some(SettingsAction::Revert);
+            }
             if ui.button("Reset to Defaults").clicked() {
-                self.config = Config::default();
-                self.listen_port = self.config.default_port.to_string();
-                self.nickname = self
-                    .config
-                    .nickname
-                    .clone()
-                    .unwrap_or_else(|| "You".to_string());
-                self.enable_encryption = self.config.enable_encryption;
+                action = Some(SettingsAction::Defaults);
             }
         });
+
+        match (action, edited) {
+            (Some(SettingsAction::Apply), Ok(config)) => {
+                self.apply_settings(config);
+                self.set_settings_notice("Settings applied".to_string());
+            }
+            (Some(SettingsAction::Save), Ok(config)) => {
+                self.apply_settings(config);
+                let notice = match self.config.save() {
+                    Ok(()) => {
+                        info!("Settings saved successfully");
+                        "Settings saved".to_string()
+                    }
+                    Err(e) => {
+                        error!("Failed to save config: {}", e);
+                        e.to_string()
+                    }
+                };
+                self.set_settings_notice(notice);
+            }
+            (Some(SettingsAction::Revert), _) => {
+                self.settings = Some(SettingsDraft::new(&self.config));
+            }
+            (Some(SettingsAction::Defaults), _) => {
+                self.settings = Some(SettingsDraft::new(&Config::default()));
+            }
+            _ => {}
+        }
+    }
+
+    fn set_settings_notice(&mut self, notice: String) {
+        if let Some(draft) = &mut self.settings {
+            draft.notice = Some(notice);
+        }
+    }
+
+    /// Makes edited settings current and passes them to the running sessions.
+    fn apply_settings(&mut self, config: Config) {
+        let history_changed = config.save_history != self.config.save_history
+            || config.history_path() != self.config.history_path();
+        self.config = config;
+        self.nickname = self
+            .config
+            .nickname
+            .clone()
+            .unwrap_or_else(|| "You".to_string());
+        self.enable_encryption = self.config.enable_encryption;
+        self.notifications = self.config.desktop_notifications;
+        self.listen_port = self.config.default_port.to_string();
+        if history_changed {
+            self.history = ChatHistory::load_for(&self.config);
+        }
+
+        let config = self.session_config();
+        for backend in &self.backends {
+            let _ = backend.session.update_config(config.clone());
+        }
+    }
+
+    /// Returns the settings sessions started by the GUI run with.
+    fn session_config(&self) -> Config {
+        let mut config = self.config.clone();
+        // Offers are answered in the dialog instead
+        config.auto_accept_transfers = false;
+        config
     }
 }
 
@@ -543,7 +2331,7 @@ pub fn run_gui() -> Result<()> {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([800.0, 600.0])
             .with_min_inner_size([400.0, 300.0])
-            .with_title("Rust P2P Chat - Drag & Drop Files")
+            .with_title(WINDOW_TITLE)
             .with_drag_and_drop(true)
             .with_icon(load_icon()),
         ..Default::default()
@@ -552,14 +2340,104 @@ pub fn run_gui() -> Result<()> {
     eframe::run_native(
         "Rust P2P Chat",
         options,
-        Box::new(|_cc| Ok(Box::new(P2PChatApp::new()))),
+        Box::new(|cc| {
+            // Loads the thumbnails of received images
+            egui_extras::install_image_loaders(&cc.egui_ctx);
+            Ok(Box::new(P2PChatApp::new()))
+        }),
     )
-    .map_err(|e| {
-        ChatError::Io(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            e.to_string(),
-        ))
-    })?;
+    .map_err(|e| ChatError::Io(std::io::Error::other(e.to_string())))?;
 
     Ok(())
 }
+
+/// Opens the native folder dialog on a thread of its own.
+fn pick_folder() -> Promise<Option<PathBuf>> {
+    let dialog = rfd::AsyncFileDialog::new()
+        .set_title("Download folder")
+        .pick_folder();
+    Promise::spawn_thread("folder_dialog", move || {
+        futures::executor::block_on(dialog).map(|folder| folder.path().to_path_buf())
+    })
+}
+
+/// Edits a duration of at least one second.
+fn seconds_ui(ui: &mut Ui, secs: &mut u64, max: u64) {
+    ui.add(egui::DragValue::new(secs).range(1..=max).suffix(" s"));
+}
+
+/// Edits an optional bandwidth limit in KB/s.
+fn limit_ui(ui: &mut Ui, limit: &mut Option<u64>) {
+    ui.horizontal(|ui| {
+        let mut limited = limit.is_some();
+        if ui.checkbox(&mut limited, "").changed() {
+            *limit = limited.then_some(1024);
+        }
+        match limit {
+            Some(kbps) => {
+                ui.add(
+                    egui::DragValue::new(kbps)
+                        .range(1..=1_000_000)
+                        .suffix(" KB/s"),
+                );
+            }
+            None => {
+                ui.label(RichText::new("Unlimited").color(egui::Color32::GRAY));
+            }
+        }
+    });
+}
+
+/// Closes a session and stops its runtime on a thread of its own.
+///
+/// Without a session the runtime is just dropped, which stops whatever is
+/// still running on it.
+fn shut_down(session: Option<ChatSession>, runtime: Runtime) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        if let Some(session) = session {
+            if let Err(e) = runtime.block_on(session.close()) {
+                error!("Chat session ended with error: {}", e);
+            }
+        }
+        // Stops whatever is still running, such as peers that did not
+        // close in time
+        drop(runtime);
+    })
+}
+
+/// Shows a desktop notification on a thread of its own, as some platforms
+/// wait for the notification server to answer.
+fn show_notification(summary: String, body: String) {
+    thread::spawn(move || {
+        let result = notify_rust::Notification::new()
+            .appname("Rust P2P Chat")
+            .summary(&summary)
+            .body(&body)
+            .show();
+        if let Err(e) = result {
+            warn!("Failed to show notification: {}", e);
+        }
+    });
+}
+
+/// Returns `true` if `path` is an image that can be shown as a thumbnail.
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Formats a byte count for display, e.g. `1.5 MB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
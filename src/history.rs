@@ -0,0 +1,274 @@
+//! Chat history stored per conversation.
+//!
+//! The history keeps the most recent messages of every conversation, keyed
+//! by a conversation name such as a peer's nickname. It is stored in the
+//! history file (see [`Config::history_path`]) when `save_history` is
+//! enabled, so the GUI can show earlier messages when a conversation is
+//! opened again. The file holds one JSON record per message, so recording a
+//! message appends a line instead of rewriting the whole file.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use rust_p2p_chat::history::{ChatHistory, HistoryEntry};
+//!
+//! let mut history = ChatHistory::in_memory();
+//! history
+//!     .push("alice", HistoryEntry::new("alice", "Hello!"))
+//!     .unwrap();
+//!
+//! assert_eq!(history.entries("alice")[0].text, "Hello!");
+//! assert!(history.entries("bob").is_empty());
+//! ```
+
+use crate::config::Config;
+use crate::error::{ChatError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Number of messages kept for each conversation; older ones are dropped.
+pub const MAX_ENTRIES: usize = 1000;
+
+/// A message recorded in the history.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    /// Display name of whoever wrote the message.
+    pub sender: String,
+    /// The message text, or a description of a file.
+    pub text: String,
+    /// When the message was sent or received, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Whether the message was encrypted.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Whether the entry describes a file transfer.
+    #[serde(default)]
+    pub is_file: bool,
+}
+
+impl HistoryEntry {
+    /// Creates an unencrypted text entry timestamped now.
+    pub fn new(sender: impl Into<String>, text: impl Into<String>) -> Self {
+        HistoryEntry {
+            sender: sender.into(),
+            text: text.into(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            encrypted: false,
+            is_file: false,
+        }
+    }
+}
+
+/// A line of the history file: one message of a conversation.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    conversation: String,
+    #[serde(flatten)]
+    entry: HistoryEntry,
+}
+
+/// The single JSON object earlier versions stored the history as.
+#[derive(Debug, Deserialize)]
+struct HistoryFile {
+    #[serde(default)]
+    conversations: BTreeMap<String, Vec<HistoryEntry>>,
+}
+
+/// Messages by conversation, optionally persisted to a file.
+#[derive(Debug, Default)]
+pub struct ChatHistory {
+    /// Where the history is persisted, if anywhere.
+    path: Option<PathBuf>,
+    /// Entries by conversation, oldest first.
+    conversations: BTreeMap<String, Vec<HistoryEntry>>,
+    /// Number of records in the file, including dropped entries.
+    logged: usize,
+    /// Whether the file must be rewritten before records can be appended,
+    /// because it is in the old format or ends in a corrupt line.
+    rewrite: bool,
+}
+
+impl ChatHistory {
+    /// Creates a history that is never written to disk.
+    pub fn in_memory() -> Self {
+        ChatHistory::default()
+    }
+
+    /// Loads the history stored at `path`.
+    ///
+    /// A missing file results in an empty history; corrupt records are
+    /// logged and ignored. Changes are saved back to `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the history file
+    pub fn load(path: &Path) -> Self {
+        let mut history = ChatHistory {
+            path: Some(path.to_path_buf()),
+            ..ChatHistory::default()
+        };
+        let Ok(contents) = fs::read_to_string(path) else {
+            return history;
+        };
+
+        let mut corrupt = false;
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str::<Record>(line) {
+                Ok(record) => {
+                    history.insert(record.conversation, record.entry);
+                    history.logged += 1;
+                }
+                Err(_) => corrupt = true,
+            }
+        }
+        if history.logged == 0 {
+            if let Ok(file) = serde_json::from_str::<HistoryFile>(&contents) {
+                history.conversations = file.conversations;
+                history.rewrite = true;
+                corrupt = false;
+            }
+        }
+        if corrupt {
+            warn!(
+                "Ignoring corrupt records in history file {}",
+                path.display()
+            );
+        }
+        history.rewrite |= corrupt || !(contents.is_empty() || contents.ends_with('\n'));
+        history
+    }
+
+    /// Loads the history file belonging to `config`.
+    ///
+    /// Falls back to an in-memory history if `save_history` is disabled or
+    /// the platform directories cannot be determined.
+    pub fn load_for(config: &Config) -> Self {
+        config
+            .history_path()
+            .filter(|_| config.save_history)
+            .map(|path| ChatHistory::load(&path))
+            .unwrap_or_else(ChatHistory::in_memory)
+    }
+
+    /// Records a message in a conversation.
+    ///
+    /// Only the last [`MAX_ENTRIES`] messages of each conversation are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation` - Name of the conversation, e.g. a peer's nickname
+    /// * `entry` - The message
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` if the history file cannot be written
+    pub fn push(&mut self, conversation: &str, entry: HistoryEntry) -> Result<()> {
+        self.insert(conversation.to_string(), entry.clone());
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        // Dropped entries stay in the file until it is rewritten, which
+        // happens once it holds twice as many records as are kept
+        let kept: usize = self.conversations.values().map(Vec::len).sum();
+        if self.rewrite || self.logged >= 2 * kept {
+            return self.save();
+        }
+        let record = Record {
+            conversation: conversation.to_string(),
+            entry,
+        };
+        let mut line = serde_json::to_string(&record)
+            .map_err(|e| ChatError::Configuration(format!("Failed to encode history: {}", e)))?;
+        line.push('\n');
+        create_parent(path)?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| ChatError::Configuration(format!("Failed to write history: {}", e)))?;
+        self.logged += 1;
+        Ok(())
+    }
+
+    /// Returns the messages of a conversation, oldest first.
+    pub fn entries(&self, conversation: &str) -> &[HistoryEntry] {
+        self.conversations
+            .get(conversation)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the names of all conversations with messages, sorted.
+    pub fn conversations(&self) -> impl Iterator<Item = &String> {
+        self.conversations.keys()
+    }
+
+    /// Forgets the messages of a conversation.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Configuration` if the history file cannot be written
+    pub fn clear(&mut self, conversation: &str) -> Result<()> {
+        if self.conversations.remove(conversation).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Adds an entry to a conversation, dropping the oldest beyond
+    /// [`MAX_ENTRIES`].
+    fn insert(&mut self, conversation: String, entry: HistoryEntry) {
+        let entries = self.conversations.entry(conversation).or_default();
+        entries.push(entry);
+        if entries.len() > MAX_ENTRIES {
+            let excess = entries.len() - MAX_ENTRIES;
+            entries.drain(..excess);
+        }
+    }
+
+    /// Rewrites the file with only the entries that are kept.
+    fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        create_parent(path)?;
+        let mut contents = String::new();
+        for (conversation, entries) in &self.conversations {
+            for entry in entries {
+                let record = Record {
+                    conversation: conversation.clone(),
+                    entry: entry.clone(),
+                };
+                let line = serde_json::to_string(&record).map_err(|e| {
+                    ChatError::Configuration(format!("Failed to encode history: {}", e))
+                })?;
+                contents.push_str(&line);
+                contents.push('\n');
+            }
+        }
+        fs::write(path, contents)
+            .map_err(|e| ChatError::Configuration(format!("Failed to write history: {}", e)))?;
+        self.logged = self.conversations.values().map(Vec::len).sum();
+        self.rewrite = false;
+        Ok(())
+    }
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            ChatError::Configuration(format!("Failed to create history directory: {}", e))
+        })?;
+    }
+    Ok(())
+}
@@ -30,44 +30,92 @@
 //!
 //! - [`P2PChat`]: Main application orchestrator
 //! - [`config::Config`]: Configuration management
+//! - [`contacts::Contacts`]: Named peers with identity fingerprints
+//! - [`discovery::Discovery`]: Opt-in peer discovery on the local network
 //! - [`file_transfer::FileTransfer`]: File operations
+//! - [`history::ChatHistory`]: Saved messages of each conversation
+//! - [`transfer::TransferManager`]: Chunked transfers with pause/resume/cancel
+//! - [`compression`]: Negotiated compression of transfer chunks
+//! - [`bandwidth`]: Upload and download limits for transfers
 //! - [`encryption::E2EEncryption`]: End-to-end encryption
 //! - [`protocol`]: Message types and serialization
 //! - [`commands`]: Command system
+//! - [`events`]: Events published to frontends such as the GUI
+//! - [`session::ChatSession`]: Handle to a running session, for embedding the chat
+//! - [`terminal`]: The terminal frontend
+//! - [`completion`]: Tab completion for the terminal
+//! - [`tui`]: The full-screen terminal frontend
 
+pub mod bandwidth;
 pub mod colors;
 pub mod commands;
+pub mod completion;
+pub mod compression;
 pub mod config;
+pub mod contacts;
+pub mod content_cache;
+pub mod discovery;
 pub mod encryption;
 pub mod error;
+pub mod events;
 pub mod file_transfer;
 pub mod gui;
+pub mod history;
 pub mod peer;
 pub mod protocol;
 pub mod reliability;
+pub mod session;
+pub mod terminal;
+pub mod transfer;
+pub mod tui;
 
 use futures::future::try_join;
+use rsa::RsaPrivateKey;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
 use std::io;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::select;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
 use tracing::{debug, error, info, instrument, warn};
 
+use crate::bandwidth::BandwidthLimits;
 use crate::colors::Colors;
 use crate::commands::CommandHandler;
+use crate::config::PortRange;
+use crate::contacts::Contacts;
+use crate::content_cache::ContentCache;
+use crate::discovery::{Announcement, Discovery};
 use crate::encryption::E2EEncryption;
-use crate::peer::PeerManager;
-use crate::protocol::{Command, EncryptionMessage, Message, MessageType, StatusUpdate};
+use crate::events::{ChatEvent, EVENT_CAPACITY};
+use crate::peer::{Peer, PeerInfo, PeerManager};
+use crate::protocol::{
+    Capabilities, Command, Compression, EncryptionMessage, Frame, FrameDecoder, GossipMessage,
+    Message, MessageType, StatusUpdate, GOSSIP_TTL,
+};
+use crate::session::{ChatSession, Input};
+use crate::transfer::{TransferEvent, TransferManager};
 // Note: ReliabilityManager integration is prepared but not fully connected in this implementation
 
 // Re-export important types for library users
 pub use crate::config::Config;
 pub use crate::error::{ChatError, Result};
 
+/// How long leaving waits for goodbyes and queued messages to be sent.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the progress of running transfers is published.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
 /// A decentralized peer-to-peer chat application.
 ///
 /// `P2PChat` provides a complete implementation of a peer-to-peer chat system
@@ -115,6 +163,14 @@ pub use crate::error::{ChatError, Result};
 pub struct P2PChat {
     /// Application configuration
     config: Config,
+    /// Ports to try instead of the one passed to `start`, if set
+    port_range: Option<PortRange>,
+    /// Address the listener is bound to, once `start` has bound it
+    local_addr: watch::Sender<Option<SocketAddr>>,
+    /// Events published to frontends
+    events: broadcast::Sender<ChatEvent>,
+    /// Cancelled to end the session gracefully
+    shutdown: CancellationToken,
 }
 
 impl P2PChat {
@@ -133,19 +189,72 @@ impl P2PChat {
     /// let chat = P2PChat::new(config).unwrap();
     /// ```
     pub fn new(config: Config) -> Result<Self> {
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            port_range: None,
+            local_addr: watch::channel(None).0,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            shutdown: CancellationToken::new(),
+        })
+    }
+
+    /// Returns a receiver for the events of the session.
+    ///
+    /// Subscribe before the session starts so no events are missed. Events
+    /// are published whether or not anyone is subscribed; a
+    /// [`ChatSession`] subscribes for you.
+    pub fn subscribe(&self) -> broadcast::Receiver<ChatEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns a token that ends the session when cancelled.
+    ///
+    /// Cancelling it has the same effect as `/quit`: every peer is told we
+    /// are leaving, unfinished transfers are cancelled and messages already
+    /// queued are still delivered. `main` cancels it on Ctrl+C.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Listens on the first free port in `range` instead of a single port.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_p2p_chat::{config::PortRange, Config, P2PChat};
+    ///
+    /// let range: PortRange = "8080-8090".parse().unwrap();
+    /// let chat = P2PChat::new(Config::default()).unwrap().with_port_range(range);
+    /// ```
+    pub fn with_port_range(mut self, range: PortRange) -> Self {
+        self.port_range = Some(range);
+        self
     }
 
-    /// Starts the P2P chat application.
-    #[instrument(skip(self), fields(port = listen_port, peer = peer_address.as_deref().unwrap_or("none")))]
+    /// Returns a receiver for the address the chat listens on.
+    ///
+    /// The value is `None` until [`start`](Self::start) has bound its
+    /// listener, and then holds the actual address, which differs from the
+    /// requested one when port 0 or a port range is used. The discovery
+    /// announcer and the GUI use it to show the real port.
+    pub fn local_addr(&self) -> watch::Receiver<Option<SocketAddr>> {
+        self.local_addr.subscribe()
+    }
+
+    /// Starts the P2P chat application in the terminal.
     ///
     /// This method either connects to a peer at the specified address or starts
-    /// listening for incoming connections on the given port.
+    /// listening for incoming connections on the given port. The listener is
+    /// bound to the configured `bind_address`. Input is read from stdin and
+    /// events are printed by the [`terminal`] frontend; use
+    /// [`ChatSession::start`] to run the chat without a terminal.
     ///
     /// # Arguments
     ///
-    /// * `listen_port` - Port number to listen on (if not connecting to a peer)
-    /// * `peer_address` - Optional peer address to connect to (format: "ip:port")
+    /// * `listen_port` - Port number to listen on, or 0 for any free port;
+    ///   ignored if a port range was set with [`with_port_range`](Self::with_port_range)
+    /// * `peer_address` - Optional peer address to connect to (format: "ip:port",
+    ///   or "[ipv6]:port")
     ///
     /// # Returns
     ///
@@ -172,92 +281,192 @@ impl P2PChat {
     ///
     /// # Errors
     ///
-    /// - `ChatError::BindFailed` if the port is already in use
+    /// - `ChatError::Configuration` if the bind address is invalid
+    /// - `ChatError::BindFailed` if the port, or every port in the range, is
+    ///   already in use
     /// - `ChatError::ConnectFailed` if connection to peer fails
     /// - `ChatError::Io` for other network-related errors
     pub async fn start(&mut self, listen_port: u16, peer_address: Option<String>) -> Result<()> {
-        let addr = format!("0.0.0.0:{}", listen_port);
-        debug!("Attempting to bind to address: {}", addr);
+        let (session, input) = ChatSession::attach(self);
+        let config = self.config.clone();
+        let (result, frontend) = tokio::join!(
+            self.run(listen_port, peer_address, input),
+            terminal::run(session, &config)
+        );
+        result.and(frontend)
+    }
+
+    /// Starts the chat like [`start`](Self::start), in the full-screen
+    /// terminal UI of [`tui`].
+    ///
+    /// # Arguments
+    ///
+    /// * `listen_port` - Port to listen on for incoming connections
+    /// * `peer_address` - Optional peer address to connect to
+    ///
+    /// # Errors
+    ///
+    /// Those of [`start`](Self::start), and `ChatError::Io` if the terminal
+    /// cannot be set up.
+    pub async fn start_tui(
+        &mut self,
+        listen_port: u16,
+        peer_address: Option<String>,
+    ) -> Result<()> {
+        let (session, input) = ChatSession::attach(self);
+        let (result, frontend) = tokio::join!(
+            self.run(listen_port, peer_address, input),
+            tui::run(session)
+        );
+        result.and(frontend)
+    }
 
-        let listener = TcpListener::bind(&addr).await.map_err(|e| {
-            error!("Failed to bind to {}: {}", addr, e);
-            ChatError::BindFailed(addr.clone(), e)
-        })?;
+    /// Runs the session until `input` is closed, the user quits or the
+    /// shutdown token is cancelled, then says goodbye to every peer.
+    ///
+    /// Everything that happens is published as a [`ChatEvent`].
+    #[instrument(skip(self, input), fields(port = listen_port, peer = peer_address.as_deref().unwrap_or("none")))]
+    pub(crate) async fn run(
+        &mut self,
+        listen_port: u16,
+        peer_address: Option<String>,
+        input: mpsc::UnboundedReceiver<Input>,
+    ) -> Result<()> {
+        let range = self.port_range.unwrap_or(PortRange {
+            start: listen_port,
+            end: listen_port,
+        });
+        let listener = self.bind_in_range(range)?;
+        let addr = listener.local_addr()?;
+        self.local_addr.send_replace(Some(addr));
 
         info!("Successfully bound to address: {}", addr);
-        println!(
-            "{}Listening on: {}{}",
-            Colors::BRIGHT_GREEN,
-            addr,
-            Colors::RESET
-        );
-        println!(
-            "{}Type /help for available commands{}",
-            Colors::DIM,
-            Colors::RESET
-        );
+
+        let mut context = SessionContext::new(
+            self.config.clone(),
+            self.events.clone(),
+            self.shutdown.clone(),
+        )?;
+        context.emit(ChatEvent::Listening(addr));
+        let fingerprint = E2EEncryption::with_identity(context.identity.clone()).fingerprint()?;
+        context.emit(ChatEvent::LocalIdentity(fingerprint.clone()));
+        if self.config.enable_discovery {
+            let announcement = Announcement {
+                nickname: self.config.nickname.clone(),
+                port: addr.port(),
+                fingerprint,
+            };
+            match Discovery::from_config(&self.config, announcement) {
+                Ok(discovery) => {
+                    let discovery = Arc::new(discovery);
+                    tokio::spawn(run_discovery(
+                        discovery.clone(),
+                        context.events.clone(),
+                        context.shutdown.clone(),
+                    ));
+                    context.discovery = Some(discovery);
+                    context.emit(ChatEvent::Notice(format!(
+                        "Discovering peers on {}",
+                        self.config.discovery_address
+                    )));
+                }
+                Err(e) => context.emit(ChatEvent::Warning(format!(
+                    "LAN discovery unavailable: {}",
+                    e
+                ))),
+            }
+        }
+        let context = Arc::new(context);
+        let mut input_handle = tokio::spawn(handle_enhanced_input(context.clone(), input));
 
         if let Some(peer_addr) = peer_address {
             info!(
                 "Attempting dual mode: connect to {} or accept incoming",
                 peer_addr
             );
-            self.connect_or_accept(listener, &peer_addr).await?;
+            select! {
+                result = self.connect_or_accept(&listener, &peer_addr, &context) => result?,
+                _ = &mut input_handle => context.shutdown.cancel(),
+                _ = context.shutdown.cancelled() => {}
+            }
         } else {
-            info!("Listen-only mode: waiting for peer connection");
-            println!(
-                "{}Waiting for peer to connect...{}",
-                Colors::YELLOW,
-                Colors::RESET
-            );
-            let (stream, peer_addr) = listener.accept().await?;
-            info!("Accepted connection from: {}", peer_addr);
-            println!(
-                "{}✓ Peer connected from: {}{}",
-                Colors::BRIGHT_GREEN,
-                peer_addr,
-                Colors::RESET
-            );
-            handle_enhanced_connection(stream, self.config.clone()).await?;
+            info!("Listen-only mode: waiting for peer connections");
+            context.emit(ChatEvent::Warning(
+                "Waiting for peers to connect...".to_string(),
+            ));
+        }
+
+        // Keep accepting so more peers can join the chat
+        while !context.shutdown.is_cancelled() {
+            select! {
+                result = listener.accept() => match result {
+                    Ok((stream, peer_addr)) => {
+                        info!("Accepted connection from: {}", canonical_address(peer_addr));
+                        spawn_peer(stream, false, context.clone());
+                    }
+                    Err(e) => warn!("Failed to accept connection: {}", e),
+                },
+                _ = &mut input_handle => break,
+                _ = context.shutdown.cancelled() => break,
+            }
         }
 
+        context.close().await;
+        input_handle.abort();
         info!("Chat session completed");
         Ok(())
     }
 
-    async fn connect_or_accept(&mut self, listener: TcpListener, peer_addr: &str) -> Result<()> {
+    /// Binds to the first port in `range` that is not in use.
+    fn bind_in_range(&self, range: PortRange) -> Result<TcpListener> {
+        for port in range.ports() {
+            let addr = self.config.listen_address(port)?;
+            debug!("Attempting to bind to address: {}", addr);
+            match bind_listener(addr) {
+                Ok(listener) => return Ok(listener),
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse && port < range.end => {
+                    debug!("Port {} is in use, trying the next one", port);
+                }
+                Err(e) => {
+                    error!("Failed to bind to {}: {}", addr, e);
+                    let addr = if range.start == range.end {
+                        addr.to_string()
+                    } else {
+                        format!("{}-{}", self.config.listen_address(range.start)?, range.end)
+                    };
+                    return Err(ChatError::BindFailed(addr, e));
+                }
+            }
+        }
+        Err(ChatError::Configuration(format!(
+            "Invalid port range '{}'",
+            range
+        )))
+    }
+
+    async fn connect_or_accept(
+        &self,
+        listener: &TcpListener,
+        peer_addr: &str,
+        context: &Arc<SessionContext>,
+    ) -> Result<()> {
         debug!("Starting connect-or-accept race for peer: {}", peer_addr);
-        println!(
-            "{}Attempting to connect to peer at: {}{}",
-            Colors::YELLOW,
-            peer_addr,
-            Colors::RESET
-        );
+        context.emit(ChatEvent::Connecting(peer_addr.to_string()));
 
         select! {
             result = listener.accept() => {
                 let (stream, addr) = result?;
+                let addr = canonical_address(addr);
                 info!("Won race by accepting connection from: {}", addr);
-                println!("{}✓ Peer connected from: {}{}", Colors::BRIGHT_GREEN, addr, Colors::RESET);
-                handle_enhanced_connection(stream, self.config.clone()).await?;
-            }
-            result = TcpStream::connect(peer_addr) => {
-                match result {
-                    Ok(stream) => {
-                        let addr = stream.peer_addr()?;
-                        info!("Won race by connecting to peer at: {}", addr);
-                        println!("{}✓ Connected to peer at: {}{}", Colors::BRIGHT_GREEN, addr, Colors::RESET);
-                        handle_enhanced_connection(stream, self.config.clone()).await?;
-                    }
-                    Err(e) => {
-                        warn!("Failed to connect to {}: {}. Falling back to accept", peer_addr, e);
-                        println!("{}Failed to connect: {}. Waiting for incoming connection...{}",
-                                Colors::YELLOW, e, Colors::RESET);
-                        let (stream, addr) = listener.accept().await?;
-                        info!("Fallback: accepted connection from: {}", addr);
-                        println!("{}✓ Peer connected from: {}{}", Colors::BRIGHT_GREEN, addr, Colors::RESET);
-                        handle_enhanced_connection(stream, self.config.clone()).await?;
-                    }
+                spawn_peer(stream, false, context.clone());
+            }
+            result = connect_to(peer_addr, context) => {
+                if let Err(e) = result {
+                    warn!("{}. Falling back to accept", e);
+                    context.emit(ChatEvent::Warning(format!(
+                        "{}. Waiting for incoming connections...",
+                        e
+                    )));
                 }
             }
         }
@@ -265,19 +474,237 @@ impl P2PChat {
     }
 }
 
-// Enhanced connection handler with new features
-#[instrument(skip(stream, config), fields(peer_addr = ?stream.peer_addr()))]
-pub async fn handle_enhanced_connection(stream: TcpStream, config: Config) -> Result<()> {
+/// Returns `true` if `addr` is the IPv6 wildcard, which also accepts IPv4.
+pub(crate) fn is_dual_stack(addr: &SocketAddr) -> bool {
+    addr.is_ipv6() && addr.ip().is_unspecified()
+}
+
+/// Shows IPv4 peers accepted by a dual-stack listener as plain IPv4 addresses.
+fn canonical_address(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
+
+/// Creates the TCP listener for incoming peers.
+///
+/// Binding to `::` accepts both IPv6 and IPv4 connections, regardless of the
+/// platform's default for IPv6 sockets.
+fn bind_listener(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if is_dual_stack(&addr) {
+        socket.set_only_v6(false)?;
+    }
+    // Same as TcpListener::bind, so a restarted node can reuse the port at once
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Connects to a peer and adds it to the session.
+///
+/// Used both at startup, racing against incoming connections, and by the
+/// `/connect` command.
+///
+/// # Errors
+///
+/// - `ChatError::ConnectFailed` if the peer cannot be reached, or with
+///   `TimedOut` if it does not answer within `connect_timeout_secs`
+async fn connect_to(peer_addr: &str, context: &Arc<SessionContext>) -> Result<()> {
+    let connect_timeout = Duration::from_secs(context.config().connect_timeout_secs);
+    let stream = match timeout(connect_timeout, TcpStream::connect(peer_addr)).await {
+        Ok(result) => result.map_err(|e| ChatError::ConnectFailed(peer_addr.to_string(), e))?,
+        Err(_) => {
+            return Err(ChatError::ConnectFailed(
+                peer_addr.to_string(),
+                io::ErrorKind::TimedOut.into(),
+            ))
+        }
+    };
+    info!("Connected to peer at: {}", stream.peer_addr()?);
+    spawn_peer(stream, true, context.clone());
+    Ok(())
+}
+
+/// State shared by every connection of a chat session.
+struct SessionContext {
+    /// Settings of the session, which frontends may replace while it runs.
+    config: RwLock<Config>,
+    peers: PeerManager,
+    limits: Arc<BandwidthLimits>,
+    content_cache: Arc<tokio::sync::Mutex<ContentCache>>,
+    /// Our identity key, shared by the encryption sessions of all peers.
+    identity: RsaPrivateKey,
+    /// LAN discovery, if enabled in the configuration.
+    discovery: Option<Arc<Discovery>>,
+    /// Events published to frontends.
+    events: broadcast::Sender<ChatEvent>,
+    /// Cancelled when the session ends; peers then say goodbye.
+    shutdown: CancellationToken,
+    /// Tasks serving the peers, waited for on shutdown.
+    tasks: TaskTracker,
+}
+
+impl SessionContext {
+    fn new(
+        config: Config,
+        events: broadcast::Sender<ChatEvent>,
+        shutdown: CancellationToken,
+    ) -> Result<Self> {
+        let content_cache = config
+            .content_cache_path()
+            .map(|path| ContentCache::load(&path))
+            .unwrap_or_else(ContentCache::in_memory);
+        let identity = match config.identity_path() {
+            Some(path) => E2EEncryption::load_or_create_identity(&path).or_else(|e| {
+                warn!("Using a temporary identity key: {}", e);
+                E2EEncryption::generate_identity()
+            })?,
+            None => E2EEncryption::generate_identity()?,
+        };
+        let peers = PeerManager::new().0;
+        peers.set_local_nickname(config.nickname.clone());
+        Ok(SessionContext {
+            peers,
+            limits: Arc::new(BandwidthLimits::from_config(&config)),
+            content_cache: Arc::new(tokio::sync::Mutex::new(content_cache)),
+            identity,
+            discovery: None,
+            events,
+            shutdown,
+            tasks: TaskTracker::new(),
+            config: RwLock::new(config),
+        })
+    }
+
+    /// Returns the current settings.
+    fn config(&self) -> Config {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replaces the settings. Connections read them as they need them, so
+    /// some settings only apply to peers that connect afterwards.
+    fn set_config(&self, config: Config) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Ends the session: every peer says goodbye, and this waits up to
+    /// [`SHUTDOWN_TIMEOUT`] for their connections to close.
+    async fn close(&self) {
+        self.shutdown.cancel();
+        self.tasks.close();
+        if timeout(SHUTDOWN_TIMEOUT, self.tasks.wait()).await.is_err() {
+            warn!("Some peer connections did not close in time");
+        }
+    }
+
+    /// Publishes an event; it is dropped if no frontend is subscribed.
+    fn emit(&self, event: ChatEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+/// Runs a chat session with a single, already connected peer in the
+/// terminal.
+///
+/// Returns when the peer disconnects, the user quits or `shutdown` is
+/// cancelled. In the last two cases the peer is told we are leaving before
+/// the connection is closed.
+///
+/// # Arguments
+///
+/// * `stream` - The connection to the peer
+/// * `config` - Configuration for the session
+/// * `shutdown` - Token that ends the session when cancelled, such as on Ctrl+C
+#[instrument(skip(stream, config, shutdown), fields(peer_addr = ?stream.peer_addr()))]
+pub async fn handle_enhanced_connection(
+    stream: TcpStream,
+    config: Config,
+    shutdown: CancellationToken,
+) -> Result<()> {
     info!("Starting enhanced connection handler");
+    let chat = P2PChat::new(config.clone())?;
+    let (session, input) = ChatSession::attach(&chat);
+    let context = Arc::new(SessionContext::new(chat.config, chat.events, shutdown)?);
+
+    let serve = async {
+        let mut input_handle = tokio::spawn(handle_enhanced_input(context.clone(), input));
+        let peer = serve_peer(stream, false, context.clone());
+        tokio::pin!(peer);
+        let result = tokio::select! {
+            result = &mut peer => result,
+            _ = &mut input_handle => {
+                context.shutdown.cancel();
+                peer.await
+            }
+        };
+        // Also ends the input handler, and with it the terminal
+        context.shutdown.cancel();
+        input_handle.abort();
+        result
+    };
+    let (result, frontend) = tokio::join!(serve, terminal::run(session, &config));
+    result.and(frontend)
+}
+
+/// Announces this node periodically and reports newly discovered peers.
+async fn run_discovery(
+    discovery: Arc<Discovery>,
+    events: broadcast::Sender<ChatEvent>,
+    shutdown: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(discovery::ANNOUNCE_INTERVAL);
+    loop {
+        select! {
+            _ = shutdown.cancelled() => return,
+            _ = interval.tick() => {
+                if let Err(e) = discovery.announce().await {
+                    debug!("Failed to send discovery announcement: {}", e);
+                }
+            }
+            result = discovery.receive() => match result {
+                Ok((peer, true)) => {
+                    let _ = events.send(ChatEvent::PeerDiscovered(peer));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("LAN discovery stopped: {}", e);
+                    return;
+                }
+            },
+        }
+    }
+}
+
+fn spawn_peer(stream: TcpStream, outgoing: bool, context: Arc<SessionContext>) {
+    context.tasks.clone().spawn(async move {
+        if let Err(e) = serve_peer(stream, outgoing, context).await {
+            warn!("Connection closed with error: {}", e);
+        }
+    });
+}
+
+/// Registers a connection in the session's [`PeerManager`] and runs it until
+/// the peer disconnects, or until we leave with `/disconnect` or by ending
+/// the session, in which case the peer gets a goodbye.
+///
+/// `outgoing` tells frontends whether we dialed the peer; only then does its
+/// address identify a contact.
+#[instrument(skip(stream, context), fields(peer_addr = ?stream.peer_addr()))]
+async fn serve_peer(stream: TcpStream, outgoing: bool, context: Arc<SessionContext>) -> Result<()> {
+    let address = canonical_address(stream.peer_addr()?);
     let (reader, writer) = stream.into_split();
     let (tx, rx) = mpsc::channel(100);
+    // File chunks get their own small queue so they never delay chat messages
+    let (bulk_tx, bulk_rx) = mpsc::channel(4);
+    let config = context.config();
 
     // Initialize encryption
     debug!("Initializing encryption system");
-    let encryption = Arc::new(tokio::sync::Mutex::new(E2EEncryption::new()?));
-    let enc_clone1 = encryption.clone();
-    let enc_clone2 = encryption.clone();
-    let tx_clone = tx.clone();
+    let encryption = Arc::new(tokio::sync::Mutex::new(E2EEncryption::with_identity(
+        context.identity.clone(),
+    )));
 
     // Initialize file transfer
     debug!(
@@ -285,89 +712,233 @@ pub async fn handle_enhanced_connection(stream: TcpStream, config: Config) -> Re
         config.max_file_size_mb
     );
     let file_transfer = Arc::new(file_transfer::FileTransfer::new(config.max_file_size_mb));
+    let transfers = Arc::new(
+        TransferManager::new(config.max_file_size_mb)
+            .with_media_extensions(config.media_extensions.clone())
+            .with_shared_content_cache(context.content_cache.clone())
+            .with_auto_accept(config.auto_accept_transfers),
+    );
+
+    let id = context.peers.next_peer_id();
+    let peer = Peer {
+        info: PeerInfo {
+            id: id.clone(),
+            nickname: None,
+            address,
+            connected_at: std::time::SystemTime::now(),
+        },
+        tx: tx.clone(),
+        bulk_tx,
+        encryption: encryption.clone(),
+        transfers,
+        shutdown: Arc::new(tokio::sync::Notify::new()),
+    };
+    let shutdown = peer.shutdown.clone();
+    context.peers.add_peer(id.clone(), peer.clone()).await?;
+    info!("Registered peer {} from {}", id, address);
+    context.emit(ChatEvent::PeerConnected {
+        id: id.clone(),
+        address,
+        outgoing,
+    });
+
+    // Announce optional features before the encryption handshake
+    let capabilities = Capabilities {
+        compression: if config.enable_compression {
+            compression::SUPPORTED.to_vec()
+        } else {
+            Vec::new()
+        },
+    };
+    tx.send(Message::new_capabilities(capabilities))
+        .await
+        .map_err(|_| ChatError::PeerDisconnected)?;
+    if let Some(nickname) = context.peers.local_nickname() {
+        tx.send(Message::new_nickname(nickname))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+    }
 
     // Start encryption handshake
     tokio::spawn(async move {
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        let enc = enc_clone1.lock().await;
+        let enc = encryption.lock().await;
         if let Ok(pub_key) = enc.get_public_key_base64() {
             let msg = Message::new_encryption(EncryptionMessage::PublicKeyExchange(pub_key));
-            let _ = tx_clone.send(msg).await;
+            let _ = tx.send(msg).await;
+            let challenge = EncryptionMessage::Challenge(enc.challenge_base64());
+            let _ = tx.send(Message::new_encryption(challenge)).await;
         }
     });
 
-    // Spawn tasks with encryption
-    let read_handle = tokio::spawn(read_enhanced_messages(
-        reader,
-        config.clone(),
-        enc_clone2.clone(),
-        tx.clone(),
-        file_transfer.clone(),
+    // Set once the peer announces its capabilities; until then it may be a
+    // simple peer that reads text as plain lines
+    let framed_text = Arc::new(AtomicBool::new(false));
+    let mut write_handle = tokio::spawn(write_enhanced_messages(
+        writer,
+        rx,
+        bulk_rx,
+        framed_text.clone(),
+        context.limits.clone(),
+        context.events.clone(),
     ));
-    let write_handle = tokio::spawn(write_enhanced_messages(writer, rx));
-    let input_handle = tokio::spawn(handle_enhanced_input(tx, config, encryption, file_transfer));
 
-    // Wait for any task to complete
-    tokio::select! {
-        _ = read_handle => {},
-        _ = write_handle => {},
-        _ = input_handle => {},
+    let messages = read_enhanced_messages(
+        reader,
+        peer.clone(),
+        outgoing,
+        &framed_text,
+        &context,
+        file_transfer,
+    );
+    let handshake = handshake_deadline(&peer, &context);
+    let mut left = false;
+    let (result, leaving) = tokio::select! {
+        result = messages => {
+            (result.map(|goodbye| left = goodbye), false)
+        }
+        result = handshake => (result, false),
+        result = report_progress(&peer, &context) => (result, false),
+        _ = &mut write_handle => (Ok(()), false),
+        _ = shutdown.notified() => (say_goodbye(&peer).await, true),
+        _ = context.shutdown.cancelled() => (say_goodbye(&peer).await, true),
+    };
+
+    if leaving {
+        // The writer stops once everything queued before the goodbye is sent
+        if timeout(SHUTDOWN_TIMEOUT, &mut write_handle).await.is_err() {
+            warn!("Timed out saying goodbye to {}", peer.info.display_name());
+        }
     }
+    write_handle.abort();
+    // The peer may already be gone after /disconnect
+    let removed = context.peers.remove_peer(&id).await;
+    context.emit(ChatEvent::PeerDisconnected {
+        id: id.clone(),
+        name: removed.as_ref().unwrap_or(&peer).info.display_name(),
+        left,
+    });
+    info!("Peer {} disconnected", id);
+    result
+}
 
-    Ok(())
+/// Cancels the unfinished transfers with a peer and queues a goodbye, the
+/// last message before the writer closes the connection.
+async fn say_goodbye(peer: &Peer) -> Result<()> {
+    let cancelled = peer.transfers.cancel_all(&peer.tx).await;
+    if !cancelled.is_empty() {
+        debug!("Cancelled transfers on leaving: {:?}", cancelled);
+    }
+    peer.tx
+        .send(Message::new_goodbye())
+        .await
+        .map_err(|_| ChatError::PeerDisconnected)
+}
+
+/// Publishes the progress of the peer's transfers whenever it changed since
+/// the last [`PROGRESS_INTERVAL`]; never returns.
+async fn report_progress(peer: &Peer, context: &SessionContext) -> Result<()> {
+    let mut reported = HashMap::new();
+    let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut name = None;
+        for status in peer.transfers.list().await {
+            let snapshot = (status.transferred, status.state);
+            if reported.insert(status.id, snapshot) == Some(snapshot) {
+                continue;
+            }
+            if name.is_none() {
+                // The peer may have picked a nickname since it connected
+                name = Some(match context.peers.get_peer(&peer.info.id).await {
+                    Some(current) => current.info.display_name(),
+                    None => peer.info.display_name(),
+                });
+            }
+            context.emit(ChatEvent::TransferProgress {
+                peer: name.clone().unwrap_or_default(),
+                status,
+            });
+        }
+    }
+}
+
+/// Fails if the peer has not completed the encryption handshake within
+/// `handshake_timeout_secs`; otherwise never returns.
+///
+/// # Errors
+///
+/// - `ChatError::ConnectFailed` with `TimedOut` if the handshake stalled
+async fn handshake_deadline(peer: &Peer, context: &SessionContext) -> Result<()> {
+    let config = context.config();
+    if !config.enable_encryption {
+        return std::future::pending().await;
+    }
+    tokio::time::sleep(Duration::from_secs(config.handshake_timeout_secs)).await;
+    if peer.encryption.lock().await.is_verified() {
+        return std::future::pending().await;
+    }
+
+    let error = ChatError::ConnectFailed(
+        peer.info.address.to_string(),
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            "encryption handshake did not complete",
+        ),
+    );
+    context.emit(ChatEvent::Error(error.to_string()));
+    Err(error)
 }
 
 async fn read_enhanced_messages(
     mut reader: OwnedReadHalf,
-    config: Config,
-    encryption: Arc<tokio::sync::Mutex<E2EEncryption>>,
-    tx: mpsc::Sender<Message>,
+    mut peer: Peer,
+    outgoing: bool,
+    framed_text: &AtomicBool,
+    context: &SessionContext,
     file_transfer: Arc<file_transfer::FileTransfer>,
-) -> Result<()> {
+) -> Result<bool> {
+    let config = context.config();
     let mut buffer = vec![0; config.buffer_size];
+    let mut decoder = FrameDecoder::new();
 
     loop {
         match reader.read(&mut buffer).await {
             Ok(0) => {
-                println!("\n{}Peer disconnected{}", Colors::RED, Colors::RESET);
-                return Ok(());
+                debug!("{} closed the connection", peer.info.display_name());
+                return Ok(false);
             }
             Ok(n) => {
-                // Try new protocol first
-                match Message::deserialize(&buffer[..n]) {
-                    Ok(message) => {
-                        handle_message(
-                            message,
-                            encryption.clone(),
-                            tx.clone(),
-                            &config,
-                            &file_transfer,
-                        )
-                        .await?
-                    }
-                    Err(_) => {
-                        // Fallback to plain text
-                        let text = String::from_utf8_lossy(&buffer[..n]).trim_end().to_string();
-                        print!("\r\x1b[2K");
-                        println!(
-                            "{}{}Peer:{} {}",
-                            Colors::BOLD,
-                            Colors::BRIGHT_CYAN,
-                            Colors::RESET,
-                            text
-                        );
-                        print!(
-                            "{}{}You:{} ",
-                            Colors::BOLD,
-                            Colors::BRIGHT_GREEN,
-                            Colors::RESET
-                        );
-                        io::Write::flush(&mut io::stdout())?;
+                decoder.extend(&buffer[..n]);
+                while let Some(frame) = decoder.next_frame()? {
+                    match frame {
+                        Frame::Message(message)
+                            if matches!(message.msg_type, MessageType::Goodbye) =>
+                        {
+                            debug!("{} said goodbye", peer.info.display_name());
+                            return Ok(true);
+                        }
+                        Frame::Message(message) => {
+                            if matches!(message.msg_type, MessageType::Capabilities(_)) {
+                                framed_text.store(true, Ordering::Relaxed);
+                            }
+                            context.limits.download.acquire(message.chunk_len()).await;
+                            handle_message(message, &mut peer, outgoing, context, &file_transfer)
+                                .await?
+                        }
+                        Frame::Text(text) => {
+                            // Plain text from a simple peer
+                            receive_chat(Message::new_text(text), &peer, context).await?
+                        }
                     }
                 }
             }
             Err(e) => {
-                eprintln!("\nError reading: {}", e);
+                context.emit(ChatEvent::Error(format!(
+                    "Lost connection to {}: {}",
+                    peer.info.display_name(),
+                    e
+                )));
                 return Err(e.into());
             }
         }
@@ -376,17 +947,22 @@ async fn read_enhanced_messages(
 
 async fn handle_message(
     message: Message,
-    encryption: Arc<tokio::sync::Mutex<E2EEncryption>>,
-    tx: mpsc::Sender<Message>,
-    config: &Config,
+    peer: &mut Peer,
+    outgoing: bool,
+    context: &SessionContext,
     file_transfer: &Arc<file_transfer::FileTransfer>,
 ) -> Result<()> {
+    let config = context.config();
+
     // Send acknowledgment for messages that require it
     match &message.msg_type {
-        MessageType::Text(_) | MessageType::EncryptedText(_) | MessageType::File(_) => {
+        MessageType::Text(_)
+        | MessageType::EncryptedText(_)
+        | MessageType::PrivateText(_)
+        | MessageType::File(_) => {
             debug!("Sending acknowledgment for message ID: {}", message.id);
             let ack = Message::new_acknowledgment(message.id);
-            if let Err(e) = tx.send(ack).await {
+            if let Err(e) = peer.tx.send(ack).await {
                 warn!("Failed to send acknowledgment: {:?}", e);
             }
         }
@@ -394,166 +970,153 @@ async fn handle_message(
     }
 
     match message.msg_type {
-        MessageType::Text(text) => {
-            print!("\r\x1b[2K");
-            println!(
-                "{}{}Peer:{} {} {}(unencrypted){}",
-                Colors::BOLD,
-                Colors::BRIGHT_CYAN,
-                Colors::RESET,
-                text,
-                Colors::DIM,
-                Colors::RESET
-            );
-            print!(
-                "{}{}You:{} ",
-                Colors::BOLD,
-                Colors::BRIGHT_GREEN,
-                Colors::RESET
-            );
-            io::Write::flush(&mut io::stdout())?;
+        MessageType::Text(_) | MessageType::EncryptedText(_) | MessageType::Gossip(_) => {
+            receive_chat(message, peer, context).await?;
         }
-        MessageType::EncryptedText(encrypted) => {
-            let enc = encryption.lock().await;
-            match enc.decrypt_message(&encrypted) {
-                Ok(text) => {
-                    print!("\r\x1b[2K");
-                    println!(
-                        "{}{}Peer:{} {} {}🔒{}",
-                        Colors::BOLD,
-                        Colors::BRIGHT_CYAN,
-                        Colors::RESET,
-                        text,
-                        Colors::GREEN,
-                        Colors::RESET
-                    );
-                    print!(
-                        "{}{}You:{} ",
-                        Colors::BOLD,
-                        Colors::BRIGHT_GREEN,
-                        Colors::RESET
-                    );
-                    io::Write::flush(&mut io::stdout())?;
-                }
-                Err(_) => {
-                    println!(
-                        "\n{}Failed to decrypt message{}",
-                        Colors::RED,
-                        Colors::RESET
-                    );
+        MessageType::Nickname(nickname) => {
+            let previous = peer.info.display_name();
+            if let Some(assigned) = context.peers.set_nickname(&peer.info.id, &nickname).await {
+                peer.info.nickname = Some(assigned);
+                if peer.info.display_name() != previous {
+                    context.emit(ChatEvent::PeerRenamed {
+                        id: peer.info.id.clone(),
+                        previous,
+                        name: peer.info.display_name(),
+                    });
                 }
             }
         }
+        MessageType::PrivateText(encrypted) => {
+            let enc = peer.encryption.lock().await;
+            match enc.decrypt_message(&encrypted) {
+                Ok(text) => context.emit(ChatEvent::MessageReceived {
+                    from: peer.info.display_name(),
+                    text,
+                    encrypted: true,
+                    private: true,
+                }),
+                Err(_) => context.emit(ChatEvent::Error(format!(
+                    "Failed to decrypt private message from {}",
+                    peer.info.display_name()
+                ))),
+            }
+        }
         MessageType::File(file_info) => {
-            println!(
-                "\n{}📁 Receiving file: {} ({} bytes){}",
-                Colors::YELLOW,
-                file_info.name,
-                file_info.size,
-                Colors::RESET
-            );
+            context.emit(ChatEvent::Notice(format!(
+                "📁 Receiving file: {} ({} bytes)",
+                file_info.name, file_info.size
+            )));
 
             // Save the file
             let download_dir = config.download_path();
             match file_transfer.save_file(&file_info, &download_dir).await {
                 Ok(file_path) => {
-                    println!(
-                        "{}✓ File saved to: {}{}",
-                        Colors::GREEN,
-                        file_path.display(),
-                        Colors::RESET
-                    );
-
-                    // Check if auto-open is enabled and if it's a media file
-                    if config.auto_open_media
-                        && file_transfer::FileTransfer::is_media_file(
-                            &file_info.name,
-                            &config.media_extensions,
-                        )
-                    {
-                        println!("{}🎬 Opening media file...{}", Colors::CYAN, Colors::RESET);
-                        if let Err(e) = file_transfer::FileTransfer::open_file(&file_path) {
-                            eprintln!("{}Failed to open file: {}{}", Colors::RED, e, Colors::RESET);
-                        }
-                    }
+                    context.emit(ChatEvent::Notice(format!(
+                        "✓ File saved to: {}",
+                        file_path.display()
+                    )));
+                    open_received_media(&file_path, &file_info.name, context);
                 }
                 Err(e) => {
-                    eprintln!("{}Failed to save file: {}{}", Colors::RED, e, Colors::RESET);
+                    context.emit(ChatEvent::Error(format!("Failed to save file: {}", e)));
                 }
             }
         }
         MessageType::Status(status) => match status {
             StatusUpdate::TransferProgress(name, current, total) => {
                 let percent = (current as f64 / total as f64) * 100.0;
-                print!(
-                    "\r{}Progress {}: {:.1}%{}",
-                    Colors::YELLOW,
-                    name,
-                    percent,
-                    Colors::RESET
-                );
-                io::Write::flush(&mut io::stdout())?;
+                debug!("Progress {}: {:.1}%", name, percent);
             }
             StatusUpdate::EncryptionEnabled => {
-                println!(
-                    "\n{}🔒 End-to-end encryption enabled!{}",
-                    Colors::GREEN,
-                    Colors::RESET
-                );
+                debug!("{} reports encryption enabled", peer.info.display_name());
             }
             _ => {}
         },
         MessageType::Encryption(enc_msg) => {
             match enc_msg {
                 EncryptionMessage::PublicKeyExchange(key) => {
-                    println!(
-                        "\n{}Received encryption key from peer...{}",
-                        Colors::YELLOW,
-                        Colors::RESET
-                    );
-                    // Set peer's public key
-                    let mut enc = encryption.lock().await;
+                    debug!("Received encryption key from {}", peer.info.display_name());
+                    // Set peer's public key; it is only trusted once the peer
+                    // proves it holds the private key
+                    let mut enc = peer.encryption.lock().await;
                     if let Err(e) = enc.set_peer_public_key(&key) {
-                        eprintln!("Failed to set peer public key: {}", e);
+                        context.emit(ChatEvent::Error(format!(
+                            "Invalid encryption key from {}: {}",
+                            peer.info.display_name(),
+                            e
+                        )));
+                    }
+                }
+                EncryptionMessage::Challenge(challenge) => {
+                    let mut enc = peer.encryption.lock().await;
+                    if let Err(e) = enc.set_peer_challenge(&challenge) {
+                        context.emit(ChatEvent::Error(format!(
+                            "Invalid encryption challenge from {}: {}",
+                            peer.info.display_name(),
+                            e
+                        )));
                         return Ok(());
                     }
 
-                    // If we haven't sent our key yet, send it
-                    if !enc.is_ready() {
-                        if let Ok(encrypted_key) = enc.generate_shared_key() {
-                            drop(enc); // Release lock before sending
-                            let msg = Message::new_encryption(
-                                EncryptionMessage::SharedKeyExchange(encrypted_key),
-                            );
-                            tx.send(msg)
+                    // Both sides announce their public key at the same time, so
+                    // only the side with the greater key picks the session key
+                    if !enc.is_ready() && enc.picks_session_key()? {
+                        let encrypted_key = enc.generate_shared_key()?;
+                        let proof = enc.identity_proof()?;
+                        drop(enc); // Release lock before sending
+                        for msg in [
+                            EncryptionMessage::SharedKeyExchange(encrypted_key),
+                            EncryptionMessage::IdentityProof(proof),
+                        ] {
+                            peer.tx
+                                .send(Message::new_encryption(msg))
                                 .await
                                 .map_err(|_| ChatError::PeerDisconnected)?;
-                            println!(
-                                "{}Sending encrypted session key...{}",
-                                Colors::YELLOW,
-                                Colors::RESET
-                            );
                         }
+                        debug!("Sent encrypted session key");
                     }
                 }
                 EncryptionMessage::SharedKeyExchange(encrypted_key) => {
-                    println!(
-                        "\n{}Received encrypted session key...{}",
-                        Colors::YELLOW,
-                        Colors::RESET
-                    );
-                    let mut enc = encryption.lock().await;
-                    if let Err(e) = enc.set_shared_key(&encrypted_key) {
-                        eprintln!("Failed to set shared key: {}", e);
+                    debug!("Received encrypted session key");
+                    let mut enc = peer.encryption.lock().await;
+                    let proof = enc
+                        .set_shared_key(&encrypted_key)
+                        .and_then(|_| enc.identity_proof());
+                    drop(enc);
+                    match proof {
+                        Ok(proof) => peer
+                            .tx
+                            .send(Message::new_encryption(EncryptionMessage::IdentityProof(
+                                proof,
+                            )))
+                            .await
+                            .map_err(|_| ChatError::PeerDisconnected)?,
+                        Err(e) => context.emit(ChatEvent::Error(format!(
+                            "Invalid session key from {}: {}",
+                            peer.info.display_name(),
+                            e
+                        ))),
+                    }
+                }
+                EncryptionMessage::IdentityProof(signature) => {
+                    let mut enc = peer.encryption.lock().await;
+                    if let Err(e) = enc.verify_identity_proof(&signature) {
+                        context.emit(ChatEvent::Error(format!(
+                            "{} could not prove its identity: {}",
+                            peer.info.display_name(),
+                            e
+                        )));
                         return Ok(());
                     }
+                    let fingerprint = enc.peer_fingerprint();
                     drop(enc);
-
-                    // Send confirmation
-                    let msg = Message::new_encryption(EncryptionMessage::HandshakeComplete);
-                    tx.send(msg)
-                        .await
-                        .map_err(|_| ChatError::PeerDisconnected)?;
+                    if let Some(fingerprint) = fingerprint {
+                        check_identity(&peer.info, outgoing, fingerprint, context);
+                    }
+                    context.emit(ChatEvent::EncryptionEnabled {
+                        id: peer.info.id.clone(),
+                        name: peer.info.display_name(),
+                    });
 
                     // Send status update
                     let status_msg = Message {
@@ -561,25 +1124,15 @@ async fn handle_message(
                         timestamp: std::time::SystemTime::now(),
                         msg_type: MessageType::Status(StatusUpdate::EncryptionEnabled),
                     };
-                    tx.send(status_msg)
+                    peer.tx
+                        .send(status_msg)
                         .await
                         .map_err(|_| ChatError::PeerDisconnected)?;
                 }
                 EncryptionMessage::HandshakeComplete => {
-                    println!(
-                        "\n{}🔒 Encryption handshake complete!{}",
-                        Colors::GREEN,
-                        Colors::RESET
-                    );
-                    // Send status update
-                    let status_msg = Message {
-                        id: rand::random(),
-                        timestamp: std::time::SystemTime::now(),
-                        msg_type: MessageType::Status(StatusUpdate::EncryptionEnabled),
-                    };
-                    tx.send(status_msg)
-                        .await
-                        .map_err(|_| ChatError::PeerDisconnected)?;
+                    // Older peers confirm the handshake without proving their
+                    // identity, which is not enough to trust the session
+                    debug!("{} sent HandshakeComplete", peer.info.display_name());
                 }
             }
         }
@@ -588,139 +1141,457 @@ async fn handle_message(
             // Note: In full implementation, this would notify the reliability manager
             // For now, we just log it
         }
+        MessageType::Capabilities(capabilities) => {
+            let local: &[Compression] = if config.enable_compression {
+                compression::SUPPORTED
+            } else {
+                &[]
+            };
+            let algorithm = compression::negotiate(local, &capabilities.compression);
+            debug!("Negotiated compression: {:?}", algorithm);
+            peer.transfers.set_compression(algorithm);
+        }
+        MessageType::Transfer(transfer_msg) => {
+            let download_dir = config.download_path();
+            let event = peer
+                .transfers
+                .handle_message(transfer_msg, &download_dir, &peer.tx)
+                .await?;
+            if let TransferEvent::Completed { path, name, .. } = &event {
+                if path.is_file() {
+                    open_received_media(path, name, context);
+                }
+            }
+            if !matches!(event, TransferEvent::Progress | TransferEvent::Ignored) {
+                context.emit(ChatEvent::Transfer {
+                    from: peer.info.display_name(),
+                    event,
+                });
+            }
+        }
         _ => {}
     }
     Ok(())
 }
 
+/// Matches a peer's identity fingerprint against the saved contacts and
+/// reports the result, which warns if a contact's key has changed.
+///
+/// Only a peer we dialed (`outgoing`) is matched by its address; a peer that
+/// connected to us comes from an arbitrary port.
+fn check_identity(peer: &PeerInfo, outgoing: bool, fingerprint: String, context: &SessionContext) {
+    let mut contacts = Contacts::load_for(&context.config());
+    let dialed = outgoing.then(|| peer.address.to_string());
+    let contact = contacts
+        .record_seen(dialed.as_deref(), &fingerprint)
+        .unwrap_or_else(|e| {
+            warn!("Failed to update contacts: {}", e);
+            None
+        });
+    context.emit(ChatEvent::PeerIdentity {
+        id: peer.id.clone(),
+        name: peer.display_name(),
+        fingerprint,
+        contact,
+    });
+}
+
+/// Opens a received file if it is media and auto-open is enabled.
+fn open_received_media(path: &Path, name: &str, context: &SessionContext) {
+    let config = context.config();
+    if config.auto_open_media
+        && file_transfer::FileTransfer::is_media_file(name, &config.media_extensions)
+    {
+        context.emit(ChatEvent::Notice("🎬 Opening media file...".to_string()));
+        if let Err(e) = file_transfer::FileTransfer::open_file(path) {
+            context.emit(ChatEvent::Error(format!("Failed to open file: {}", e)));
+        }
+    }
+}
+
+/// Reports a chat message and relays it to our other peers.
+///
+/// Messages already seen, because they reached us over another path, are
+/// dropped. Direct messages are relayed as [`GossipMessage`]s and gossip is
+/// forwarded with a decremented TTL, keeping the original message ID.
+async fn receive_chat(message: Message, peer: &Peer, context: &SessionContext) -> Result<()> {
+    if !context.peers.mark_seen(message.id) {
+        debug!("Dropping duplicate message ID: {}", message.id);
+        return Ok(());
+    }
+
+    let (origin, ttl, payload) = match message.msg_type {
+        MessageType::Gossip(gossip) => (Some(gossip.origin), gossip.ttl, *gossip.payload),
+        other => (None, GOSSIP_TTL, other),
+    };
+    let (text, encrypted) = match payload {
+        MessageType::Text(text) => (text, false),
+        MessageType::EncryptedText(encrypted) => {
+            let enc = peer.encryption.lock().await;
+            match enc.decrypt_message(&encrypted) {
+                Ok(text) => (text, true),
+                Err(_) => {
+                    context.emit(ChatEvent::Error(format!(
+                        "Failed to decrypt message from {}",
+                        peer.info.display_name()
+                    )));
+                    return Ok(());
+                }
+            }
+        }
+        other => {
+            warn!("Ignoring unexpected gossip payload: {:?}", other);
+            return Ok(());
+        }
+    };
+
+    // A relay decrypts what it forwards and names the origin itself, so a
+    // relayed message is neither end-to-end encrypted nor authenticated
+    let (sender, encrypted) = match &origin {
+        Some(origin) => (
+            format!(
+                "via {} (from {}, unverified)",
+                peer.info.display_name(),
+                origin
+            ),
+            false,
+        ),
+        None => (peer.info.display_name(), encrypted),
+    };
+    context.emit(ChatEvent::MessageReceived {
+        from: sender.clone(),
+        text: text.clone(),
+        encrypted,
+        private: false,
+    });
+
+    let ttl = ttl.saturating_sub(1);
+    if ttl > 0 {
+        let relay = Message {
+            msg_type: MessageType::Gossip(GossipMessage {
+                origin: origin.unwrap_or_else(|| peer.info.display_name()),
+                ttl,
+                payload: Box::new(MessageType::Text(text)),
+            }),
+            ..message
+        };
+        context.peers.broadcast(relay, Some(&peer.info.id)).await?;
+    }
+    Ok(())
+}
+
 async fn write_enhanced_messages(
     mut writer: OwnedWriteHalf,
     mut rx: mpsc::Receiver<Message>,
+    mut bulk_rx: mpsc::Receiver<Message>,
+    framed: Arc<AtomicBool>,
+    limits: Arc<BandwidthLimits>,
+    events: broadcast::Sender<ChatEvent>,
 ) -> Result<()> {
-    while let Some(message) = rx.recv().await {
-        match &message.msg_type {
-            MessageType::Text(text) => {
-                // Send as plain text for compatibility
-                let plain = format!("{}\n", text);
-                writer.write_all(plain.as_bytes()).await?;
-                writer.flush().await?;
+    'write: loop {
+        // Chat and control messages always go ahead of file chunks
+        tokio::select! {
+            biased;
+            Some(message) = rx.recv() => {
+                write_enhanced_message(&mut writer, &message, &framed, &events).await?;
+                if matches!(message.msg_type, MessageType::Goodbye) {
+                    break;
+                }
             }
-            _ => {
-                // Send using new protocol
-                if let Ok(data) = message.serialize() {
-                    writer.write_all(&data).await?;
-                    writer.flush().await?;
+            Some(chunk) = bulk_rx.recv() => {
+                let throttle = limits.upload.acquire(chunk.chunk_len());
+                tokio::pin!(throttle);
+                loop {
+                    tokio::select! {
+                        biased;
+                        Some(message) = rx.recv() => {
+                            write_enhanced_message(&mut writer, &message, &framed, &events).await?;
+                            // File chunks still queued are dropped
+                            if matches!(message.msg_type, MessageType::Goodbye) {
+                                break 'write;
+                            }
+                        }
+                        _ = &mut throttle => break,
+                    }
                 }
+                write_enhanced_message(&mut writer, &chunk, &framed, &events).await?;
             }
+            else => break,
         }
     }
+    writer.shutdown().await?;
     Ok(())
 }
 
+/// Writes one message to the peer.
+///
+/// Text is sent as a plain line until `framed` is set, once the peer has
+/// announced its capabilities. Simple peers such as [`P2PPeer`] only
+/// read plain lines.
+///
+/// A message that cannot be framed, such as one over `MAX_FRAME_SIZE`, is
+/// reported as a [`ChatEvent::Error`] and skipped; the connection stays up.
+async fn write_enhanced_message(
+    writer: &mut OwnedWriteHalf,
+    message: &Message,
+    framed: &AtomicBool,
+    events: &broadcast::Sender<ChatEvent>,
+) -> Result<()> {
+    if let MessageType::Text(text) = &message.msg_type {
+        if !framed.load(Ordering::Relaxed) {
+            writer.write_all(format!("{}\n", text).as_bytes()).await?;
+            writer.flush().await?;
+            return Ok(());
+        }
+    }
+    // Framed text keeps its message ID for de-duplication when it is relayed
+    match message.to_frame() {
+        Ok(data) => {
+            writer.write_all(&data).await?;
+            writer.flush().await?;
+        }
+        Err(e) => {
+            error!("Failed to serialize message {}: {}", message.id, e);
+            let _ = events.send(ChatEvent::Error(format!("Message not sent: {}", e)));
+        }
+    }
+    Ok(())
+}
+
+/// Handles the input of a frontend until it is closed or the user quits.
 async fn handle_enhanced_input(
-    tx: mpsc::Sender<Message>,
-    mut config: Config,
-    encryption: Arc<tokio::sync::Mutex<E2EEncryption>>,
-    file_transfer: Arc<file_transfer::FileTransfer>,
+    context: Arc<SessionContext>,
+    mut input: mpsc::UnboundedReceiver<Input>,
 ) -> Result<()> {
-    let reader = BufReader::new(tokio::io::stdin());
-    let mut lines = reader.lines();
+    let mut config = context.config();
     let mut command_handler = CommandHandler::new(config.clone());
-    let peer_manager = PeerManager::new().0;
+    let limits = &context.limits;
 
-    println!(
-        "{}Type messages and press Enter to send (Ctrl+C to exit){}",
-        Colors::DIM,
-        Colors::RESET
-    );
-    print!(
-        "{}{}You:{} ",
-        Colors::BOLD,
-        Colors::BRIGHT_GREEN,
-        Colors::RESET
-    );
-    io::Write::flush(&mut io::stdout())?;
+    loop {
+        let input = tokio::select! {
+            input = input.recv() => input,
+            _ = context.shutdown.cancelled() => None,
+        };
+        let Some(input) = input else {
+            break;
+        };
+        let command = match input {
+            Input::Line(line) => match CommandHandler::parse_command(&line) {
+                Some(command) => command,
+                None => {
+                    broadcast_text(line, &context).await?;
+                    continue;
+                }
+            },
+            Input::Text(text) => {
+                broadcast_text(text, &context).await?;
+                continue;
+            }
+            Input::Command(command) => command,
+            Input::Config(new) => {
+                apply_config(*new, &context).await;
+                config = context.config();
+                command_handler = CommandHandler::new(config.clone());
+                continue;
+            }
+        };
 
-    while let Ok(Some(line)) = lines.next_line().await {
-        if line.is_empty() {
-            print!(
-                "{}{}You:{} ",
-                Colors::BOLD,
-                Colors::BRIGHT_GREEN,
-                Colors::RESET
-            );
-            io::Write::flush(&mut io::stdout())?;
-            continue;
-        }
-
-        // Check for commands
-        if let Some(command) = CommandHandler::parse_command(&line) {
-            match &command {
-                Command::Quit => break,
-                Command::SendFile(path) => {
-                    match file_transfer.prepare_file(&PathBuf::from(&path)).await {
-                        Ok(file_info) => {
-                            let msg = Message {
-                                id: rand::random(),
-                                timestamp: std::time::SystemTime::now(),
-                                msg_type: MessageType::File(file_info),
-                            };
-                            tx.send(msg)
-                                .await
-                                .map_err(|_| ChatError::PeerDisconnected)?;
-                            println!("{}✓ File sent{}", Colors::GREEN, Colors::RESET);
-                        }
-                        Err(e) => println!("{}✗ Error: {}{}", Colors::RED, e, Colors::RESET),
+        match &command {
+            Command::Quit => break,
+            Command::SendFile(path) => {
+                let peers = context.peers.peers().await;
+                if peers.is_empty() {
+                    context.emit(ChatEvent::Error("No peers connected".to_string()));
+                }
+                for peer in peers {
+                    send_path(peer, PathBuf::from(&path), context.clone()).await;
+                }
+            }
+            Command::Connect(target) => {
+                let contacts = Contacts::load_for(&config);
+                let discovered = context.discovery.as_ref().and_then(|d| d.find(target));
+                let address = match discovered {
+                    Some(peer) if contacts.get(target).is_none() => peer.address.to_string(),
+                    _ => contacts.resolve(target),
+                };
+                context.emit(ChatEvent::Connecting(address.clone()));
+                let context = context.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = connect_to(&address, &context).await {
+                        context.emit(ChatEvent::Error(e.to_string()));
                     }
+                });
+            }
+            Command::Discover => match &context.discovery {
+                Some(discovery) => context.emit(ChatEvent::Notice(discovery_summary(discovery))),
+                None => context.emit(ChatEvent::Warning(
+                    "LAN discovery is disabled (set enable_discovery = true in config.toml)"
+                        .to_string(),
+                )),
+            },
+            Command::PrivateMessage(name, text) => {
+                // The parser splits at the first space, but nicknames may
+                // contain spaces
+                let args = format!("{} {}", name, text);
+                let (name, text) = match context.peers.split_recipient(&args).await {
+                    Some((peer, text)) => (peer.info.id, text),
+                    None => (name.clone(), text.clone()),
+                };
+                match context.peers.send_private(&name, &text).await {
+                    Ok(peer) => context.emit(ChatEvent::MessageSent {
+                        to: Some(peer.info.display_name()),
+                        text,
+                        // Private messages are only sent encrypted
+                        encrypted: true,
+                    }),
+                    Err(e) => context.emit(ChatEvent::Error(e.to_string())),
                 }
-                Command::ToggleAutoOpen => {
-                    config.auto_open_media = !config.auto_open_media;
-                    config.save()?;
-                    command_handler = CommandHandler::new(config.clone());
-                    println!(
-                        "{}✓ Auto-open media: {}{}",
-                        Colors::GREEN,
-                        if config.auto_open_media {
-                            "enabled"
-                        } else {
-                            "disabled"
-                        },
-                        Colors::RESET
-                    );
+            }
+            _ => {
+                match command_handler
+                    .handle_command(command, &context.peers)
+                    .await
+                {
+                    Ok(response) => context.emit(ChatEvent::Notice(response)),
+                    Err(e) => context.emit(ChatEvent::Error(e.to_string())),
+                }
+                // Keep settings changed by the handler, such as the nickname
+                // and bandwidth limits
+                config = command_handler.config().clone();
+                context.set_config(config.clone());
+                limits.upload.set_limit(config.max_upload_kbps);
+                limits.download.set_limit(config.max_download_kbps);
+                if let Some(discovery) = &context.discovery {
+                    discovery.set_nickname(config.nickname.clone());
                 }
-                _ => match command_handler.handle_command(command, &peer_manager).await {
-                    Ok(response) => println!("{}", response),
-                    Err(e) => println!("{}✗ Error: {}{}", Colors::RED, e, Colors::RESET),
-                },
             }
-        } else {
-            // Try to encrypt message if encryption is ready
-            let enc = encryption.lock().await;
-            let msg = if enc.is_ready() {
-                match enc.encrypt_message(&line) {
-                    Ok(encrypted) => Message::new_encrypted_text(encrypted),
-                    Err(_) => Message::new_text(line),
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies settings changed by a frontend to the running session.
+///
+/// Bandwidth limits and the nickname take effect at once, and transfers
+/// use the new download settings. Connection settings apply to peers that
+/// connect afterwards; the listening address and LAN discovery keep their
+/// settings until the session is restarted.
+async fn apply_config(config: Config, context: &SessionContext) {
+    let previous = context.config();
+    context.limits.upload.set_limit(config.max_upload_kbps);
+    context.limits.download.set_limit(config.max_download_kbps);
+    if config.nickname != previous.nickname {
+        match &config.nickname {
+            Some(nickname) => {
+                if let Err(e) = context.peers.announce_nickname(nickname).await {
+                    context.emit(ChatEvent::Error(e.to_string()));
                 }
+            }
+            None => context.peers.set_local_nickname(None),
+        }
+        if let Some(discovery) = &context.discovery {
+            discovery.set_nickname(config.nickname.clone());
+        }
+    }
+    context.set_config(config);
+    context.emit(ChatEvent::Notice("✓ Settings updated".to_string()));
+}
+
+/// Sends chat text to every peer.
+async fn broadcast_text(text: String, context: &SessionContext) -> Result<()> {
+    // Text is encrypted for each peer whose handshake has completed
+    let mut encrypted = true;
+    for peer in context.peers.peers().await {
+        encrypted &= peer.encryption.lock().await.is_verified();
+    }
+    if context.peers.broadcast_text(&text).await? == 0 {
+        context.emit(ChatEvent::Warning(
+            "No peers connected, message not sent".to_string(),
+        ));
+    } else {
+        context.emit(ChatEvent::MessageSent {
+            to: None,
+            text,
+            encrypted,
+        });
+    }
+    Ok(())
+}
+
+/// Offers a file or directory to `peer` and streams it in the background.
+async fn send_path(peer: Peer, path: PathBuf, context: Arc<SessionContext>) {
+    let name = peer.info.display_name();
+    match peer.transfers.start_outgoing(&path, &peer.tx).await {
+        Ok((id, path)) => {
+            let (kind, label) = if path.is_dir() {
+                ("directory", "Directory")
             } else {
-                Message::new_text(line)
+                ("file", "File")
             };
-            drop(enc);
-
-            tx.send(msg)
-                .await
-                .map_err(|_| ChatError::PeerDisconnected)?;
+            context.emit(ChatEvent::Notice(format!(
+                "📤 Sending {} to {}: {} [#{}]",
+                kind,
+                name,
+                path.display(),
+                id
+            )));
+            tokio::spawn(async move {
+                match peer
+                    .transfers
+                    .stream_outgoing(id, peer.bulk_tx.clone())
+                    .await
+                {
+                    Ok(transfer::TransferState::Completed) => {
+                        let status = peer.transfers.status(id).await;
+                        let detail = match status {
+                            Some(status) if status.deduplicated => {
+                                format!(" ({} already had it)", name)
+                            }
+                            Some(status) => status
+                                .compression_ratio()
+                                .map(|r| format!(" ({:.1}x compressed)", r))
+                                .unwrap_or_default(),
+                            None => String::new(),
+                        };
+                        context.emit(ChatEvent::Notice(format!(
+                            "✓ {} sent to {} [#{}]{}",
+                            label, name, id, detail
+                        )));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        context.emit(ChatEvent::Error(format!("Transfer #{} failed: {}", id, e)))
+                    }
+                }
+            });
         }
-
-        print!(
-            "{}{}You:{} ",
-            Colors::BOLD,
-            Colors::BRIGHT_GREEN,
-            Colors::RESET
-        );
-        io::Write::flush(&mut io::stdout())?;
+        Err(e) => context.emit(ChatEvent::Error(e.to_string())),
     }
+}
 
-    Ok(())
+/// Formats the peers found on the local network for the `/discover` command.
+fn discovery_summary(discovery: &Discovery) -> String {
+    let peers = discovery.peers();
+    if peers.is_empty() {
+        return "No peers found on the local network yet.".to_string();
+    }
+    let lines: Vec<String> = peers
+        .iter()
+        .map(|peer| {
+            format!(
+                "  - {} at {} [{}]",
+                peer.display_name(),
+                peer.address,
+                peer.fingerprint
+            )
+        })
+        .collect();
+    format!(
+        "Discovered peers ({}):\n{}\nUse /connect <name> to join one.",
+        peers.len(),
+        lines.join("\n")
+    )
 }
 
 // Keep original simple implementation for backward compatibility
@@ -739,10 +1610,10 @@ impl P2PPeer {
 
     pub async fn start(&self) -> io::Result<()> {
         let config = Config::default();
-        let mut chat = P2PChat::new(config).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let mut chat = P2PChat::new(config).map_err(io::Error::other)?;
         chat.start(self.listen_port, self.peer_address.clone())
             .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .map_err(io::Error::other)
     }
 }
 
@@ -755,16 +1626,8 @@ pub async fn handle_connection(stream: TcpStream) -> io::Result<()> {
     let write_handle = tokio::spawn(async move { write_messages_simple(writer).await });
 
     let _ = try_join(
-        async {
-            read_handle
-                .await
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
-        },
-        async {
-            write_handle
-                .await
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
-        },
+        async { read_handle.await.map_err(io::Error::other) },
+        async { write_handle.await.map_err(io::Error::other) },
     )
     .await?;
 
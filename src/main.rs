@@ -1,20 +1,34 @@
 // #![cfg_attr(windows, windows_subsystem = "windows")] // Commented out to fix argument parsing
 
 use clap::{Parser, Subcommand};
-use rust_p2p_chat::{config::Config, P2PChat};
+use rust_p2p_chat::{
+    config::{Config, PortRange},
+    contacts::Contacts,
+    P2PChat,
+};
+use std::fs::OpenOptions;
 use std::io;
+use std::sync::Mutex;
 use tracing::{debug, error, info, warn};
-use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use tracing_subscriber::{fmt, fmt::writer::BoxMakeWriter, prelude::*, EnvFilter};
 
 #[derive(Parser)]
 #[command(name = "rust-p2p-chat")]
 #[command(about = "A true peer-to-peer chat application", long_about = None)]
 struct Cli {
-    /// Port to listen on
+    /// Port to listen on (0 picks any free port)
     #[arg(short, long, default_value_t = 8080)]
     port: u16,
 
-    /// Peer address to connect to (e.g., 192.168.1.100:8080)
+    /// Try ports in sequence until a free one is found (e.g., 8080-8090)
+    #[arg(long, value_name = "START-END", conflicts_with = "port")]
+    port_range: Option<PortRange>,
+
+    /// Address to listen on (e.g., 0.0.0.0, 192.168.1.5, or :: for IPv4 and IPv6)
+    #[arg(short, long)]
+    bind: Option<String>,
+
+    /// Peer address or contact name to connect to (e.g., 192.168.1.100:8080, [::1]:8080 or alice)
     #[arg(short = 'c', long)]
     connect: Option<String>,
 
@@ -34,6 +48,10 @@ struct Cli {
     #[arg(short, long)]
     gui: bool,
 
+    /// Launch the full-screen terminal interface (logs go to rust-p2p-chat.log in the data dir)
+    #[arg(long, conflicts_with = "gui")]
+    tui: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -60,9 +78,31 @@ async fn main() -> io::Result<()> {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(format!("rust_p2p_chat={}", log_level)));
 
+    // The full-screen interface owns the terminal, so it logs to a file
+    let writer = if cli.tui {
+        let file = match Config::log_path() {
+            Some(path) => path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| OpenOptions::new().create(true).append(true).open(path)),
+            // A name of our own, so that no other user's file is appended to
+            None => OpenOptions::new().write(true).create_new(true).open(
+                std::env::temp_dir().join(format!("rust-p2p-chat-{}.log", std::process::id())),
+            ),
+        };
+        match file {
+            Ok(file) => BoxMakeWriter::new(Mutex::new(file)),
+            Err(_) => BoxMakeWriter::new(io::sink),
+        }
+    } else {
+        BoxMakeWriter::new(io::stdout)
+    };
+
     tracing_subscriber::registry()
         .with(
             fmt::layer()
+                .with_writer(writer)
+                .with_ansi(!cli.tui)
                 .with_target(false)
                 .with_timer(fmt::time::UtcTime::rfc_3339())
                 .with_level(true),
@@ -77,7 +117,7 @@ async fn main() -> io::Result<()> {
         info!("Launching GUI interface with drag & drop support");
         return rust_p2p_chat::gui::run_gui().map_err(|e| {
             error!("GUI error: {}", e);
-            io::Error::new(io::ErrorKind::Other, format!("GUI error: {}", e))
+            io::Error::other(format!("GUI error: {}", e))
         });
     }
 
@@ -124,6 +164,10 @@ async fn main() -> io::Result<()> {
         warn!("Encryption disabled via CLI - messages will be unencrypted!");
         config.enable_encryption = false;
     }
+    if let Some(bind) = cli.bind {
+        info!("Binding to address: {}", bind);
+        config.bind_address = bind;
+    }
     if let Some(nick) = &cli.nickname {
         info!("Setting nickname to: {}", nick);
         config.nickname = cli.nickname;
@@ -144,6 +188,15 @@ async fn main() -> io::Result<()> {
         println!();
     }
 
+    // Allow connecting to a saved contact by name
+    let connect = cli.connect.map(|target| {
+        let address = Contacts::load_for(&config).resolve(&target);
+        if address != target {
+            println!("Connecting to contact {} at {}", target, address);
+        }
+        address
+    });
+
     // Create and start the chat
     info!(
         "Initializing P2P chat with port {} and encryption {}",
@@ -157,21 +210,39 @@ async fn main() -> io::Result<()> {
 
     let mut chat = P2PChat::new(config).map_err(|e| {
         error!("Failed to create chat: {}", e);
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("Failed to create chat: {}", e),
-        )
+        io::Error::other(format!("Failed to create chat: {}", e))
     })?;
+    if let Some(range) = cli.port_range {
+        info!("Trying ports {}", range);
+        chat = chat.with_port_range(range);
+    }
 
-    if let Some(ref peer_addr) = cli.connect {
+    if let Some(ref peer_addr) = connect {
         info!("Attempting to connect to peer at: {}", peer_addr);
     } else {
         info!("Starting in listen mode on port: {}", cli.port);
     }
 
-    chat.start(cli.port, cli.connect).await.map_err(|e| {
+    // Ctrl+C leaves like /quit; pressing it again exits at once
+    let shutdown = chat.shutdown_token();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("Interrupted, leaving the chat");
+            shutdown.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+
+    let result = if cli.tui {
+        chat.start_tui(cli.port, connect).await
+    } else {
+        chat.start(cli.port, connect).await
+    };
+    result.map_err(|e| {
         error!("Chat session ended with error: {}", e);
-        io::Error::new(io::ErrorKind::Other, format!("Chat error: {}", e))
+        io::Error::other(format!("Chat error: {}", e))
     })?;
 
     info!("Chat session ended normally");
@@ -8,8 +8,13 @@
 //!
 //! - Concurrent peer management with thread-safe operations
 //! - Message broadcasting to multiple peers
+//! - Private messages to a single peer
+//! - Nickname exchange, with unique display names when nicknames collide
+//! - Relaying of chat messages across the mesh, with de-duplication by message ID
+//! - Per-peer encryption and file transfer state
 //! - Peer information tracking (nickname, address, connection time)
 //! - Automatic peer cleanup on disconnection
+//! - Closing individual connections on request
 //! - Message passing between peers using channels
 //!
 //! # Architecture
@@ -48,13 +53,15 @@
 //! }
 //! ```
 
-use crate::error::Result;
-use crate::protocol::Message;
-use std::collections::HashMap;
+use crate::encryption::E2EEncryption;
+use crate::error::{ChatError, Result};
+use crate::protocol::{GossipMessage, Message, MessageType};
+use crate::transfer::TransferManager;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::{mpsc, Mutex, Notify};
 
 /// Information about a connected peer in the chat network.
 ///
@@ -89,6 +96,17 @@ pub struct PeerInfo {
     pub connected_at: std::time::SystemTime,
 }
 
+impl PeerInfo {
+    /// Returns the name to show for this peer: its nickname if known,
+    /// otherwise "Peer" followed by its ID.
+    pub fn display_name(&self) -> String {
+        match &self.nickname {
+            Some(nickname) => nickname.clone(),
+            None => format!("Peer {}", self.id),
+        }
+    }
+}
+
 /// Central manager for all peer connections in the chat network.
 ///
 /// The `PeerManager` coordinates all peer-related operations including adding
@@ -128,36 +146,76 @@ pub struct PeerManager {
     /// Channel for sending messages between peers (reserved for future use).
     #[allow(dead_code)]
     message_tx: mpsc::Sender<(String, Message)>,
+    /// Counter used to hand out short peer IDs.
+    next_id: AtomicU32,
+    /// IDs of chat messages already delivered or forwarded.
+    seen: StdMutex<SeenMessages>,
+    /// Our own nickname, announced to every peer that connects.
+    local_nickname: StdMutex<Option<String>>,
+}
+
+/// Longest nickname accepted from a peer, in characters.
+pub const MAX_NICKNAME_LEN: usize = 32;
+
+/// Number of message IDs remembered for de-duplication.
+const SEEN_CAPACITY: usize = 4096;
+
+/// Bounded set of recently seen message IDs; the oldest are forgotten first.
+#[derive(Default)]
+struct SeenMessages {
+    order: VecDeque<u64>,
+    ids: HashSet<u64>,
+}
+
+impl SeenMessages {
+    fn insert(&mut self, id: u64) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
 }
 
 /// Represents an individual peer connection in the chat network.
 ///
 /// A `Peer` contains all the necessary information and communication channels
-/// for interacting with a connected peer. It wraps the TCP stream and provides
-/// a message channel for sending data to the peer.
+/// for interacting with a connected peer. Each peer has its own encryption
+/// session and file transfers; the connection's writer task drains `tx` and
+/// `bulk_tx` onto the socket.
 ///
 /// # Examples
 ///
 /// ```rust,no_run
+/// use rust_p2p_chat::encryption::E2EEncryption;
 /// use rust_p2p_chat::peer::{Peer, PeerInfo};
-/// use tokio::sync::mpsc;
+/// use rust_p2p_chat::transfer::TransferManager;
+/// use tokio::sync::{mpsc, Mutex, Notify};
 /// use std::sync::Arc;
 ///
 /// // Note: This is a conceptual example - actual peer creation
-/// // is handled by the PeerManager in practice
-/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-/// # let stream = tokio::net::TcpStream::connect("127.0.0.1:8080").await?;
+/// // is handled by the connection handler in practice
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
 /// # let (tx, _rx) = mpsc::channel(100);
+/// # let (bulk_tx, _bulk_rx) = mpsc::channel(4);
 /// # let info = PeerInfo {
-/// #     id: "peer1".to_string(),
+/// #     id: "1".to_string(),
 /// #     nickname: None,
 /// #     address: "127.0.0.1:8080".parse()?,
 /// #     connected_at: std::time::SystemTime::now(),
 /// # };
 /// let peer = Peer {
 ///     info,
-///     stream: Arc::new(stream),
 ///     tx,
+///     bulk_tx,
+///     encryption: Arc::new(Mutex::new(E2EEncryption::new()?)),
+///     transfers: Arc::new(TransferManager::new(100)),
+///     shutdown: Arc::new(Notify::new()),
 /// };
 /// # Ok(())
 /// # }
@@ -165,10 +223,23 @@ pub struct PeerManager {
 pub struct Peer {
     /// Metadata about this peer.
     pub info: PeerInfo,
-    /// Shared TCP stream for network communication.
-    pub stream: Arc<TcpStream>,
-    /// Channel sender for queuing messages to this peer.
+    /// Channel sender for queuing chat and control messages to this peer.
     pub tx: mpsc::Sender<Message>,
+    /// Channel sender for file chunks, which are sent after anything in `tx`.
+    pub bulk_tx: mpsc::Sender<Message>,
+    /// Encryption session negotiated with this peer.
+    pub encryption: Arc<Mutex<E2EEncryption>>,
+    /// File transfers to and from this peer.
+    pub transfers: Arc<TransferManager>,
+    /// Notified to close the connection to this peer.
+    pub shutdown: Arc<Notify>,
+}
+
+impl Peer {
+    /// Asks the connection handler to close the connection to this peer.
+    pub fn disconnect(&self) {
+        self.shutdown.notify_one();
+    }
 }
 
 impl PeerManager {
@@ -194,11 +265,22 @@ impl PeerManager {
             PeerManager {
                 peers: Arc::new(Mutex::new(HashMap::new())),
                 message_tx: tx,
+                next_id: AtomicU32::new(1),
+                seen: StdMutex::new(SeenMessages::default()),
+                local_nickname: StdMutex::new(None),
             },
             rx,
         )
     }
 
+    /// Returns a new, unused peer ID.
+    ///
+    /// IDs are short sequential numbers ("1", "2", ...) so they are easy to
+    /// type in commands.
+    pub fn next_peer_id(&self) -> String {
+        self.next_id.fetch_add(1, Ordering::Relaxed).to_string()
+    }
+
     /// Adds a new peer to the manager.
     ///
     /// # Arguments
@@ -219,10 +301,13 @@ impl PeerManager {
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let (peer_manager, _) = PeerManager::new();
     /// // peer creation omitted for brevity
-    /// # let stream = tokio::net::TcpStream::connect("127.0.0.1:8080").await?;
     /// # let (tx, _rx) = mpsc::channel(100);
+    /// # let (bulk_tx, _bulk_rx) = mpsc::channel(4);
     /// # let info = PeerInfo { id: "peer1".to_string(), nickname: None, address: "127.0.0.1:8080".parse()?, connected_at: std::time::SystemTime::now() };
-    /// # let peer = Peer { info, stream: Arc::new(stream), tx };
+    /// # let encryption = Arc::new(tokio::sync::Mutex::new(rust_p2p_chat::encryption::E2EEncryption::new()?));
+    /// # let transfers = Arc::new(rust_p2p_chat::transfer::TransferManager::new(100));
+    /// # let shutdown = Arc::new(tokio::sync::Notify::new());
+    /// # let peer = Peer { info, tx, bulk_tx, encryption, transfers, shutdown };
     /// peer_manager.add_peer("peer1".to_string(), peer).await?;
     /// # Ok(())
     /// # }
@@ -261,8 +346,212 @@ impl PeerManager {
         peers.get(id).cloned()
     }
 
+    /// Records that the message with `id` has been seen.
+    ///
+    /// Used to drop chat messages that reach us more than once over
+    /// different paths through the mesh.
+    ///
+    /// # Returns
+    ///
+    /// `true` the first time an ID is seen, `false` for duplicates.
+    pub fn mark_seen(&self, id: u64) -> bool {
+        self.seen.lock().unwrap().insert(id)
+    }
+
+    /// Returns the nickname we announce to peers, if one is set.
+    pub fn local_nickname(&self) -> Option<String> {
+        self.local_nickname.lock().unwrap().clone()
+    }
+
+    /// Sets the nickname announced to peers that connect from now on.
+    ///
+    /// Use [`announce_nickname`](Self::announce_nickname) to also tell the
+    /// peers that are already connected.
+    pub fn set_local_nickname(&self, nickname: Option<String>) {
+        *self.local_nickname.lock().unwrap() = nickname;
+    }
+
+    /// Changes our nickname and sends it to every connected peer.
+    ///
+    /// # Arguments
+    ///
+    /// * `nickname` - The new nickname
+    pub async fn announce_nickname(&self, nickname: &str) -> Result<()> {
+        self.set_local_nickname(Some(nickname.to_string()));
+        self.broadcast(Message::new_nickname(nickname.to_string()), None)
+            .await
+    }
+
+    /// Records the nickname a peer announced.
+    ///
+    /// Control characters are removed and the nickname is shortened to
+    /// [`MAX_NICKNAME_LEN`] characters. If another peer already uses the
+    /// same nickname (ignoring case), the peer ID is appended, e.g.
+    /// `Alice#3`, so that every peer can be told apart and addressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID of the peer that sent the nickname
+    /// * `nickname` - The nickname as received
+    ///
+    /// # Returns
+    ///
+    /// The name now used for the peer, or `None` if the peer is not
+    /// connected or the nickname is empty.
+    pub async fn set_nickname(&self, id: &str, nickname: &str) -> Option<String> {
+        let nickname: String = nickname
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_NICKNAME_LEN)
+            .collect();
+        let nickname = nickname.trim();
+        if nickname.is_empty() {
+            return None;
+        }
+
+        let mut peers = self.peers.lock().await;
+        let taken = peers.iter().any(|(other, peer)| {
+            other != id
+                && peer
+                    .info
+                    .nickname
+                    .as_deref()
+                    .is_some_and(|nick| nick.eq_ignore_ascii_case(nickname))
+        });
+        let assigned = if taken {
+            format!("{}#{}", nickname, id)
+        } else {
+            nickname.to_string()
+        };
+        let peer = peers.get_mut(id)?;
+        peer.info.nickname = Some(assigned.clone());
+        Some(assigned)
+    }
+
+    /// Finds a connected peer by ID or nickname.
+    ///
+    /// IDs are matched first; nicknames are matched case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Peer ID as shown by `/peers`, or the peer's nickname
+    pub async fn find_peer(&self, name: &str) -> Option<Peer> {
+        if let Some(peer) = self.get_peer(name).await {
+            return Some(peer);
+        }
+        let peers = self.peers.lock().await;
+        peers
+            .values()
+            .find(|peer| {
+                peer.info
+                    .nickname
+                    .as_deref()
+                    .is_some_and(|nick| nick.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+    }
+
+    /// Splits the arguments of `/msg` into the recipient and the message.
+    ///
+    /// Nicknames may contain spaces, so the longest run of leading words
+    /// that names a connected peer is the recipient and the remaining words
+    /// are the message.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Everything after `/msg`
+    ///
+    /// # Returns
+    ///
+    /// The recipient and the message, or `None` if the message would be
+    /// empty or no leading words name a connected peer.
+    pub async fn split_recipient(&self, args: &str) -> Option<(Peer, String)> {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        for split in (1..words.len()).rev() {
+            if let Some(peer) = self.find_peer(&words[..split].join(" ")).await {
+                return Some((peer, words[split..].join(" ")));
+            }
+        }
+        None
+    }
+
+    /// Closes the connection to a peer.
+    ///
+    /// The peer is removed right away; its connection handler closes the
+    /// socket shortly after.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Peer ID or nickname
+    ///
+    /// # Returns
+    ///
+    /// The peer that was disconnected.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if no such peer is connected
+    pub async fn disconnect(&self, name: &str) -> Result<Peer> {
+        let peer = self
+            .find_peer(name)
+            .await
+            .ok_or_else(|| ChatError::Connection(format!("no connected peer named '{}'", name)))?;
+        peer.disconnect();
+        self.remove_peer(&peer.info.id).await;
+        Ok(peer)
+    }
+
+    /// Sends a private message to a single peer.
+    ///
+    /// The text is encrypted with that peer's session key and is not
+    /// relayed to anyone else.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Peer ID or nickname of the recipient
+    /// * `text` - The message to send
+    ///
+    /// # Returns
+    ///
+    /// The peer the message was sent to.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if no such peer is connected or the
+    ///   encryption handshake with it has not completed
+    /// - `ChatError::Encryption` if the text cannot be encrypted
+    /// - `ChatError::PeerDisconnected` if the peer's connection has closed
+    pub async fn send_private(&self, name: &str, text: &str) -> Result<Peer> {
+        let peer = self
+            .find_peer(name)
+            .await
+            .ok_or_else(|| ChatError::Connection(format!("no connected peer named '{}'", name)))?;
+
+        let encrypted = {
+            let enc = peer.encryption.lock().await;
+            if !enc.is_verified() {
+                return Err(ChatError::Connection(format!(
+                    "encryption with {} is not established yet",
+                    peer.info.display_name()
+                )));
+            }
+            enc.encrypt_message(text)?
+        };
+        peer.tx
+            .send(Message::new_private_text(encrypted))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        Ok(peer)
+    }
+
     /// Broadcasts a message to all connected peers.
     ///
+    /// This is also how messages are relayed: the message ID is marked as
+    /// seen so that copies coming back to us are dropped, and the peer the
+    /// message arrived from is excluded. Chat text, including the payload of
+    /// a [`GossipMessage`], is encrypted separately for each peer whose
+    /// encryption handshake has completed.
+    ///
     /// # Arguments
     ///
     /// * `message` - The message to broadcast
@@ -290,23 +579,75 @@ impl PeerManager {
     /// # }
     /// ```
     pub async fn broadcast(&self, message: Message, exclude: Option<&str>) -> Result<()> {
-        let peers = self.peers.lock().await;
-        for (id, peer) in peers.iter() {
-            if exclude.is_none_or(|ex| ex != id) {
-                let _ = peer.tx.send(message.clone()).await;
+        self.forward(message, exclude).await;
+        Ok(())
+    }
+
+    /// Sends a chat message to every connected peer.
+    ///
+    /// The text is encrypted separately for each peer whose encryption
+    /// handshake has completed and sent as plain text to the others. All
+    /// copies share one message ID, so peers relaying it can tell them apart
+    /// from new messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The message to send
+    ///
+    /// # Returns
+    ///
+    /// The number of peers the message was queued for.
+    pub async fn broadcast_text(&self, text: &str) -> Result<usize> {
+        Ok(self
+            .forward(Message::new_text(text.to_string()), None)
+            .await)
+    }
+
+    async fn forward(&self, message: Message, exclude: Option<&str>) -> usize {
+        self.mark_seen(message.id);
+
+        let peers: Vec<Peer> = {
+            let peers = self.peers.lock().await;
+            peers
+                .iter()
+                .filter(|(id, _)| exclude != Some(id.as_str()))
+                .map(|(_, peer)| peer.clone())
+                .collect()
+        };
+
+        let mut sent = 0;
+        for peer in peers {
+            let msg_type = {
+                let enc = peer.encryption.lock().await;
+                encrypt_for_peer(&enc, message.msg_type.clone())
+            };
+            let message = Message {
+                msg_type,
+                ..message.clone()
+            };
+            if peer.tx.send(message).await.is_ok() {
+                sent += 1;
             }
         }
-        Ok(())
+        sent
+    }
+
+    /// Returns all connected peers, in the order they connected.
+    pub async fn peers(&self) -> Vec<Peer> {
+        let peers = self.peers.lock().await;
+        let mut peers: Vec<Peer> = peers.values().cloned().collect();
+        peers.sort_by_key(|p| p.info.connected_at);
+        peers
     }
 
-    /// Returns a list of all connected peers' information.
+    /// Returns a list of all connected peers' information, in the order
+    /// they connected.
     ///
     /// # Returns
     ///
     /// A vector containing `PeerInfo` for all connected peers.
     pub async fn list_peers(&self) -> Vec<PeerInfo> {
-        let peers = self.peers.lock().await;
-        peers.values().map(|p| p.info.clone()).collect()
+        self.peers().await.into_iter().map(|p| p.info).collect()
     }
 
     /// Returns the number of currently connected peers.
@@ -320,16 +661,38 @@ impl PeerManager {
     }
 }
 
+/// Encrypts chat text for a peer once its handshake has completed and it
+/// has proven its identity.
+///
+/// Other message types, and text for peers without a session key, are
+/// returned unchanged.
+fn encrypt_for_peer(enc: &E2EEncryption, msg_type: MessageType) -> MessageType {
+    match msg_type {
+        MessageType::Text(text) if enc.is_verified() => match enc.encrypt_message(&text) {
+            Ok(encrypted) => MessageType::EncryptedText(encrypted),
+            Err(_) => MessageType::Text(text),
+        },
+        MessageType::Gossip(gossip) => MessageType::Gossip(GossipMessage {
+            payload: Box::new(encrypt_for_peer(enc, *gossip.payload)),
+            ..gossip
+        }),
+        other => other,
+    }
+}
+
 impl Clone for Peer {
     /// Creates a clone of the peer.
     ///
-    /// All components (info, stream, and channel) are cloned, allowing
-    /// the peer to be shared across multiple async tasks safely.
+    /// All components (info, channels and shared session state) are cloned,
+    /// allowing the peer to be shared across multiple async tasks safely.
     fn clone(&self) -> Self {
         Peer {
             info: self.info.clone(),
-            stream: self.stream.clone(),
             tx: self.tx.clone(),
+            bulk_tx: self.bulk_tx.clone(),
+            encryption: self.encryption.clone(),
+            transfers: self.transfers.clone(),
+            shutdown: self.shutdown.clone(),
         }
     }
 }
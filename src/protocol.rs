@@ -15,12 +15,21 @@
 //! - **Heartbeats**: Keep-alive messages for connection monitoring
 //! - **Acknowledgments**: Message delivery confirmations
 //! - **Encryption**: Key exchange and encryption setup messages
+//! - **Transfers**: Chunked file transfers with pause, resume and cancel control
+//! - **Capabilities**: Optional features (such as compression) each peer supports
 //!
 //! # Serialization
 //!
 //! All messages are serialized using bincode for efficient binary encoding.
 //! This provides fast serialization/deserialization and compact message sizes.
 //!
+//! # Framing
+//!
+//! On the wire every binary message is wrapped in a frame: a [`FRAME_MARKER`]
+//! byte, a big-endian `u32` payload length and the bincode payload. Plain text
+//! lines (terminated by `\n`) may be interleaved with frames for compatibility
+//! with simple peers; [`FrameDecoder`] separates the two.
+//!
 //! # Security
 //!
 //! - Encrypted messages use Base64 encoding for text representation
@@ -51,9 +60,25 @@
 //! }
 //! ```
 
+use crate::error::ChatError;
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 
+/// Byte that introduces a binary frame on the wire.
+///
+/// Typed chat text never contains a NUL byte, so the marker cleanly separates
+/// binary frames from legacy newline-terminated text.
+pub const FRAME_MARKER: u8 = 0x00;
+
+/// Largest frame payload accepted from a peer (16 MB).
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Longest plain text line accepted from a peer (64 KB).
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Size of the frame header: marker byte plus `u32` length.
+const FRAME_HEADER_LEN: usize = 5;
+
 /// Enumeration of all supported message types in the P2P chat protocol.
 ///
 /// Each variant represents a different type of communication that can occur
@@ -80,6 +105,8 @@ pub enum MessageType {
     Text(String),
     /// Base64 encoded encrypted text message using AES-256-GCM.
     EncryptedText(String),
+    /// Encrypted text addressed only to the receiving peer; never relayed.
+    PrivateText(String),
     /// File transfer data with metadata and integrity verification.
     File(FileInfo),
     /// Command execution request (e.g., /help, /quit).
@@ -92,6 +119,91 @@ pub enum MessageType {
     Acknowledgment(u64),
     /// Encryption-related messages for key exchange and setup.
     Encryption(EncryptionMessage),
+    /// Chunked file transfer data and control messages.
+    Transfer(TransferMessage),
+    /// Optional protocol features supported by the sender.
+    Capabilities(Capabilities),
+    /// Chat message relayed on behalf of a peer that may not be directly connected.
+    Gossip(GossipMessage),
+    /// The sender's nickname, sent on connect and whenever it changes.
+    Nickname(String),
+    /// The sender is leaving; nothing follows on the connection.
+    Goodbye,
+}
+
+/// Compression algorithms that can be negotiated between peers.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Compression {
+    /// Raw deflate (RFC 1951).
+    Deflate,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Deflate => write!(f, "deflate"),
+        }
+    }
+}
+
+/// Optional protocol features, exchanged once when a connection starts.
+///
+/// Each peer sends its own capabilities; a feature is only used when both
+/// sides support it.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::protocol::{Capabilities, Compression, Message, MessageType};
+///
+/// let caps = Capabilities { compression: vec![Compression::Deflate] };
+/// let msg = Message::new_capabilities(caps.clone());
+/// assert_eq!(msg.msg_type, MessageType::Capabilities(caps));
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Capabilities {
+    /// Supported compression algorithms, in order of preference.
+    pub compression: Vec<Compression>,
+}
+
+/// Maximum number of hops a chat message travels through the mesh.
+pub const GOSSIP_TTL: u8 = 8;
+
+/// A chat message forwarded through the mesh of connected peers.
+///
+/// When a peer receives a chat message it has not seen before, it passes it
+/// on to its other peers as a `GossipMessage`, keeping the original
+/// [`Message::id`] so that copies arriving over several paths are dropped.
+/// Each hop decrements `ttl` and the message is no longer forwarded once it
+/// would reach zero.
+///
+/// The payload is [`MessageType::Text`] or [`MessageType::EncryptedText`];
+/// encrypted payloads use the session key of the hop the message travels
+/// over, so every relay can read the messages it forwards. Nothing
+/// authenticates `origin` either: receivers show the relay that delivered
+/// the message and treat the origin as a claim.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::protocol::{GossipMessage, MessageType, GOSSIP_TTL};
+///
+/// let gossip = GossipMessage {
+///     origin: "Alice".to_string(),
+///     ttl: GOSSIP_TTL - 1,
+///     payload: Box::new(MessageType::Text("Hello everyone".to_string())),
+/// };
+/// assert!(gossip.ttl > 0);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GossipMessage {
+    /// Display name of the peer that wrote the message, as given by the
+    /// first relay.
+    pub origin: String,
+    /// Remaining hops, including the one this message is travelling over.
+    pub ttl: u8,
+    /// The chat text.
+    pub payload: Box<MessageType>,
 }
 
 /// Core message structure for P2P chat communication.
@@ -202,6 +314,39 @@ pub enum Command {
     ToggleAutoOpen,
     /// Display message reliability and connection statistics.
     Stats,
+    /// List active and finished file transfers.
+    ListTransfers,
+    /// Cancel a file transfer (contains transfer ID).
+    CancelTransfer(u32),
+    /// Pause a file transfer (contains transfer ID).
+    PauseTransfer(u32),
+    /// Resume a paused file transfer (contains transfer ID).
+    ResumeTransfer(u32),
+    /// Show the current transfer bandwidth limits.
+    ShowLimits,
+    /// Set the upload limit in KB/s (`None` removes the limit).
+    SetUploadLimit(Option<u64>),
+    /// Set the download limit in KB/s (`None` removes the limit).
+    SetDownloadLimit(Option<u64>),
+    /// Send a private message to one peer (peer ID or nickname, text).
+    ///
+    /// The parser splits at the first space; a session finds nicknames with
+    /// spaces with [`split_recipient`](crate::peer::PeerManager::split_recipient).
+    PrivateMessage(String, String),
+    /// List saved contacts.
+    ListContacts,
+    /// Save a contact (name, address, optional identity fingerprint).
+    AddContact(String, String, Option<String>),
+    /// Remove a saved contact (contains the name).
+    RemoveContact(String),
+    /// Connect to another peer (address or contact name).
+    Connect(String),
+    /// Close the connection to a peer (peer ID or nickname).
+    Disconnect(String),
+    /// List peers found on the local network.
+    Discover,
+    /// Accept a file transfer offered by a peer (contains transfer ID).
+    AcceptTransfer(u32),
 }
 
 /// Status update messages for system events and notifications.
@@ -247,23 +392,28 @@ pub enum StatusUpdate {
 ///
 /// # Security Protocol
 ///
-/// 1. Peers exchange RSA public keys using `PublicKeyExchange`
-/// 2. One peer generates an AES-256 key and sends it encrypted with the other's public key
-/// 3. Both peers confirm successful setup with `HandshakeComplete`
+/// 1. Peers exchange RSA public keys using `PublicKeyExchange`, each followed
+///    by a fresh random `Challenge`
+/// 2. The peer with the greater key generates an AES-256 key and sends it
+///    encrypted with the other's public key
+/// 3. Each peer sends an `IdentityProof`, a signature with its identity key
+///    over both keys, both challenges and the session key; encryption is only
+///    reported as enabled once the peer's proof checks out
 ///
 /// # Examples
 ///
 /// ```rust
 /// use rust_p2p_chat::protocol::EncryptionMessage;
 ///
-/// // Step 1: Exchange public keys
+/// // Step 1: Exchange public keys and challenges
 /// let pub_key_msg = EncryptionMessage::PublicKeyExchange("base64_public_key".to_string());
+/// let challenge_msg = EncryptionMessage::Challenge("base64_challenge".to_string());
 ///
 /// // Step 2: Share encrypted AES key
 /// let shared_key_msg = EncryptionMessage::SharedKeyExchange("encrypted_aes_key".to_string());
 ///
-/// // Step 3: Confirm handshake
-/// let complete_msg = EncryptionMessage::HandshakeComplete;
+/// // Step 3: Prove possession of the identity key
+/// let proof_msg = EncryptionMessage::IdentityProof("base64_signature".to_string());
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum EncryptionMessage {
@@ -273,6 +423,175 @@ pub enum EncryptionMessage {
     SharedKeyExchange(String),
     /// Confirmation that encryption handshake is complete.
     HandshakeComplete,
+    /// Random challenge the peer must sign (Base64 encoded).
+    Challenge(String),
+    /// Signature over the handshake transcript with the sender's identity
+    /// key (Base64 encoded).
+    IdentityProof(String),
+}
+
+/// Metadata announcing a chunked file transfer.
+///
+/// Sent once before any [`TransferMessage::Chunk`] so the receiver can check
+/// the size limit, create a partial file and later verify the SHA-256 hash.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::protocol::TransferHeader;
+///
+/// let header = TransferHeader {
+///     id: 4242,
+///     name: "video.mp4".to_string(),
+///     size: 10 * 1024 * 1024,
+///     hash: "abc123...".to_string(),
+/// };
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransferHeader {
+    /// Transfer identifier chosen by the sender.
+    pub id: u32,
+    /// Original filename including extension.
+    pub name: String,
+    /// Total file size in bytes.
+    pub size: u64,
+    /// SHA-256 hash of the complete file for integrity verification.
+    pub hash: String,
+}
+
+/// A single file inside a [`DirectoryManifest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManifestEntry {
+    /// Path relative to the transferred directory, using `/` as separator.
+    pub path: String,
+    /// File size in bytes.
+    pub size: u64,
+    /// SHA-256 hash of the file for integrity verification.
+    pub hash: String,
+}
+
+/// Metadata announcing a directory transfer.
+///
+/// The files listed in `entries` are streamed back to back as one chunked
+/// transfer, in manifest order. The receiver recreates the tree under
+/// `<download_dir>/<name>` and verifies every file against its own hash.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::protocol::{DirectoryManifest, ManifestEntry};
+///
+/// let manifest = DirectoryManifest {
+///     id: 4242,
+///     name: "photos".to_string(),
+///     entries: vec![
+///         ManifestEntry { path: "a.jpg".to_string(), size: 100, hash: "abc...".to_string() },
+///         ManifestEntry { path: "2024/b.jpg".to_string(), size: 50, hash: "def...".to_string() },
+///     ],
+/// };
+/// assert_eq!(manifest.total_size().unwrap(), 150);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DirectoryManifest {
+    /// Transfer identifier chosen by the sender.
+    pub id: u32,
+    /// Name of the transferred directory.
+    pub name: String,
+    /// Files in the order their data is streamed.
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl DirectoryManifest {
+    /// Returns the combined size of all files in bytes.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the sizes add up to more than `u64::MAX`,
+    ///   which only a malformed manifest does
+    pub fn total_size(&self) -> crate::error::Result<u64> {
+        self.entries
+            .iter()
+            .try_fold(0u64, |total, entry| total.checked_add(entry.size))
+            .ok_or_else(|| {
+                ChatError::FileTransfer(format!("Invalid size in manifest of {}", self.name))
+            })
+    }
+}
+
+/// Messages that drive a chunked file transfer.
+///
+/// The sender announces the file with `Start` (or a whole directory with
+/// `StartDirectory`) and waits for the receiver to reply with `Accept`, or
+/// with `AlreadyHave` if it already has a file with the same hash. After an
+/// `Accept` the sender streams the data as a sequence of
+/// `Chunk`s and finishes with `Complete`. Either side may send `Pause`,
+/// `Resume` or `Cancel` at any point to control the transfer.
+///
+/// Every message carries the ID the sender announced the transfer with. Both
+/// peers may send files at the same time and pick their IDs independently,
+/// so `Pause`, `Resume` and `Cancel` also tell which side sent them.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::protocol::TransferMessage;
+///
+/// let chunk = TransferMessage::Chunk { id: 4242, offset: 0, data: vec![1, 2, 3] };
+/// let pause = TransferMessage::Pause { id: 4242, from_sender: false };
+/// assert_eq!(chunk.transfer_id(), pause.transfer_id());
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TransferMessage {
+    /// Announces a new transfer.
+    Start(TransferHeader),
+    /// Announces a new directory transfer.
+    StartDirectory(DirectoryManifest),
+    /// The receiver wants the data; the sender may start streaming.
+    Accept { id: u32 },
+    /// The receiver already has the content; no data needs to be sent.
+    AlreadyHave { id: u32 },
+    /// A slice of file data starting at `offset`.
+    ///
+    /// For directory transfers the offset counts across all files of the
+    /// manifest, so a chunk may span the end of one file and the start of
+    /// the next.
+    Chunk { id: u32, offset: u64, data: Vec<u8> },
+    /// A [`Chunk`](Self::Chunk) whose data was compressed with the negotiated
+    /// algorithm. `offset` refers to the uncompressed stream.
+    CompressedChunk {
+        id: u32,
+        offset: u64,
+        algorithm: Compression,
+        data: Vec<u8>,
+    },
+    /// All chunks have been sent.
+    Complete { id: u32 },
+    /// Stop sending chunks until resumed.
+    ///
+    /// `from_sender` is set when the peer sending the data sent the message.
+    Pause { id: u32, from_sender: bool },
+    /// Continue a paused transfer.
+    Resume { id: u32, from_sender: bool },
+    /// Abort the transfer; the receiver discards partial data.
+    Cancel { id: u32, from_sender: bool },
+}
+
+impl TransferMessage {
+    /// Returns the ID of the transfer this message belongs to.
+    pub fn transfer_id(&self) -> u32 {
+        match self {
+            TransferMessage::Start(header) => header.id,
+            TransferMessage::StartDirectory(manifest) => manifest.id,
+            TransferMessage::Chunk { id, .. }
+            | TransferMessage::CompressedChunk { id, .. }
+            | TransferMessage::Accept { id }
+            | TransferMessage::AlreadyHave { id }
+            | TransferMessage::Complete { id }
+            | TransferMessage::Pause { id, .. }
+            | TransferMessage::Resume { id, .. }
+            | TransferMessage::Cancel { id, .. } => *id,
+        }
+    }
 }
 
 impl Message {
@@ -380,4 +699,219 @@ impl Message {
             msg_type: MessageType::EncryptedText(encrypted),
         }
     }
+
+    /// Creates a new private message.
+    ///
+    /// # Arguments
+    ///
+    /// * `encrypted` - Base64 encoded text, encrypted with the recipient's session key
+    pub fn new_private_text(encrypted: String) -> Self {
+        Message {
+            id: rand::random(),
+            timestamp: SystemTime::now(),
+            msg_type: MessageType::PrivateText(encrypted),
+        }
+    }
+
+    /// Creates a nickname announcement.
+    ///
+    /// # Arguments
+    ///
+    /// * `nickname` - The sender's current nickname
+    pub fn new_nickname(nickname: String) -> Self {
+        Message {
+            id: rand::random(),
+            timestamp: SystemTime::now(),
+            msg_type: MessageType::Nickname(nickname),
+        }
+    }
+
+    /// Creates the last message sent before closing a connection on purpose,
+    /// so the peer can tell leaving apart from a dropped connection.
+    pub fn new_goodbye() -> Self {
+        Message {
+            id: rand::random(),
+            timestamp: SystemTime::now(),
+            msg_type: MessageType::Goodbye,
+        }
+    }
+
+    /// Creates a new file transfer message.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The transfer message (start, chunk, control, etc.)
+    pub fn new_transfer(msg: TransferMessage) -> Self {
+        Message {
+            id: rand::random(),
+            timestamp: SystemTime::now(),
+            msg_type: MessageType::Transfer(msg),
+        }
+    }
+
+    /// Creates a new capabilities announcement.
+    ///
+    /// # Arguments
+    ///
+    /// * `capabilities` - The optional features supported locally
+    pub fn new_capabilities(capabilities: Capabilities) -> Self {
+        Message {
+            id: rand::random(),
+            timestamp: SystemTime::now(),
+            msg_type: MessageType::Capabilities(capabilities),
+        }
+    }
+
+    /// Serializes the message into a length-prefixed wire frame.
+    ///
+    /// # Returns
+    ///
+    /// Returns the frame bytes: [`FRAME_MARKER`], big-endian `u32` payload
+    /// length, then the bincode payload.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_p2p_chat::protocol::{Frame, FrameDecoder, Message};
+    ///
+    /// let frame = Message::new_heartbeat().to_frame().unwrap();
+    ///
+    /// let mut decoder = FrameDecoder::new();
+    /// decoder.extend(&frame);
+    /// assert!(matches!(decoder.next_frame().unwrap(), Some(Frame::Message(_))));
+    /// ```
+    pub fn to_frame(&self) -> Result<Vec<u8>, bincode::Error> {
+        let payload = self.serialize()?;
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        frame.push(FRAME_MARKER);
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+
+    /// Returns the number of file data bytes carried by this message.
+    ///
+    /// This is the chunk payload for [`TransferMessage::Chunk`] and
+    /// [`TransferMessage::CompressedChunk`] and zero for everything else.
+    /// Bandwidth limits only count these bytes.
+    pub fn chunk_len(&self) -> usize {
+        match &self.msg_type {
+            MessageType::Transfer(
+                TransferMessage::Chunk { data, .. } | TransferMessage::CompressedChunk { data, .. },
+            ) => data.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// A unit of data decoded from the wire.
+#[derive(Debug)]
+pub enum Frame {
+    /// A binary protocol message.
+    Message(Message),
+    /// A newline-terminated plain text line (without the terminator).
+    Text(String),
+}
+
+/// Incremental decoder that splits a byte stream into [`Frame`]s.
+///
+/// TCP delivers a stream of bytes, so a single read may contain several
+/// messages or only part of one. The decoder buffers input until a complete
+/// frame or text line is available.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::protocol::{Frame, FrameDecoder, Message};
+///
+/// let mut decoder = FrameDecoder::new();
+/// decoder.extend(b"hello\n");
+/// decoder.extend(&Message::new_heartbeat().to_frame().unwrap());
+///
+/// assert!(matches!(decoder.next_frame().unwrap(), Some(Frame::Text(t)) if t == "hello"));
+/// assert!(matches!(decoder.next_frame().unwrap(), Some(Frame::Message(_))));
+/// assert!(decoder.next_frame().unwrap().is_none());
+/// ```
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// Creates an empty decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends bytes received from the peer.
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete frame, or `None` if more data is needed.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::InvalidMessage` if a frame exceeds [`MAX_FRAME_SIZE`]
+    ///   or its payload cannot be deserialized, or if a text line exceeds
+    ///   [`MAX_LINE_LEN`]
+    pub fn next_frame(&mut self) -> crate::error::Result<Option<Frame>> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        if self.buffer[0] == FRAME_MARKER {
+            if self.buffer.len() < FRAME_HEADER_LEN {
+                return Ok(None);
+            }
+            let len = u32::from_be_bytes([
+                self.buffer[1],
+                self.buffer[2],
+                self.buffer[3],
+                self.buffer[4],
+            ]) as usize;
+            if len > MAX_FRAME_SIZE {
+                self.buffer.clear();
+                return Err(ChatError::InvalidMessage(format!(
+                    "frame of {} bytes exceeds limit",
+                    len
+                )));
+            }
+            if self.buffer.len() < FRAME_HEADER_LEN + len {
+                return Ok(None);
+            }
+            let payload: Vec<u8> = self.buffer.drain(..FRAME_HEADER_LEN + len).collect();
+            let message = Message::deserialize(&payload[FRAME_HEADER_LEN..])
+                .map_err(|e| ChatError::InvalidMessage(e.to_string()))?;
+            return Ok(Some(Frame::Message(message)));
+        }
+
+        match self
+            .buffer
+            .iter()
+            .position(|&b| b == b'\n' || b == FRAME_MARKER)
+        {
+            // Without a limit a peer could grow the buffer without ever
+            // ending the line
+            Some(pos) if pos > MAX_LINE_LEN => self.line_too_long(),
+            None if self.buffer.len() > MAX_LINE_LEN => self.line_too_long(),
+            Some(pos) => {
+                let line: Vec<u8> = self.buffer.drain(..pos).collect();
+                if self.buffer.first() == Some(&b'\n') {
+                    self.buffer.remove(0);
+                }
+                let text = String::from_utf8_lossy(&line).trim_end().to_string();
+                Ok(Some(Frame::Text(text)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn line_too_long(&mut self) -> crate::error::Result<Option<Frame>> {
+        self.buffer.clear();
+        Err(ChatError::InvalidMessage(format!(
+            "text line exceeds {} bytes",
+            MAX_LINE_LEN
+        )))
+    }
 }
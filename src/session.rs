@@ -0,0 +1,289 @@
+//! Handle to a running chat session.
+//!
+//! A [`ChatSession`] runs a [`P2PChat`] in the background and is the only
+//! thing a frontend needs: it yields the session's [`ChatEvent`]s and has
+//! methods for sending messages, files and commands. The terminal UI and the
+//! GUI are both built on it, and it works just as well from tests or other
+//! programs that embed the chat engine.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rust_p2p_chat::events::ChatEvent;
+//! use rust_p2p_chat::session::ChatSession;
+//! use rust_p2p_chat::{Config, P2PChat};
+//!
+//! #[tokio::main]
+//! async fn main() -> rust_p2p_chat::Result<()> {
+//!     let chat = P2PChat::new(Config::default())?;
+//!     let mut session = ChatSession::start(chat, 0, Some("192.168.1.100:8080".to_string()));
+//!
+//!     while let Some(event) = session.next_event().await {
+//!         match event {
+//!             ChatEvent::PeerConnected { .. } => session.send_message("Hello!")?,
+//!             ChatEvent::MessageReceived { from, text, .. } => println!("{}: {}", from, text),
+//!             _ => {}
+//!         }
+//!     }
+//!     session.wait().await
+//! }
+//! ```
+
+use crate::config::Config;
+use crate::error::{ChatError, Result};
+use crate::events::ChatEvent;
+use crate::protocol::Command;
+use crate::P2PChat;
+use futures::Stream;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Input from a frontend to the session.
+#[derive(Debug)]
+pub(crate) enum Input {
+    /// A line typed at the prompt; commands start with `/`.
+    Line(String),
+    /// Chat text sent to every peer as-is, even if it starts with `/`.
+    Text(String),
+    /// A command built by the frontend.
+    Command(Command),
+    /// New settings for the running session.
+    Config(Box<Config>),
+}
+
+/// A running chat session.
+///
+/// Dropping the handle, or calling [`close`](Self::close), ends the session.
+pub struct ChatSession {
+    input: mpsc::UnboundedSender<Input>,
+    events: broadcast::Receiver<ChatEvent>,
+    local_addr: watch::Receiver<Option<SocketAddr>>,
+    task: Option<JoinHandle<Result<()>>>,
+}
+
+impl ChatSession {
+    /// Starts `chat` in the background.
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `chat` - The chat to run
+    /// * `listen_port` - Port to listen on, or 0 for any free port
+    /// * `peer_address` - Optional address or contact name to connect to
+    pub fn start(mut chat: P2PChat, listen_port: u16, peer_address: Option<String>) -> Self {
+        let (mut session, input) = ChatSession::attach(&chat);
+        session.task = Some(tokio::spawn(async move {
+            chat.run(listen_port, peer_address, input).await
+        }));
+        session
+    }
+
+    /// Creates a handle for a session the caller runs itself, with the
+    /// returned input receiver.
+    pub(crate) fn attach(chat: &P2PChat) -> (Self, mpsc::UnboundedReceiver<Input>) {
+        let (input, receiver) = mpsc::unbounded_channel();
+        let session = ChatSession {
+            input,
+            events: chat.subscribe(),
+            local_addr: chat.local_addr(),
+            task: None,
+        };
+        (session, receiver)
+    }
+
+    /// Returns a receiver for the address the session listens on.
+    ///
+    /// The value is `None` until the listener is bound.
+    pub fn local_addr(&self) -> watch::Receiver<Option<SocketAddr>> {
+        self.local_addr.clone()
+    }
+
+    /// Waits for the next event.
+    ///
+    /// Events the handle fell too far behind on are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The event, or `None` once the session has ended and every event
+    /// published before was returned.
+    pub async fn next_event(&mut self) -> Option<ChatEvent> {
+        loop {
+            let received = tokio::select! {
+                biased;
+                received = self.events.recv() => received,
+                _ = self.input.closed() => return self.try_next_event(),
+            };
+            match received {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => warn!("Skipped {} chat events", missed),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Returns the next event if one is waiting, without blocking.
+    ///
+    /// Suited to frontends that poll, such as the GUI once per frame.
+    pub fn try_next_event(&mut self) -> Option<ChatEvent> {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Lagged(missed)) => warn!("Skipped {} chat events", missed),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Returns the events as a stream, which ends with the session.
+    pub fn events(&mut self) -> impl Stream<Item = ChatEvent> + '_ {
+        futures::stream::unfold(self, |session| async move {
+            let event = session.next_event().await?;
+            Some((event, session))
+        })
+    }
+
+    /// Returns another receiver for the events of the session.
+    ///
+    /// It only receives events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ChatEvent> {
+        self.events.resubscribe()
+    }
+
+    /// Handles a line as if typed at the prompt: commands start with `/`,
+    /// anything else is sent to every peer.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if the session has ended
+    pub fn send_line(&self, line: impl Into<String>) -> Result<()> {
+        self.send(Input::Line(line.into()))
+    }
+
+    /// Sends a chat message to every peer.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if the session has ended
+    pub fn send_message(&self, text: impl Into<String>) -> Result<()> {
+        self.send(Input::Text(text.into()))
+    }
+
+    /// Sends a message to one peer only, like `/msg`.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer` - Peer ID or nickname
+    /// * `text` - The message
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if the session has ended
+    pub fn send_private(&self, peer: impl Into<String>, text: impl Into<String>) -> Result<()> {
+        self.command(Command::PrivateMessage(peer.into(), text.into()))
+    }
+
+    /// Sends a file or directory to every peer, like `/send`.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if the session has ended
+    pub fn send_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref().to_string_lossy().into_owned();
+        self.command(Command::SendFile(path))
+    }
+
+    /// Connects to another peer, like `/connect`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Address, contact name or discovered nickname
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if the session has ended
+    pub fn connect(&self, target: impl Into<String>) -> Result<()> {
+        self.command(Command::Connect(target.into()))
+    }
+
+    /// Runs a command, with its outcome reported as events.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if the session has ended
+    pub fn command(&self, command: Command) -> Result<()> {
+        self.send(Input::Command(command))
+    }
+
+    /// Replaces the settings of the running session.
+    ///
+    /// Bandwidth limits and the nickname change at once, and later
+    /// transfers use the new download directory, size limit and media
+    /// settings. Connection settings apply to peers that connect afterwards;
+    /// the listening address and LAN discovery need a new session.
+    /// The session confirms with a `Notice`. Nothing is written to disk.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Connection` if the session has ended
+    pub fn update_config(&self, config: Config) -> Result<()> {
+        self.send(Input::Config(Box::new(config)))
+    }
+
+    /// Returns `true` once the session has ended.
+    ///
+    /// [`wait`](Self::wait) then returns without blocking.
+    pub fn is_finished(&self) -> bool {
+        match &self.task {
+            Some(task) => task.is_finished(),
+            None => self.input.is_closed(),
+        }
+    }
+
+    /// Waits until the session has ended.
+    pub async fn closed(&self) {
+        self.input.closed().await
+    }
+
+    /// Waits for the session to end on its own, such as after `/quit`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that ended the session, such as
+    /// `ChatError::BindFailed`.
+    pub async fn wait(self) -> Result<()> {
+        self.input.closed().await;
+        match self.task {
+            Some(task) => task
+                .await
+                .map_err(|e| ChatError::Connection(format!("Chat session failed: {}", e)))?,
+            None => Ok(()),
+        }
+    }
+
+    /// Ends the session and waits for it to stop.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that ended the session, if it failed first.
+    pub async fn close(self) -> Result<()> {
+        let ChatSession { input, task, .. } = self;
+        drop(input);
+        match task {
+            Some(task) => task
+                .await
+                .map_err(|e| ChatError::Connection(format!("Chat session failed: {}", e)))?,
+            None => Ok(()),
+        }
+    }
+
+    fn send(&self, input: Input) -> Result<()> {
+        self.input
+            .send(input)
+            .map_err(|_| ChatError::Connection("Chat session has ended".to_string()))
+    }
+}
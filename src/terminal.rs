@@ -0,0 +1,537 @@
+//! The terminal frontend.
+//!
+//! Reads lines from stdin into a [`ChatSession`] and prints its events with
+//! colors, keeping a `You:` prompt at the bottom. This is what
+//! [`P2PChat::start`](crate::P2PChat::start) runs.
+//!
+//! When stdin is a terminal, the line can be edited, earlier lines are
+//! recalled with the arrow keys, also across sessions, and Tab completes
+//! commands, file paths and names (see [`Completions`]). Private messages are
+//! left out of the input history (see [`kept_in_history`]).
+
+use crate::colors::Colors;
+use crate::commands::CommandHandler;
+use crate::completion::Completions;
+use crate::config::Config;
+use crate::contacts::ContactIdentity;
+use crate::error::Result;
+use crate::events::ChatEvent;
+use crate::protocol::Command;
+use crate::session::ChatSession;
+use crate::transfer::TransferEvent;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Context, Editor, ExternalPrinter, Helper};
+use std::borrow::Cow;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+#[cfg(unix)]
+use nix::sys::termios::{self, SetArg, Termios};
+
+/// Number of typed lines remembered across sessions.
+const MAX_HISTORY: usize = 500;
+
+const PROMPT: &str = "You: ";
+
+/// Runs the terminal frontend until the session ends or stdin is closed.
+///
+/// # Arguments
+///
+/// * `session` - The session to show
+/// * `config` - Configuration of the session, for the input history and
+///   contact names to complete
+///
+/// # Errors
+///
+/// - `ChatError::Io` if stdin or stdout fail
+pub async fn run(mut session: ChatSession, config: &Config) -> Result<()> {
+    let completions = Arc::new(Mutex::new(Completions::new(config.contacts_path())));
+    let mut input = LineReader::new(completions.clone(), config.input_history_path())?;
+    loop {
+        tokio::select! {
+            event = session.next_event() => match event {
+                Some(event) => {
+                    completions.lock().unwrap().update(&event);
+                    input.show_event(&event)?;
+                    // Start reading once the session is up, so that the
+                    // terminal is not left in raw mode if it fails to start
+                    if matches!(event, ChatEvent::Listening(_) | ChatEvent::PeerConnected { .. }) {
+                        input.start();
+                    }
+                }
+                None => break,
+            },
+            line = input.lines.recv() => match line.transpose()? {
+                Some(line) => {
+                    let quit = matches!(
+                        CommandHandler::parse_command(line.trim()),
+                        Some(Command::Quit)
+                    );
+                    if !line.is_empty() && session.send_line(line).is_err() {
+                        break;
+                    }
+                    if quit {
+                        input.stop();
+                    } else {
+                        input.resume();
+                    }
+                }
+                // Closing stdin, Ctrl+C and Ctrl+D end the session like /quit
+                None => {
+                    input.stop();
+                    break;
+                }
+            },
+        }
+    }
+    input.finish();
+    Ok(())
+}
+
+/// Whether a typed line is added to the input history.
+///
+/// The history is saved in plain text, so private messages sent with `/msg`
+/// or `/pm` are not recorded.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_p2p_chat::terminal::kept_in_history;
+///
+/// assert!(kept_in_history("/send notes.txt"));
+/// assert!(kept_in_history("hello everyone"));
+/// assert!(!kept_in_history("/msg alice the door code is 1234"));
+/// assert!(!kept_in_history("  /pm bob see you"));
+/// ```
+pub fn kept_in_history(line: &str) -> bool {
+    !matches!(line.split_whitespace().next(), Some("/msg" | "/pm"))
+}
+
+/// Reads lines from stdin on a thread of its own.
+///
+/// A pending read on Tokio's stdin keeps the runtime from shutting down
+/// until Enter is pressed, so quitting would hang. The thread reads one line
+/// at a time and then waits to be resumed, so that it does not hold the
+/// terminal in raw mode while the session ends.
+struct LineReader {
+    lines: mpsc::UnboundedReceiver<io::Result<String>>,
+    resume: std_mpsc::Sender<()>,
+    /// Prints above the line being edited, or directly while no line is
+    /// read.
+    printer: Option<Box<dyn ExternalPrinter + Send>>,
+    /// Whether the thread has been started.
+    started: bool,
+    /// Whether the thread may be waiting for a line.
+    reading: bool,
+    /// Mode of the terminal before the thread put it in raw mode.
+    #[cfg(unix)]
+    terminal_mode: Option<Termios>,
+}
+
+impl LineReader {
+    fn new(completions: Arc<Mutex<Completions>>, history: Option<PathBuf>) -> Result<Self> {
+        let config = rustyline::Config::builder()
+            .max_history_size(MAX_HISTORY)
+            .and_then(|builder| builder.history_ignore_dups(true))
+            .map_err(io::Error::other)?
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor =
+            Editor::<InputHelper, FileHistory>::with_config(config).map_err(io::Error::other)?;
+        editor.set_helper(Some(InputHelper { completions }));
+        if let Some(path) = &history {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            // A missing file just means nothing was typed yet
+            let _ = editor.load_history(path);
+        }
+        #[cfg(unix)]
+        let terminal_mode = termios::tcgetattr(io::stdin()).ok();
+        let printer = if io::stdin().is_terminal() {
+            editor
+                .create_external_printer()
+                .ok()
+                .map(|printer| Box::new(printer) as Box<dyn ExternalPrinter + Send>)
+        } else {
+            None
+        };
+
+        let (sender, lines) = mpsc::unbounded_channel();
+        let (resume, resumed) = std_mpsc::channel();
+        std::thread::spawn(move || {
+            while resumed.recv().is_ok() {
+                let line = match editor.readline(PROMPT) {
+                    Ok(line) => Ok(line),
+                    Err(ReadlineError::Io(e)) => Err(e),
+                    Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+                    Err(e) => Err(io::Error::other(e)),
+                };
+                if let Ok(line) = &line {
+                    if kept_in_history(line) && editor.add_history_entry(line).unwrap_or(false) {
+                        if let Some(path) = &history {
+                            if let Err(e) = editor.append_history(path) {
+                                debug!("Could not save input history: {}", e);
+                            }
+                        }
+                    }
+                }
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(LineReader {
+            lines,
+            resume,
+            printer,
+            started: false,
+            reading: false,
+            #[cfg(unix)]
+            terminal_mode,
+        })
+    }
+
+    /// Reads the first line.
+    fn start(&mut self) {
+        if !self.started {
+            self.started = true;
+            self.reading = true;
+            self.resume();
+        }
+    }
+
+    /// Reads the next line.
+    fn resume(&self) {
+        let _ = self.resume.send(());
+    }
+
+    /// Notes that the thread stopped reading, because the session is ending.
+    fn stop(&mut self) {
+        self.reading = false;
+    }
+
+    /// Gives the terminal back if the session ended while a line was read.
+    ///
+    /// The thread then still waits for input in raw mode, and the terminal
+    /// would stay in it after the process exits.
+    fn finish(self) {
+        #[cfg(unix)]
+        if let (true, Some(mode)) = (self.reading, &self.terminal_mode) {
+            let _ = termios::tcsetattr(io::stdin(), SetArg::TCSANOW, mode);
+            // Leave the line that was being edited
+            println!();
+        }
+    }
+
+    /// Prints an event above the prompt.
+    fn show_event(&mut self, event: &ChatEvent) -> Result<()> {
+        let Some(text) = format_event(event) else {
+            return Ok(());
+        };
+        match &mut self.printer {
+            Some(printer) => printer
+                .print(format!("{}\n", text))
+                .map_err(io::Error::other)?,
+            None => println!("{}", text),
+        }
+        Ok(())
+    }
+}
+
+/// Completes the line being edited with [`Completions`].
+struct InputHelper {
+    completions: Arc<Mutex<Completions>>,
+}
+
+impl Completer for InputHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.completions.lock().unwrap().complete(line, pos))
+    }
+}
+
+impl Highlighter for InputHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        _default: bool,
+    ) -> Cow<'b, str> {
+        Cow::Owned(format!(
+            "{}{}{}{}",
+            Colors::BOLD,
+            Colors::BRIGHT_GREEN,
+            prompt,
+            Colors::RESET
+        ))
+    }
+}
+
+impl Hinter for InputHelper {
+    type Hint = String;
+}
+
+impl Validator for InputHelper {}
+
+impl Helper for InputHelper {}
+
+/// Formats an event for the terminal, or returns `None` if it is not shown.
+pub fn format_event(event: &ChatEvent) -> Option<String> {
+    let text = match event {
+        ChatEvent::Listening(addr) => format!(
+            "{}Listening on: {}{}{}\n{}Type /help for available commands\nType messages and press Enter to send (Ctrl+C to exit){}",
+            Colors::BRIGHT_GREEN,
+            addr,
+            if crate::is_dual_stack(addr) {
+                " (IPv4 and IPv6)"
+            } else {
+                ""
+            },
+            Colors::RESET,
+            Colors::DIM,
+            Colors::RESET
+        ),
+        ChatEvent::LocalIdentity(fingerprint) => {
+            format!("{}Your identity: {}{}", Colors::DIM, fingerprint, Colors::RESET)
+        }
+        ChatEvent::Connecting(address) => {
+            format!("{}Connecting to {}...{}", Colors::YELLOW, address, Colors::RESET)
+        }
+        ChatEvent::PeerConnected {
+            address, outgoing, ..
+        } => format!(
+            "{}✓ {}: {}{}",
+            Colors::BRIGHT_GREEN,
+            if *outgoing {
+                "Connected to peer at"
+            } else {
+                "Peer connected from"
+            },
+            address,
+            Colors::RESET
+        ),
+        ChatEvent::PeerRenamed { previous, name, .. } => format!(
+            "{}{} is now known as {}{}",
+            Colors::CYAN,
+            previous,
+            name,
+            Colors::RESET
+        ),
+        ChatEvent::PeerDisconnected { name, left: true, .. } => {
+            format!("{}{} left the chat{}", Colors::YELLOW, name, Colors::RESET)
+        }
+        ChatEvent::PeerDisconnected { name, .. } => {
+            format!("{}{} disconnected{}", Colors::RED, name, Colors::RESET)
+        }
+        ChatEvent::PeerIdentity {
+            name,
+            fingerprint,
+            contact,
+            ..
+        } => match contact {
+            Some(ContactIdentity::Verified(contact)) => format!(
+                "{}✓ {} is your contact {} ({}){}",
+                Colors::GREEN,
+                name,
+                contact,
+                fingerprint,
+                Colors::RESET
+            ),
+            Some(ContactIdentity::Learned(contact)) => format!(
+                "{}Recorded identity of contact {}: {}{}",
+                Colors::YELLOW,
+                contact,
+                fingerprint,
+                Colors::RESET
+            ),
+            Some(ContactIdentity::Changed {
+                name: contact,
+                expected,
+            }) => format!(
+                "{}{}⚠ WARNING: the identity of contact {} has changed!\n  Expected {}\n  Got      {}\nSomeone may be impersonating this contact.{}",
+                Colors::BOLD,
+                Colors::BRIGHT_RED,
+                contact,
+                expected,
+                fingerprint,
+                Colors::RESET
+            ),
+            None => format!(
+                "{}{} identity: {}{}",
+                Colors::DIM,
+                name,
+                fingerprint,
+                Colors::RESET
+            ),
+        },
+        ChatEvent::EncryptionEnabled { name, .. } => format!(
+            "{}🔒 End-to-end encryption enabled with {}{}",
+            Colors::GREEN,
+            name,
+            Colors::RESET
+        ),
+        ChatEvent::MessageReceived {
+            from,
+            text,
+            encrypted,
+            private,
+        } => {
+            let marker = if *encrypted {
+                format!("{}🔒{}", Colors::GREEN, Colors::RESET)
+            } else {
+                format!("{}(unencrypted){}", Colors::DIM, Colors::RESET)
+            };
+            if *private {
+                format!(
+                    "{}{}[private] {}:{} {} {}",
+                    Colors::BOLD,
+                    Colors::BRIGHT_MAGENTA,
+                    from,
+                    Colors::RESET,
+                    text,
+                    marker
+                )
+            } else {
+                format!(
+                    "{}{}{}:{} {} {}",
+                    Colors::BOLD,
+                    Colors::BRIGHT_CYAN,
+                    from,
+                    Colors::RESET,
+                    text,
+                    marker
+                )
+            }
+        }
+        // What we typed is already on screen
+        ChatEvent::MessageSent { to: None, .. } => return None,
+        ChatEvent::MessageSent {
+            to: Some(to), text, ..
+        } => format!(
+            "{}{}[private → {}]{} {}",
+            Colors::BOLD,
+            Colors::BRIGHT_MAGENTA,
+            to,
+            Colors::RESET,
+            text
+        ),
+        ChatEvent::Transfer { from, event } => format_transfer(from, event)?,
+        // Shown on request by /transfers
+        ChatEvent::TransferProgress { .. } => return None,
+        ChatEvent::PeerDiscovered(peer) => format!(
+            "{}Discovered {} at {} (/connect {} to join){}",
+            Colors::DIM,
+            peer.display_name(),
+            peer.address,
+            peer.nickname.as_deref().unwrap_or(&peer.address.to_string()),
+            Colors::RESET
+        ),
+        ChatEvent::Notice(text) => text.clone(),
+        ChatEvent::Warning(text) => format!("{}{}{}", Colors::YELLOW, text, Colors::RESET),
+        ChatEvent::Error(text) => format!("{}✗ Error: {}{}", Colors::RED, text, Colors::RESET),
+    };
+    Some(text)
+}
+
+fn format_transfer(from: &str, event: &TransferEvent) -> Option<String> {
+    let text = match event {
+        TransferEvent::Started(status) => match status.files {
+            Some(files) => format!(
+                "{}📁 Receiving directory from {}: {} ({} files, {} bytes) [#{}]{}",
+                Colors::YELLOW,
+                from,
+                status.name,
+                files,
+                status.size,
+                status.id,
+                Colors::RESET
+            ),
+            None => format!(
+                "{}📁 Receiving file from {}: {} ({} bytes) [#{}]{}",
+                Colors::YELLOW,
+                from,
+                status.name,
+                status.size,
+                status.id,
+                Colors::RESET
+            ),
+        },
+        TransferEvent::Offered(status) => format!(
+            "{}📁 {} offers {}{} ({} bytes) [#{}] - /accept {} or /reject {}{}",
+            Colors::YELLOW,
+            from,
+            status.name,
+            match status.files {
+                Some(files) => format!(" ({} files)", files),
+                None => String::new(),
+            },
+            status.size,
+            status.id,
+            status.id,
+            status.id,
+            Colors::RESET
+        ),
+        TransferEvent::Completed {
+            path, deduplicated, ..
+        } => format!(
+            "{}✓ File saved to: {}{}{}",
+            Colors::GREEN,
+            path.display(),
+            if *deduplicated {
+                " (already had it, nothing transferred)"
+            } else {
+                ""
+            },
+            Colors::RESET
+        ),
+        TransferEvent::Paused { id, name } => format!(
+            "{}⏸ {} paused transfer #{} ({}){}",
+            Colors::YELLOW,
+            from,
+            id,
+            name,
+            Colors::RESET
+        ),
+        TransferEvent::Resumed { id, name } => format!(
+            "{}▶ {} resumed transfer #{} ({}){}",
+            Colors::YELLOW,
+            from,
+            id,
+            name,
+            Colors::RESET
+        ),
+        TransferEvent::Cancelled { id, name } => format!(
+            "{}✗ {} cancelled transfer #{} ({}){}",
+            Colors::RED,
+            from,
+            id,
+            name,
+            Colors::RESET
+        ),
+        TransferEvent::Failed { id, name, reason } => format!(
+            "{}✗ Transfer #{} ({}) failed: {}{}",
+            Colors::RED,
+            id,
+            name,
+            reason,
+            Colors::RESET
+        ),
+        TransferEvent::Progress | TransferEvent::Ignored => return None,
+    };
+    Some(text)
+}
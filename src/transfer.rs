@@ -0,0 +1,1709 @@
+//! Chunked file and directory transfers with pause, resume and cancel support.
+//!
+//! This module streams files between peers as a sequence of
+//! [`TransferMessage::Chunk`] frames instead of a single in-memory
+//! [`FileInfo`](crate::protocol::FileInfo) message. Every transfer gets a short
+//! numeric ID that users can refer to from the command line.
+//!
+//! # Features
+//!
+//! - Files are read and written incrementally, never fully buffered
+//! - SHA-256 verification of the reassembled file
+//! - Whole directory trees sent as a single transfer with a per-file manifest
+//! - Optional per-chunk compression when both peers support it
+//! - Content already present on the receiving side is copied locally instead
+//!   of being sent again
+//! - `/pause`, `/resume` and `/cancel` from either side of the transfer
+//! - Partial files (`.<name>.<id>.part`) are only created once a transfer
+//!   is accepted and are removed when it is cancelled
+//! - Downloads never replace existing files: they are saved as `name (1).ext`
+//! - Per-transfer progress for the `/transfers` command
+//!
+//! # Transfer Protocol
+//!
+//! 1. **Start**: The sender announces the file name, size and hash, or a
+//!    directory manifest listing every file with its relative path and hash
+//! 2. **Accept**: Unless offers are accepted automatically, the receiver
+//!    waits for the user to [`accept`](TransferManager::accept) or cancel the
+//!    offer. It then asks for the data, or answers `AlreadyHave` if its
+//!    [`ContentCache`] knows a file with the same hash; the transfer then
+//!    completes without sending any data
+//! 3. **Chunks**: File data is streamed in [`CHUNK_SIZE`] pieces; the files of
+//!    a directory are streamed back to back in manifest order
+//! 4. **Complete**: The receiver verifies the hashes and renames the partial
+//!    file (or `*.part` directory) to the first free name
+//! 5. **Control**: `Pause`, `Resume` and `Cancel` may be sent by either peer
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use rust_p2p_chat::transfer::TransferManager;
+//! use std::path::Path;
+//! use std::sync::Arc;
+//! use tokio::sync::mpsc;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let manager = Arc::new(TransferManager::new(100));
+//!     let (tx, _rx) = mpsc::channel(100);
+//!
+//!     // Announce the file (or directory) and stream it to the peer
+//!     let (id, _path) = manager.start_outgoing(Path::new("video.mp4"), &tx).await?;
+//!     manager.stream_outgoing(id, tx.clone()).await?;
+//!
+//!     // Inspect progress
+//!     for status in manager.list().await {
+//!         println!("{}", status);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::compression;
+use crate::content_cache::ContentCache;
+use crate::error::{ChatError, Result};
+use crate::file_transfer::{FileTransfer, CHUNK_SIZE};
+use crate::protocol::{
+    Compression, DirectoryManifest, ManifestEntry, Message, TransferHeader, TransferMessage,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, watch, Mutex};
+use tracing::{debug, warn};
+
+/// Identifier of a file transfer.
+///
+/// IDs are assigned locally and are unique across all peers, so commands can
+/// refer to a transfer by its ID alone. On the wire a transfer is known by the
+/// ID its sender assigned.
+pub type TransferId = u32;
+
+/// Next local transfer ID, shared by the transfer managers of all peers.
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Which way a transfer is flowing relative to the local peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDirection {
+    /// We are sending the file.
+    Outgoing,
+    /// We are receiving the file.
+    Incoming,
+}
+
+/// Lifecycle state of a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferState {
+    /// Announced to the peer, waiting for it to accept the data.
+    Waiting,
+    /// Chunks are flowing.
+    Active,
+    /// Temporarily stopped by either peer.
+    Paused,
+    /// All data was transferred (and verified, for incoming files).
+    Completed,
+    /// Aborted by either peer.
+    Cancelled,
+    /// Aborted because of an error (I/O, hash mismatch, disconnect).
+    Failed,
+}
+
+impl TransferState {
+    /// Returns `true` once the transfer can no longer change state.
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            TransferState::Completed | TransferState::Cancelled | TransferState::Failed
+        )
+    }
+}
+
+impl fmt::Display for TransferState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TransferState::Waiting => "waiting",
+            TransferState::Active => "active",
+            TransferState::Paused => "paused",
+            TransferState::Completed => "completed",
+            TransferState::Cancelled => "cancelled",
+            TransferState::Failed => "failed",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Snapshot of a transfer's progress, as shown by `/transfers`.
+#[derive(Debug, Clone)]
+pub struct TransferStatus {
+    /// Transfer identifier.
+    pub id: TransferId,
+    /// File or directory name.
+    pub name: String,
+    /// Number of files, for directory transfers.
+    pub files: Option<usize>,
+    /// Whether we are sending or receiving.
+    pub direction: TransferDirection,
+    /// Current lifecycle state.
+    pub state: TransferState,
+    /// Total size in bytes.
+    pub size: u64,
+    /// Bytes transferred so far.
+    pub transferred: u64,
+    /// Bytes of chunk data actually sent over the wire, after compression.
+    pub wire_bytes: u64,
+    /// Whether the receiver already had the content, so no data was sent.
+    pub deduplicated: bool,
+    /// When the transfer was registered.
+    pub started_at: Instant,
+}
+
+impl TransferStatus {
+    /// Returns the completion percentage (0.0 - 100.0).
+    pub fn percent(&self) -> f64 {
+        if self.size == 0 {
+            return 100.0;
+        }
+        (self.transferred as f64 / self.size as f64) * 100.0
+    }
+
+    /// Returns how many times smaller the data was on the wire, or `None` if
+    /// no chunk was compressed.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.wire_bytes == 0 || self.wire_bytes >= self.transferred {
+            return None;
+        }
+        Some(self.transferred as f64 / self.wire_bytes as f64)
+    }
+}
+
+impl fmt::Display for TransferStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = match self.direction {
+            TransferDirection::Outgoing => "↑",
+            TransferDirection::Incoming => "↓",
+        };
+        let name = match self.files {
+            Some(count) => format!("{}/ ({} files)", self.name, count),
+            None => self.name.clone(),
+        };
+        write!(
+            f,
+            "#{} {} {} {:.1}% ({} / {} bytes) {}",
+            self.id,
+            arrow,
+            name,
+            self.percent(),
+            self.transferred,
+            self.size,
+            self.state
+        )?;
+        if self.deduplicated {
+            write!(f, " [already present]")?;
+        } else if let Some(ratio) = self.compression_ratio() {
+            write!(f, " [{:.1}x compressed]", ratio)?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of processing a transfer message from the peer.
+///
+/// Returned by [`TransferManager::handle_message`] so the caller can inform
+/// the user without this module printing anything itself.
+#[derive(Debug, Clone)]
+pub enum TransferEvent {
+    /// The peer started sending us a file or directory.
+    Started(TransferStatus),
+    /// The peer offered us a file or directory, which waits for
+    /// [`TransferManager::accept`] or [`TransferManager::cancel`].
+    Offered(TransferStatus),
+    /// A chunk was written; nothing to report.
+    Progress,
+    /// An incoming file or directory was verified and saved.
+    ///
+    /// `deduplicated` is set when the content was copied from a local file
+    /// with the same hash instead of being received.
+    Completed {
+        id: TransferId,
+        name: String,
+        path: PathBuf,
+        deduplicated: bool,
+    },
+    /// The peer paused a transfer.
+    Paused { id: TransferId, name: String },
+    /// The peer resumed a transfer.
+    Resumed { id: TransferId, name: String },
+    /// The peer cancelled a transfer.
+    Cancelled { id: TransferId, name: String },
+    /// A transfer failed on our side and the peer was told to cancel it.
+    Failed {
+        id: TransferId,
+        name: String,
+        reason: String,
+    },
+    /// The message referred to an unknown or finished transfer.
+    Ignored,
+}
+
+/// A file of an incoming transfer and where its data goes.
+struct IncomingEntry {
+    path: PathBuf,
+    size: u64,
+    expected_hash: String,
+}
+
+/// Receiver-side state for a file or directory being written to disk.
+///
+/// A single file is treated as a directory with one entry whose path is the
+/// partial file itself. Entries are filled one after another as chunks
+/// arrive; the hash of each is recorded once its last byte was written.
+struct Incoming {
+    part_path: PathBuf,
+    final_path: PathBuf,
+    is_directory: bool,
+    entries: Vec<IncomingEntry>,
+    current: usize,
+    file: Option<File>,
+    written: u64,
+    hasher: Sha256,
+    hashes: Vec<String>,
+}
+
+impl Incoming {
+    /// Creates the partial file or directory once the transfer is accepted.
+    ///
+    /// Fails rather than writing into anything already at the partial path.
+    async fn open(&mut self) -> Result<()> {
+        if let Some(parent) = self.part_path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                ChatError::FileTransfer(format!("Failed to create download directory: {}", e))
+            })?;
+        }
+        if self.is_directory {
+            fs::create_dir(&self.part_path).await?;
+        } else {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&self.part_path)
+                .await?;
+        }
+        if let Err(e) = self.advance().await {
+            self.remove_partial().await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Renames the verified partial data to the first free name based on
+    /// the offered one, such as `name (1).ext`, and returns that path.
+    async fn finish(&mut self) -> Result<PathBuf> {
+        self.final_path = available_path(&self.final_path, self.is_directory).await;
+        fs::rename(&self.part_path, &self.final_path).await?;
+        Ok(self.final_path.clone())
+    }
+
+    /// Opens the next entry that still expects data, creating any empty
+    /// files (and their parent directories) along the way.
+    async fn advance(&mut self) -> Result<()> {
+        while self.file.is_none() && self.current < self.entries.len() {
+            let entry = &self.entries[self.current];
+            if let Some(parent) = entry.path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            let file = File::create(&entry.path).await?;
+            if entry.size == 0 {
+                self.hashes.push(format!("{:x}", Sha256::digest([])));
+                self.current += 1;
+            } else {
+                self.file = Some(file);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a chunk, splitting it across entries where necessary.
+    async fn write(&mut self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            self.advance().await?;
+            let Some(file) = self.file.as_mut() else {
+                return Err(ChatError::FileTransfer(
+                    "Received more data than announced".to_string(),
+                ));
+            };
+            let remaining = self.entries[self.current].size - self.written;
+            let n = data.len().min(remaining as usize);
+            file.write_all(&data[..n]).await?;
+            self.hasher.update(&data[..n]);
+            self.written += n as u64;
+            data = &data[n..];
+
+            if self.written == self.entries[self.current].size {
+                file.flush().await?;
+                self.file = None;
+                self.hashes
+                    .push(format!("{:x}", self.hasher.finalize_reset()));
+                self.current += 1;
+                self.written = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every entry was received in full and matches its hash.
+    async fn verify(&mut self) -> Result<()> {
+        self.advance().await?;
+        if self.current != self.entries.len() {
+            return Err(ChatError::FileTransfer(
+                "Transfer ended before all data was received".to_string(),
+            ));
+        }
+        for (entry, hash) in self.entries.iter().zip(&self.hashes) {
+            if *hash != entry.expected_hash {
+                if !self.is_directory {
+                    return Err(ChatError::FileTransfer("File hash mismatch".to_string()));
+                }
+                let relative = entry
+                    .path
+                    .strip_prefix(&self.part_path)
+                    .unwrap_or(&entry.path);
+                return Err(ChatError::FileTransfer(format!(
+                    "File hash mismatch: {}",
+                    relative.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes whatever was written so far.
+    async fn discard(mut self) {
+        self.file = None;
+        self.remove_partial().await;
+    }
+
+    async fn remove_partial(&self) {
+        let result = if self.is_directory {
+            fs::remove_dir_all(&self.part_path).await
+        } else {
+            fs::remove_file(&self.part_path).await
+        };
+        if let Err(e) = result {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(
+                    "Failed to remove partial data {}: {}",
+                    self.part_path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// A file to stream for an outgoing transfer.
+#[derive(Clone)]
+struct Source {
+    path: PathBuf,
+    /// Announced size; exactly this many bytes are sent.
+    size: u64,
+    /// Whether chunks of this file are worth compressing.
+    compress: bool,
+}
+
+/// A single tracked transfer.
+struct Transfer {
+    status: TransferStatus,
+    /// ID the sending peer assigned, which messages about the transfer carry.
+    /// Equal to the local ID for outgoing transfers.
+    wire_id: TransferId,
+    control: watch::Sender<TransferState>,
+    /// Files to stream, for outgoing transfers.
+    sources: Vec<Source>,
+    incoming: Option<Incoming>,
+}
+
+/// Tracks all transfers of a connection and implements the chunked protocol.
+///
+/// The manager is shared (via `Arc`) between the task reading from the peer,
+/// the input handler executing commands and the tasks streaming outgoing
+/// files. Outgoing streams observe their transfer's state through a `watch`
+/// channel so that pause, resume and cancel take effect between chunks.
+///
+/// Chunks are compressed once [`set_compression`](Self::set_compression) has
+/// recorded an algorithm negotiated with the peer.
+pub struct TransferManager {
+    transfers: Mutex<HashMap<TransferId, Transfer>>,
+    max_file_size: u64,
+    compression: std::sync::Mutex<Option<Compression>>,
+    media_extensions: Vec<String>,
+    content_cache: Arc<Mutex<ContentCache>>,
+    auto_accept: bool,
+}
+
+impl TransferManager {
+    /// Creates a transfer manager enforcing the given size limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_file_size_mb` - Maximum file size in megabytes, for both directions
+    pub fn new(max_file_size_mb: u64) -> Self {
+        TransferManager {
+            transfers: Mutex::new(HashMap::new()),
+            max_file_size: max_file_size_mb * 1024 * 1024,
+            compression: std::sync::Mutex::new(None),
+            media_extensions: Vec::new(),
+            content_cache: Arc::new(Mutex::new(ContentCache::in_memory())),
+            auto_accept: true,
+        }
+    }
+
+    /// Uses `cache` to find local copies of offered files and records every
+    /// file sent or received in it.
+    pub fn with_content_cache(self, cache: ContentCache) -> Self {
+        self.with_shared_content_cache(Arc::new(Mutex::new(cache)))
+    }
+
+    /// Like [`with_content_cache`](Self::with_content_cache), for a cache
+    /// shared by the transfer managers of several peers.
+    pub fn with_shared_content_cache(mut self, cache: Arc<Mutex<ContentCache>>) -> Self {
+        self.content_cache = cache;
+        self
+    }
+
+    /// Sets the media file types that are sent without compression.
+    ///
+    /// See [`compression::should_compress`] for how the list is applied.
+    pub fn with_media_extensions(mut self, media_extensions: Vec<String>) -> Self {
+        self.media_extensions = media_extensions;
+        self
+    }
+
+    /// Sets whether offers are accepted as soon as they arrive, which is the
+    /// default. Otherwise they are reported as [`TransferEvent::Offered`].
+    pub fn with_auto_accept(mut self, auto_accept: bool) -> Self {
+        self.auto_accept = auto_accept;
+        self
+    }
+
+    /// Records the compression algorithm negotiated with the peer.
+    ///
+    /// Passing `None` sends all subsequent chunks uncompressed.
+    pub fn set_compression(&self, algorithm: Option<Compression>) {
+        *self.compression.lock().unwrap() = algorithm;
+    }
+
+    /// Returns the compression algorithm currently used for outgoing chunks.
+    pub fn compression(&self) -> Option<Compression> {
+        *self.compression.lock().unwrap()
+    }
+
+    /// Registers an outgoing file or directory and announces it to the peer.
+    ///
+    /// Files are announced with a [`TransferMessage::Start`]. Directories are
+    /// walked recursively (symbolic links are skipped) and announced with a
+    /// [`TransferMessage::StartDirectory`] manifest listing each file's
+    /// relative path, size and hash. Call
+    /// [`stream_outgoing`](Self::stream_outgoing) afterwards, usually from a
+    /// spawned task, to send the data.
+    ///
+    /// # Returns
+    ///
+    /// The new transfer ID and the path that is being sent.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the path is missing, neither a regular
+    ///   file nor a directory, or larger than the size limit
+    /// - `ChatError::PeerDisconnected` if the peer channel is closed
+    pub async fn start_outgoing(
+        &self,
+        path: &Path,
+        tx: &mpsc::Sender<Message>,
+    ) -> Result<(TransferId, PathBuf)> {
+        let metadata = fs::metadata(path)
+            .await
+            .map_err(|e| ChatError::FileTransfer(format!("Failed to read file metadata: {}", e)))?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| ChatError::FileTransfer("Invalid file name".to_string()))?
+            .to_string_lossy()
+            .to_string();
+
+        let (announcement, sources, files, size) = if metadata.is_dir() {
+            let mut entries = Vec::new();
+            let mut sources = Vec::new();
+            for relative in collect_files(path).await? {
+                let source = path.join(&relative);
+                let size = fs::metadata(&source).await?.len();
+                let relative = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                entries.push(ManifestEntry {
+                    path: relative,
+                    size,
+                    hash: String::new(),
+                });
+                let compress =
+                    compression::should_compress(&source.to_string_lossy(), &self.media_extensions);
+                sources.push(Source {
+                    path: source,
+                    size,
+                    compress,
+                });
+            }
+            // Check the size before spending time on hashing.
+            let mut manifest = DirectoryManifest {
+                id: 0,
+                name: name.clone(),
+                entries,
+            };
+            self.check_size(manifest.total_size()?)?;
+            for (entry, source) in manifest.entries.iter_mut().zip(&sources) {
+                entry.hash = FileTransfer::hash_file(&source.path).await?;
+                self.remember(&entry.hash, &source.path).await;
+            }
+            let (files, size) = (Some(manifest.entries.len()), manifest.total_size()?);
+            (
+                TransferMessage::StartDirectory(manifest),
+                sources,
+                files,
+                size,
+            )
+        } else if metadata.is_file() {
+            self.check_size(metadata.len())?;
+            let header = TransferHeader {
+                id: 0,
+                name: name.clone(),
+                size: metadata.len(),
+                hash: FileTransfer::hash_file(path).await?,
+            };
+            self.remember(&header.hash, path).await;
+            let sources = vec![Source {
+                path: path.to_path_buf(),
+                size: metadata.len(),
+                compress: compression::should_compress(&name, &self.media_extensions),
+            }];
+            (
+                TransferMessage::Start(header),
+                sources,
+                None,
+                metadata.len(),
+            )
+        } else {
+            return Err(ChatError::FileTransfer(format!(
+                "Not a regular file or directory: {}",
+                path.display()
+            )));
+        };
+
+        let id = Self::allocate_id();
+        let announcement = match announcement {
+            TransferMessage::Start(header) => {
+                TransferMessage::Start(TransferHeader { id, ..header })
+            }
+            TransferMessage::StartDirectory(manifest) => {
+                TransferMessage::StartDirectory(DirectoryManifest { id, ..manifest })
+            }
+            other => other,
+        };
+        let mut status = Self::new_status(id, name, TransferDirection::Outgoing, size);
+        status.files = files;
+        status.state = TransferState::Waiting;
+        self.transfers.lock().await.insert(
+            id,
+            Transfer {
+                status,
+                wire_id: id,
+                control: watch::channel(TransferState::Waiting).0,
+                sources,
+                incoming: None,
+            },
+        );
+
+        debug!("Announcing outgoing transfer {} ({})", id, path.display());
+        tx.send(Message::new_transfer(announcement))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        Ok((id, path.to_path_buf()))
+    }
+
+    /// Streams the chunks of a registered outgoing transfer.
+    ///
+    /// Waits until the peer accepts the transfer, honors pause and cancel
+    /// requests between chunks and finishes with a
+    /// [`TransferMessage::Complete`]. Returns the final state of the transfer,
+    /// which is also `Completed` if the peer already had the content (see
+    /// [`TransferStatus::deduplicated`]).
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Io` if a file cannot be read (the transfer is marked failed
+    ///   and the peer is told to cancel)
+    /// - `ChatError::FileTransfer` if a file changed size since it was announced
+    /// - `ChatError::PeerDisconnected` if the peer channel is closed
+    pub async fn stream_outgoing(
+        &self,
+        id: TransferId,
+        tx: mpsc::Sender<Message>,
+    ) -> Result<TransferState> {
+        let (mut control, sources) = {
+            let transfers = self.transfers.lock().await;
+            match transfers.get(&id) {
+                Some(transfer) => (transfer.control.subscribe(), transfer.sources.clone()),
+                None => return Err(ChatError::FileTransfer(format!("Unknown transfer #{}", id))),
+            }
+        };
+
+        let result = self.send_chunks(id, &sources, &tx, &mut control).await;
+        match result {
+            Ok(state) => Ok(state),
+            Err(e) => {
+                warn!("Outgoing transfer {} failed: {}", id, e);
+                self.set_state(id, TransferState::Failed).await;
+                let cancel = TransferMessage::Cancel {
+                    id,
+                    from_sender: true,
+                };
+                let _ = tx.send(Message::new_transfer(cancel)).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn send_chunks(
+        &self,
+        id: TransferId,
+        sources: &[Source],
+        tx: &mpsc::Sender<Message>,
+        control: &mut watch::Receiver<TransferState>,
+    ) -> Result<TransferState> {
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut filled = 0;
+        let mut offset = 0u64;
+        // Whether the buffered chunk contains data from a compressible file.
+        let mut compress = false;
+
+        for source in sources {
+            // Read exactly the announced size so that the concatenated stream
+            // stays aligned with the manifest.
+            let mut file = File::open(&source.path).await?.take(source.size);
+            let mut read = 0u64;
+            loop {
+                if let Some(finished) = Self::wait_until_active(control).await {
+                    return Ok(finished);
+                }
+                let n = file.read(&mut buffer[filled..]).await?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+                read += n as u64;
+                compress |= source.compress;
+                if filled == CHUNK_SIZE {
+                    self.send_chunk(id, offset, &buffer[..filled], compress, tx)
+                        .await?;
+                    offset += filled as u64;
+                    filled = 0;
+                    compress = false;
+                }
+            }
+            if read != source.size {
+                return Err(ChatError::FileTransfer(format!(
+                    "File changed while sending: {}",
+                    source.path.display()
+                )));
+            }
+        }
+        if filled > 0 {
+            if let Some(finished) = Self::wait_until_active(control).await {
+                return Ok(finished);
+            }
+            self.send_chunk(id, offset, &buffer[..filled], compress, tx)
+                .await?;
+        }
+
+        tx.send(Message::new_transfer(TransferMessage::Complete { id }))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        self.set_state(id, TransferState::Completed).await;
+        Ok(TransferState::Completed)
+    }
+
+    /// Waits while the transfer is paused or not yet accepted. Returns the
+    /// final state if it was cancelled or otherwise finished in the meantime.
+    async fn wait_until_active(
+        control: &mut watch::Receiver<TransferState>,
+    ) -> Option<TransferState> {
+        loop {
+            let state = *control.borrow_and_update();
+            match state {
+                TransferState::Active => return None,
+                TransferState::Waiting | TransferState::Paused => {
+                    if control.changed().await.is_err() {
+                        return Some(TransferState::Cancelled);
+                    }
+                }
+                finished => return Some(finished),
+            }
+        }
+    }
+
+    async fn send_chunk(
+        &self,
+        id: TransferId,
+        offset: u64,
+        data: &[u8],
+        compress: bool,
+        tx: &mpsc::Sender<Message>,
+    ) -> Result<()> {
+        // Only use the compressed form if it actually saves space.
+        let compressed = match self.compression() {
+            Some(algorithm) if compress => {
+                let packed = compression::compress(algorithm, data)?;
+                (packed.len() < data.len()).then_some((algorithm, packed))
+            }
+            _ => None,
+        };
+        let (message, wire_len) = match compressed {
+            Some((algorithm, packed)) => {
+                let wire_len = packed.len();
+                let message = TransferMessage::CompressedChunk {
+                    id,
+                    offset,
+                    algorithm,
+                    data: packed,
+                };
+                (message, wire_len)
+            }
+            None => {
+                let message = TransferMessage::Chunk {
+                    id,
+                    offset,
+                    data: data.to_vec(),
+                };
+                (message, data.len())
+            }
+        };
+
+        tx.send(Message::new_transfer(message))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        self.record_progress(id, offset + data.len() as u64, wire_len as u64)
+            .await;
+        Ok(())
+    }
+
+    /// Processes a transfer message received from the peer.
+    ///
+    /// Offers are accepted right away or once [`accept`](Self::accept) is
+    /// called. Only then are offered files looked up by hash in the content
+    /// cache; if found, the local copy is reused and the peer is told
+    /// `AlreadyHave`, so that unaccepted offers reveal nothing about the files
+    /// we have. Otherwise the incoming file is
+    /// written to `<download_dir>/.<name>.<id>.part` and renamed once the
+    /// hash has been verified. Directories are recreated inside a
+    /// `<download_dir>/.<name>.<id>.part` directory, which is renamed once
+    /// every file has been verified. Nothing is written before the offer is
+    /// accepted, and a download never replaces an existing file or directory:
+    /// it is saved as `<name> (1)` and so on instead. Replies (such as a
+    /// `Cancel` for a rejected file) are queued on `tx`.
+    ///
+    /// Offers get a new local ID, which the returned events carry; the
+    /// sender's ID is only used in messages to the peer.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::PeerDisconnected` if a reply cannot be queued
+    pub async fn handle_message(
+        &self,
+        message: TransferMessage,
+        download_dir: &Path,
+        tx: &mpsc::Sender<Message>,
+    ) -> Result<TransferEvent> {
+        let wire_id = message.transfer_id();
+        let direction = match &message {
+            TransferMessage::Start(_) | TransferMessage::StartDirectory(_) => {
+                return self.handle_offer(message, download_dir, tx).await;
+            }
+            TransferMessage::Accept { .. } | TransferMessage::AlreadyHave { .. } => {
+                TransferDirection::Outgoing
+            }
+            TransferMessage::Chunk { .. }
+            | TransferMessage::CompressedChunk { .. }
+            | TransferMessage::Complete { .. } => TransferDirection::Incoming,
+            TransferMessage::Pause { from_sender, .. }
+            | TransferMessage::Resume { from_sender, .. }
+            | TransferMessage::Cancel { from_sender, .. } => {
+                if *from_sender {
+                    TransferDirection::Incoming
+                } else {
+                    TransferDirection::Outgoing
+                }
+            }
+        };
+        let Some(id) = Self::find(&*self.transfers.lock().await, direction, wire_id) else {
+            return Ok(TransferEvent::Ignored);
+        };
+
+        let result = match message {
+            TransferMessage::Accept { .. } => Ok(self.accepted(id).await),
+            TransferMessage::AlreadyHave { .. } => Ok(self.already_present(id).await),
+            TransferMessage::Chunk { offset, data, .. } => {
+                self.write_chunk(id, offset, &data, data.len()).await
+            }
+            TransferMessage::CompressedChunk {
+                offset,
+                algorithm,
+                data,
+                ..
+            } => match compression::decompress(algorithm, &data, CHUNK_SIZE) {
+                Ok(unpacked) => self.write_chunk(id, offset, &unpacked, data.len()).await,
+                Err(e) => Err(e),
+            },
+            TransferMessage::Complete { .. } => self.finish_incoming(id).await,
+            TransferMessage::Pause { .. } => {
+                Ok(self.remote_state_change(id, TransferState::Paused).await)
+            }
+            TransferMessage::Resume { .. } => {
+                Ok(self.remote_state_change(id, TransferState::Active).await)
+            }
+            TransferMessage::Cancel { .. } => {
+                Ok(self.remote_state_change(id, TransferState::Cancelled).await)
+            }
+            TransferMessage::Start(_) | TransferMessage::StartDirectory(_) => {
+                Ok(TransferEvent::Ignored)
+            }
+        };
+
+        match result {
+            Ok(event) => Ok(event),
+            Err(e) => {
+                let name = self.fail(id).await.unwrap_or_default();
+                let cancel = TransferMessage::Cancel {
+                    id: wire_id,
+                    from_sender: direction == TransferDirection::Outgoing,
+                };
+                tx.send(Message::new_transfer(cancel))
+                    .await
+                    .map_err(|_| ChatError::PeerDisconnected)?;
+                Ok(TransferEvent::Failed {
+                    id,
+                    name,
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Registers a file or directory the peer offered under a new local ID
+    /// and answers the offer: asks for the data, skips it if we have it or
+    /// rejects it. Offers waiting to be accepted are not answered yet.
+    async fn handle_offer(
+        &self,
+        message: TransferMessage,
+        download_dir: &Path,
+        tx: &mpsc::Sender<Message>,
+    ) -> Result<TransferEvent> {
+        let id = Self::allocate_id();
+        let wire_id = message.transfer_id();
+        let (name, result) = match message {
+            TransferMessage::Start(header) => (
+                header.name.clone(),
+                self.begin_incoming(id, header, download_dir).await,
+            ),
+            TransferMessage::StartDirectory(manifest) => (
+                manifest.name.clone(),
+                self.begin_incoming_directory(id, manifest, download_dir)
+                    .await,
+            ),
+            _ => return Ok(TransferEvent::Ignored),
+        };
+
+        let reply = match &result {
+            Ok(TransferEvent::Started(_)) => TransferMessage::Accept { id: wire_id },
+            Ok(TransferEvent::Completed { .. }) => TransferMessage::AlreadyHave { id: wire_id },
+            Ok(_) => return result,
+            Err(_) => TransferMessage::Cancel {
+                id: wire_id,
+                from_sender: false,
+            },
+        };
+        tx.send(Message::new_transfer(reply))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        Ok(result.unwrap_or_else(|e| TransferEvent::Failed {
+            id,
+            name,
+            reason: e.to_string(),
+        }))
+    }
+
+    /// Accepts a file or directory the peer offered, asking it for the data.
+    ///
+    /// If the content cache knows a file with the same content, it is copied
+    /// instead and the peer is told `AlreadyHave`.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the transfer is unknown or not an offer
+    ///   waiting to be accepted
+    /// - `ChatError::Io` if the local copy cannot be copied
+    /// - `ChatError::PeerDisconnected` if the peer channel is closed
+    pub async fn accept(&self, id: TransferId, tx: &mpsc::Sender<Message>) -> Result<String> {
+        let (name, wire_id) = {
+            let mut transfers = self.transfers.lock().await;
+            let transfer = transfers
+                .get_mut(&id)
+                .ok_or_else(|| ChatError::FileTransfer(format!("Unknown transfer #{}", id)))?;
+            if transfer.status.direction != TransferDirection::Incoming
+                || transfer.status.state != TransferState::Waiting
+            {
+                return Err(ChatError::FileTransfer(format!(
+                    "Transfer #{} is not waiting to be accepted",
+                    id
+                )));
+            }
+            let opened = match transfer.incoming.as_mut() {
+                Some(incoming) => incoming.open().await,
+                None => Ok(()),
+            };
+            if let Err(e) = opened {
+                transfer.incoming = None;
+                transfer.status.state = TransferState::Failed;
+                transfer.control.send_replace(TransferState::Failed);
+                let cancel = TransferMessage::Cancel {
+                    id: transfer.wire_id,
+                    from_sender: false,
+                };
+                let _ = tx.send(Message::new_transfer(cancel)).await;
+                return Err(e);
+            }
+            transfer.status.state = TransferState::Active;
+            transfer.control.send_replace(TransferState::Active);
+            (transfer.status.name.clone(), transfer.wire_id)
+        };
+        let reply = match self.reuse_local_copy(id).await {
+            Ok(Some(_)) => TransferMessage::AlreadyHave { id: wire_id },
+            Ok(None) => TransferMessage::Accept { id: wire_id },
+            Err(e) => {
+                let cancel = TransferMessage::Cancel {
+                    id: wire_id,
+                    from_sender: false,
+                };
+                let _ = tx.send(Message::new_transfer(cancel)).await;
+                return Err(e);
+            }
+        };
+        tx.send(Message::new_transfer(reply))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        Ok(name)
+    }
+
+    /// Cancels a transfer locally and notifies the peer. Cancelling an offer
+    /// rejects it.
+    ///
+    /// For incoming transfers the partial file or directory is deleted.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the transfer is unknown or already finished
+    /// - `ChatError::PeerDisconnected` if the peer channel is closed
+    pub async fn cancel(&self, id: TransferId, tx: &mpsc::Sender<Message>) -> Result<String> {
+        let (name, wire_id, from_sender) = self
+            .local_state_change(id, TransferState::Cancelled)
+            .await?;
+        self.discard_partial(id).await;
+        let cancel = TransferMessage::Cancel {
+            id: wire_id,
+            from_sender,
+        };
+        tx.send(Message::new_transfer(cancel))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        Ok(name)
+    }
+
+    /// Cancels every unfinished transfer, such as before disconnecting, and
+    /// notifies the peer of each.
+    ///
+    /// # Returns
+    ///
+    /// The names of the cancelled transfers.
+    pub async fn cancel_all(&self, tx: &mpsc::Sender<Message>) -> Vec<String> {
+        let mut cancelled = Vec::new();
+        for status in self.list().await {
+            if status.state.is_finished() {
+                continue;
+            }
+            match self.cancel(status.id, tx).await {
+                Ok(name) => cancelled.push(name),
+                Err(e) => debug!("Could not cancel transfer #{}: {}", status.id, e),
+            }
+        }
+        cancelled
+    }
+
+    /// Pauses a transfer and asks the peer to do the same.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the transfer is unknown or not active
+    /// - `ChatError::PeerDisconnected` if the peer channel is closed
+    pub async fn pause(&self, id: TransferId, tx: &mpsc::Sender<Message>) -> Result<String> {
+        let (name, wire_id, from_sender) =
+            self.local_state_change(id, TransferState::Paused).await?;
+        let pause = TransferMessage::Pause {
+            id: wire_id,
+            from_sender,
+        };
+        tx.send(Message::new_transfer(pause))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        Ok(name)
+    }
+
+    /// Resumes a paused transfer and asks the peer to do the same.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::FileTransfer` if the transfer is unknown or not paused
+    /// - `ChatError::PeerDisconnected` if the peer channel is closed
+    pub async fn resume(&self, id: TransferId, tx: &mpsc::Sender<Message>) -> Result<String> {
+        let (name, wire_id, from_sender) =
+            self.local_state_change(id, TransferState::Active).await?;
+        let resume = TransferMessage::Resume {
+            id: wire_id,
+            from_sender,
+        };
+        tx.send(Message::new_transfer(resume))
+            .await
+            .map_err(|_| ChatError::PeerDisconnected)?;
+        Ok(name)
+    }
+
+    /// Returns a snapshot of every known transfer, ordered by start time.
+    pub async fn list(&self) -> Vec<TransferStatus> {
+        let transfers = self.transfers.lock().await;
+        let mut list: Vec<TransferStatus> = transfers.values().map(|t| t.status.clone()).collect();
+        list.sort_by_key(|s| s.started_at);
+        list
+    }
+
+    /// Returns the status of a single transfer.
+    pub async fn status(&self, id: TransferId) -> Option<TransferStatus> {
+        let transfers = self.transfers.lock().await;
+        transfers.get(&id).map(|t| t.status.clone())
+    }
+
+    /// Formats all transfers for display by the `/transfers` command.
+    pub async fn summary(&self) -> String {
+        let list = self.list().await;
+        if list.is_empty() {
+            return "No file transfers.".to_string();
+        }
+        let mut result = format!("File transfers ({}):\n", list.len());
+        for status in list {
+            result.push_str(&format!("  {}\n", status));
+        }
+        result
+    }
+
+    fn check_size(&self, size: u64) -> Result<()> {
+        if size > self.max_file_size {
+            return Err(ChatError::FileTransfer(format!(
+                "File too large: {} MB (max: {} MB)",
+                size / 1024 / 1024,
+                self.max_file_size / 1024 / 1024
+            )));
+        }
+        Ok(())
+    }
+
+    fn allocate_id() -> TransferId {
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns the local ID of the transfer the peer knows as `wire_id`,
+    /// preferring an unfinished one if the peer reused the ID.
+    fn find(
+        transfers: &HashMap<TransferId, Transfer>,
+        direction: TransferDirection,
+        wire_id: TransferId,
+    ) -> Option<TransferId> {
+        transfers
+            .values()
+            .filter(|t| t.status.direction == direction && t.wire_id == wire_id)
+            .max_by_key(|t| (!t.status.state.is_finished(), t.status.started_at))
+            .map(|t| t.status.id)
+    }
+
+    fn new_status(
+        id: TransferId,
+        name: String,
+        direction: TransferDirection,
+        size: u64,
+    ) -> TransferStatus {
+        TransferStatus {
+            id,
+            name,
+            files: None,
+            direction,
+            state: TransferState::Active,
+            size,
+            transferred: 0,
+            wire_bytes: 0,
+            deduplicated: false,
+            started_at: Instant::now(),
+        }
+    }
+
+    async fn begin_incoming(
+        &self,
+        id: TransferId,
+        header: TransferHeader,
+        download_dir: &Path,
+    ) -> Result<TransferEvent> {
+        self.check_size(header.size)?;
+        let name = FileTransfer::sanitize_file_name(&header.name)?;
+        let part_path = partial_path(download_dir, &name, id);
+        let incoming = Incoming {
+            final_path: download_dir.join(&name),
+            is_directory: false,
+            entries: vec![IncomingEntry {
+                path: part_path.clone(),
+                size: header.size,
+                expected_hash: header.hash,
+            }],
+            part_path,
+            current: 0,
+            file: None,
+            written: 0,
+            hasher: Sha256::new(),
+            hashes: Vec::new(),
+        };
+        let status = Self::new_status(id, name, TransferDirection::Incoming, header.size);
+        let event = self.register_incoming(status, header.id, incoming).await?;
+        if let TransferEvent::Started(status) = &event {
+            if let Some(completed) = self.reuse_local_copy(status.id).await? {
+                return Ok(completed);
+            }
+        }
+        Ok(event)
+    }
+
+    async fn begin_incoming_directory(
+        &self,
+        id: TransferId,
+        manifest: DirectoryManifest,
+        download_dir: &Path,
+    ) -> Result<TransferEvent> {
+        let size = manifest.total_size()?;
+        self.check_size(size)?;
+        let name = FileTransfer::sanitize_file_name(&manifest.name)?;
+        let part_path = partial_path(download_dir, &name, id);
+        let final_path = download_dir.join(&name);
+
+        let mut seen = HashSet::new();
+        let mut entries = Vec::with_capacity(manifest.entries.len());
+        for entry in manifest.entries {
+            let relative = FileTransfer::sanitize_relative_path(&entry.path)?;
+            if !seen.insert(relative.clone()) {
+                return Err(ChatError::FileTransfer(format!(
+                    "Duplicate path in manifest: {}",
+                    entry.path
+                )));
+            }
+            entries.push(IncomingEntry {
+                path: part_path.join(relative),
+                size: entry.size,
+                expected_hash: entry.hash,
+            });
+        }
+
+        let mut status = Self::new_status(id, name, TransferDirection::Incoming, size);
+        status.files = Some(entries.len());
+        let incoming = Incoming {
+            part_path,
+            final_path,
+            is_directory: true,
+            entries,
+            current: 0,
+            file: None,
+            written: 0,
+            hasher: Sha256::new(),
+            hashes: Vec::new(),
+        };
+        self.register_incoming(status, manifest.id, incoming).await
+    }
+
+    /// Tracks an offer; its partial data is only created on disk once it is
+    /// accepted, here if offers are accepted automatically.
+    async fn register_incoming(
+        &self,
+        mut status: TransferStatus,
+        wire_id: TransferId,
+        mut incoming: Incoming,
+    ) -> Result<TransferEvent> {
+        let mut transfers = self.transfers.lock().await;
+        let duplicate = Self::find(&transfers, TransferDirection::Incoming, wire_id)
+            .is_some_and(|id| !transfers[&id].status.state.is_finished());
+        if duplicate {
+            return Err(ChatError::FileTransfer(format!(
+                "Duplicate transfer ID #{}",
+                wire_id
+            )));
+        }
+
+        if self.auto_accept {
+            incoming.open().await?;
+        } else {
+            status.state = TransferState::Waiting;
+        }
+        transfers.insert(
+            status.id,
+            Transfer {
+                status: status.clone(),
+                wire_id,
+                control: watch::channel(status.state).0,
+                sources: Vec::new(),
+                incoming: Some(incoming),
+            },
+        );
+        Ok(if self.auto_accept {
+            TransferEvent::Started(status)
+        } else {
+            TransferEvent::Offered(status)
+        })
+    }
+
+    async fn write_chunk(
+        &self,
+        id: TransferId,
+        offset: u64,
+        data: &[u8],
+        wire_len: usize,
+    ) -> Result<TransferEvent> {
+        let mut incoming = {
+            let mut transfers = self.transfers.lock().await;
+            let Some(transfer) = transfers.get_mut(&id) else {
+                return Ok(TransferEvent::Ignored);
+            };
+            if transfer.status.state.is_finished() {
+                // Chunks that were in flight when the transfer was cancelled.
+                return Ok(TransferEvent::Ignored);
+            }
+            if transfer.status.state == TransferState::Waiting {
+                // Nothing is written before the offer is accepted.
+                return Ok(TransferEvent::Ignored);
+            }
+            // Written without holding the lock, so that other transfers and
+            // commands are not held up by the disk; chunks of one transfer
+            // arrive one after another.
+            let Some(incoming) = transfer.incoming.take() else {
+                return Ok(TransferEvent::Ignored);
+            };
+            if let Err(e) = check_chunk(&transfer.status, offset, data.len()) {
+                transfer.incoming = Some(incoming);
+                return Err(e);
+            }
+            incoming
+        };
+
+        let written = incoming.write(data).await;
+        let mut transfers = self.transfers.lock().await;
+        match transfers.get_mut(&id) {
+            Some(transfer) if !transfer.status.state.is_finished() => {
+                transfer.incoming = Some(incoming);
+                written?;
+                transfer.status.transferred += data.len() as u64;
+                transfer.status.wire_bytes += wire_len as u64;
+                Ok(TransferEvent::Progress)
+            }
+            _ => {
+                // Cancelled while the chunk was being written.
+                drop(transfers);
+                incoming.discard().await;
+                Ok(TransferEvent::Ignored)
+            }
+        }
+    }
+
+    async fn finish_incoming(&self, id: TransferId) -> Result<TransferEvent> {
+        let mut transfers = self.transfers.lock().await;
+        let Some(transfer) = transfers.get_mut(&id) else {
+            return Ok(TransferEvent::Ignored);
+        };
+        if transfer.status.state.is_finished() || transfer.status.state == TransferState::Waiting {
+            return Ok(TransferEvent::Ignored);
+        }
+        let Some(mut incoming) = transfer.incoming.take() else {
+            return Ok(TransferEvent::Ignored);
+        };
+
+        if let Err(e) = incoming.verify().await {
+            incoming.discard().await;
+            return Err(e);
+        }
+
+        if let Err(e) = incoming.finish().await {
+            incoming.discard().await;
+            return Err(e);
+        }
+        transfer.status.state = TransferState::Completed;
+        transfer.control.send_replace(TransferState::Completed);
+        let name = transfer.status.name.clone();
+        drop(transfers);
+
+        for entry in &incoming.entries {
+            let relative = entry
+                .path
+                .strip_prefix(&incoming.part_path)
+                .unwrap_or(Path::new(""));
+            let path = if relative.as_os_str().is_empty() {
+                incoming.final_path.clone()
+            } else {
+                incoming.final_path.join(relative)
+            };
+            self.remember(&entry.expected_hash, &path).await;
+        }
+        Ok(TransferEvent::Completed {
+            id,
+            name,
+            path: incoming.final_path,
+            deduplicated: false,
+        })
+    }
+
+    /// Looks up a local file with the given content in the content cache.
+    ///
+    /// Every file sent or received is recorded in the cache, so the download
+    /// directory itself is not searched.
+    async fn find_local_copy(&self, hash: &str, size: u64) -> Option<PathBuf> {
+        let path = self.content_cache.lock().await.get(hash).cloned()?;
+        if file_matches(&path, size, hash).await {
+            return Some(path);
+        }
+        // Moved or modified since it was recorded.
+        self.content_cache.lock().await.remove(hash);
+        self.save_content_cache().await;
+        None
+    }
+
+    /// Completes an accepted file offer by copying a local file with the same
+    /// content to the destination instead of receiving it.
+    ///
+    /// # Returns
+    ///
+    /// The completion event, or `None` if there is no local copy and the data
+    /// has to be received.
+    ///
+    /// # Errors
+    ///
+    /// - `ChatError::Io` if the local copy cannot be copied; the transfer is
+    ///   then marked failed
+    async fn reuse_local_copy(&self, id: TransferId) -> Result<Option<TransferEvent>> {
+        let (hash, size) = {
+            let transfers = self.transfers.lock().await;
+            match transfers.get(&id).and_then(|t| t.incoming.as_ref()) {
+                Some(incoming) if !incoming.is_directory => {
+                    let entry = &incoming.entries[0];
+                    (entry.expected_hash.clone(), entry.size)
+                }
+                _ => return Ok(None),
+            }
+        };
+        let Some(existing) = self.find_local_copy(&hash, size).await else {
+            return Ok(None);
+        };
+
+        let mut transfers = self.transfers.lock().await;
+        let Some(transfer) = transfers.get_mut(&id) else {
+            return Ok(None);
+        };
+        if transfer.status.state.is_finished() {
+            // Cancelled while we were looking.
+            return Ok(None);
+        }
+        let Some(mut incoming) = transfer.incoming.take() else {
+            return Ok(None);
+        };
+
+        // Copy over the partial file so the destination never holds
+        // incomplete data.
+        incoming.file = None;
+        let copied = match fs::copy(&existing, &incoming.part_path).await {
+            Ok(_) => incoming.finish().await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = copied {
+            transfer.status.state = TransferState::Failed;
+            transfer.control.send_replace(TransferState::Failed);
+            incoming.discard().await;
+            return Err(e);
+        }
+        debug!("Reused {} for incoming transfer {}", existing.display(), id);
+
+        transfer.status.state = TransferState::Completed;
+        transfer.status.transferred = size;
+        transfer.status.deduplicated = true;
+        transfer.control.send_replace(TransferState::Completed);
+        let name = transfer.status.name.clone();
+        drop(transfers);
+
+        self.remember(&hash, &incoming.final_path).await;
+        Ok(Some(TransferEvent::Completed {
+            id,
+            name,
+            path: incoming.final_path,
+            deduplicated: true,
+        }))
+    }
+
+    /// Records a file in the content cache, logging (but otherwise ignoring)
+    /// failures since the cache is only an optimization.
+    async fn remember(&self, hash: &str, path: &Path) {
+        let path = fs::canonicalize(path)
+            .await
+            .unwrap_or_else(|_| path.to_path_buf());
+        self.content_cache
+            .lock()
+            .await
+            .insert(hash.to_string(), path);
+        self.save_content_cache().await;
+    }
+
+    /// Writes changes to the content cache to disk without holding its lock.
+    async fn save_content_cache(&self) {
+        let snapshot = self.content_cache.lock().await.snapshot();
+        if let Some(snapshot) = snapshot {
+            if let Err(e) = snapshot.save().await {
+                warn!("Failed to update content cache: {}", e);
+            }
+        }
+    }
+
+    /// Handles the peer accepting one of our offers.
+    async fn accepted(&self, id: TransferId) -> TransferEvent {
+        let mut transfers = self.transfers.lock().await;
+        if let Some(transfer) = transfers.get_mut(&id) {
+            if transfer.status.state == TransferState::Waiting {
+                transfer.status.state = TransferState::Active;
+                transfer.control.send_replace(TransferState::Active);
+            }
+        }
+        TransferEvent::Progress
+    }
+
+    /// Handles the peer already having the content of one of our offers.
+    async fn already_present(&self, id: TransferId) -> TransferEvent {
+        let mut transfers = self.transfers.lock().await;
+        if let Some(transfer) = transfers.get_mut(&id) {
+            if !transfer.status.state.is_finished() {
+                transfer.status.state = TransferState::Completed;
+                transfer.status.transferred = transfer.status.size;
+                transfer.status.deduplicated = true;
+                transfer.control.send_replace(TransferState::Completed);
+            }
+        }
+        TransferEvent::Progress
+    }
+
+    async fn remote_state_change(&self, id: TransferId, state: TransferState) -> TransferEvent {
+        let name = {
+            let mut transfers = self.transfers.lock().await;
+            let Some(transfer) = transfers.get_mut(&id) else {
+                return TransferEvent::Ignored;
+            };
+            let current = transfer.status.state;
+            let allowed = match state {
+                // Only a transfer that has been accepted can be paused or
+                // resumed; an offer must wait for the user
+                TransferState::Paused | TransferState::Active => {
+                    matches!(current, TransferState::Active | TransferState::Paused)
+                }
+                _ => !current.is_finished(),
+            };
+            if !allowed {
+                return TransferEvent::Ignored;
+            }
+            transfer.status.state = state;
+            transfer.control.send_replace(state);
+            transfer.status.name.clone()
+        };
+
+        match state {
+            TransferState::Paused => TransferEvent::Paused { id, name },
+            TransferState::Active => TransferEvent::Resumed { id, name },
+            _ => {
+                self.discard_partial(id).await;
+                TransferEvent::Cancelled { id, name }
+            }
+        }
+    }
+
+    /// Changes the state of a transfer on the user's request.
+    ///
+    /// # Returns
+    ///
+    /// The transfer's name, its wire ID and whether we are sending it, to
+    /// address the peer.
+    async fn local_state_change(
+        &self,
+        id: TransferId,
+        state: TransferState,
+    ) -> Result<(String, TransferId, bool)> {
+        let mut transfers = self.transfers.lock().await;
+        let transfer = transfers
+            .get_mut(&id)
+            .ok_or_else(|| ChatError::FileTransfer(format!("Unknown transfer #{}", id)))?;
+
+        let current = transfer.status.state;
+        let allowed = match state {
+            TransferState::Paused => current == TransferState::Active,
+            TransferState::Active => current == TransferState::Paused,
+            _ => !current.is_finished(),
+        };
+        if !allowed {
+            return Err(ChatError::FileTransfer(format!(
+                "Transfer #{} is {}",
+                id, current
+            )));
+        }
+
+        transfer.status.state = state;
+        transfer.control.send_replace(state);
+        Ok((
+            transfer.status.name.clone(),
+            transfer.wire_id,
+            transfer.status.direction == TransferDirection::Outgoing,
+        ))
+    }
+
+    async fn record_progress(&self, id: TransferId, transferred: u64, wire_bytes: u64) {
+        let mut transfers = self.transfers.lock().await;
+        if let Some(transfer) = transfers.get_mut(&id) {
+            transfer.status.transferred = transferred;
+            transfer.status.wire_bytes += wire_bytes;
+        }
+    }
+
+    async fn set_state(&self, id: TransferId, state: TransferState) {
+        let mut transfers = self.transfers.lock().await;
+        if let Some(transfer) = transfers.get_mut(&id) {
+            if !transfer.status.state.is_finished() {
+                transfer.status.state = state;
+                transfer.control.send_replace(state);
+            }
+        }
+    }
+
+    /// Marks a transfer failed and removes its partial file, returning its name.
+    async fn fail(&self, id: TransferId) -> Option<String> {
+        let name = {
+            let mut transfers = self.transfers.lock().await;
+            let transfer = transfers.get_mut(&id)?;
+            transfer.status.state = TransferState::Failed;
+            transfer.control.send_replace(TransferState::Failed);
+            transfer.status.name.clone()
+        };
+        self.discard_partial(id).await;
+        Some(name)
+    }
+
+    /// Closes and deletes the partial data of an incoming transfer, if any.
+    async fn discard_partial(&self, id: TransferId) {
+        let incoming = {
+            let mut transfers = self.transfers.lock().await;
+            transfers.get_mut(&id).and_then(|t| t.incoming.take())
+        };
+        if let Some(incoming) = incoming {
+            incoming.discard().await;
+        }
+    }
+}
+
+/// Checks that a chunk continues an incoming transfer where the previous one
+/// ended and stays within the announced size.
+fn check_chunk(status: &TransferStatus, offset: u64, len: usize) -> Result<()> {
+    if offset != status.transferred {
+        return Err(ChatError::FileTransfer(format!(
+            "Unexpected chunk offset {} (expected {})",
+            offset, status.transferred
+        )));
+    }
+    if offset + len as u64 > status.size {
+        return Err(ChatError::FileTransfer(
+            "Received more data than announced".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Where the partial data of incoming transfer `id` is written: a hidden
+/// name that includes the local ID, so offers with the same name never share
+/// or replace each other's data.
+fn partial_path(download_dir: &Path, name: &str, id: TransferId) -> PathBuf {
+    download_dir.join(format!(".{}.{}.part", name, id))
+}
+
+/// Returns `path` if nothing exists there yet, otherwise the first free
+/// `name (1).ext`, `name (2).ext`, ... next to it. Directory names are
+/// numbered as a whole.
+async fn available_path(path: &Path, is_directory: bool) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (stem, extension) = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) if !is_directory => (
+            stem.to_string_lossy(),
+            format!(".{}", extension.to_string_lossy()),
+        ),
+        _ => (name, String::new()),
+    };
+    let mut candidate = path.to_path_buf();
+    let mut n = 1;
+    while fs::symlink_metadata(&candidate).await.is_ok() {
+        candidate = path.with_file_name(format!("{} ({}){}", stem, n, extension));
+        n += 1;
+    }
+    candidate
+}
+
+/// Lists the regular files below `root` as sorted relative paths.
+///
+/// Symbolic links are skipped so that a transfer never leaves the chosen
+/// directory.
+async fn collect_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let mut entries = fs::read_dir(root.join(&relative)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            let path = relative.join(entry.file_name());
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns `true` if `path` is a regular file of `size` bytes with `hash`.
+async fn file_matches(path: &Path, size: u64, hash: &str) -> bool {
+    match fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() && metadata.len() == size => {}
+        _ => return false,
+    }
+    matches!(FileTransfer::hash_file(path).await, Ok(actual) if actual == hash)
+}
@@ -0,0 +1,859 @@
+//! The full-screen terminal frontend.
+//!
+//! Runs a [`ChatSession`] in the whole terminal window: messages scroll in a
+//! pane of their own above a persistent input line, so incoming messages
+//! never clobber what is being typed. A peer list and a status bar show who
+//! is connected, whether the connection is encrypted and how transfers are
+//! going. Events are formatted by [`terminal::format_event`] and drawn in
+//! the same [`Colors`] as the plain terminal frontend. This is what
+//! [`P2PChat::start_tui`](crate::P2PChat::start_tui) runs.
+//!
+//! # Keys
+//!
+//! - Enter sends the line; ←/→, Home/End, Backspace/Delete edit it
+//! - ↑/↓ go through earlier lines
+//! - Ctrl+U clears the line and Ctrl+W deletes the word before the cursor
+//! - PageUp/PageDown scroll the messages; End on an empty line jumps back
+//!   to the newest
+//! - Ctrl+C leaves the chat like `/quit`
+
+use crate::colors::Colors;
+use crate::contacts::ContactIdentity;
+use crate::error::Result;
+use crate::events::ChatEvent;
+use crate::session::ChatSession;
+use crate::terminal;
+use crate::transfer::{TransferEvent, TransferId, TransferStatus};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Number of lines kept in the message pane.
+const MAX_LINES: usize = 5000;
+
+/// Number of typed lines kept for ↑/↓.
+const MAX_HISTORY: usize = 500;
+
+/// Width of the peer list.
+const PEERS_WIDTH: u16 = 24;
+
+/// Runs the full-screen frontend until the session ends or Ctrl+C is
+/// pressed.
+///
+/// The terminal is restored when it returns, even on errors.
+///
+/// # Errors
+///
+/// - `ChatError::Io` if the terminal cannot be set up or drawn to
+pub async fn run(mut session: ChatSession) -> Result<()> {
+    // Also restores the terminal before a panic message is printed
+    let mut terminal = ratatui::try_init()?;
+    let result = run_app(&mut terminal, &mut session).await;
+    ratatui::try_restore()?;
+    result
+}
+
+async fn run_app(terminal: &mut DefaultTerminal, session: &mut ChatSession) -> Result<()> {
+    let mut app = App::default();
+    let mut inputs = read_events();
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        tokio::select! {
+            event = session.next_event() => match event {
+                Some(event) => {
+                    app.handle_event(event);
+                    // Draw once for everything that is already waiting
+                    while let Some(event) = session.try_next_event() {
+                        app.handle_event(event);
+                    }
+                }
+                None => break,
+            },
+            input = inputs.recv() => match input.transpose()? {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    match app.handle_key(key) {
+                        Action::Send(line) => {
+                            if session.send_line(line).is_err() {
+                                break;
+                            }
+                        }
+                        Action::Quit => break,
+                        Action::None => {}
+                    }
+                }
+                // Anything else, such as a resize, only needs a redraw
+                Some(_) => {}
+                None => break,
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Reads terminal events on a thread of its own, like
+/// [`terminal`]'s stdin reader, until the receiver is dropped.
+fn read_events() -> mpsc::UnboundedReceiver<io::Result<Event>> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        // Polling lets the thread notice that the frontend has gone
+        while !sender.is_closed() {
+            match event::poll(Duration::from_millis(200)) {
+                Ok(false) => {}
+                Ok(true) => {
+                    if sender.send(event::read()).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    break;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+/// What a key press asks the frontend to do.
+enum Action {
+    Send(String),
+    Quit,
+    None,
+}
+
+/// A connected peer in the peer list.
+struct PeerEntry {
+    id: String,
+    name: String,
+    encrypted: bool,
+    /// Whether the peer's key differs from the one saved for its contact.
+    key_changed: bool,
+}
+
+#[derive(Default)]
+struct App {
+    /// Formatted messages, oldest first.
+    lines: VecDeque<Line<'static>>,
+    /// How many rows the message pane is scrolled up from the newest.
+    scroll: usize,
+    /// Rows of messages that fit on screen, as of the last draw.
+    page: usize,
+    input: InputLine,
+    listening_on: Option<SocketAddr>,
+    /// Connected peers, in the order they connected.
+    peers: Vec<PeerEntry>,
+    /// Transfers that have not finished.
+    transfers: BTreeMap<TransferId, TransferStatus>,
+}
+
+impl App {
+    fn handle_event(&mut self, event: ChatEvent) {
+        match &event {
+            ChatEvent::Listening(addr) => self.listening_on = Some(*addr),
+            ChatEvent::PeerConnected { id, address, .. } => self.peers.push(PeerEntry {
+                id: id.clone(),
+                name: address.to_string(),
+                encrypted: false,
+                key_changed: false,
+            }),
+            ChatEvent::PeerRenamed { id, name, .. } => {
+                if let Some(peer) = self.peer_mut(id) {
+                    peer.name = name.clone();
+                }
+            }
+            ChatEvent::PeerDisconnected { id, .. } => self.peers.retain(|peer| peer.id != *id),
+            ChatEvent::PeerIdentity { id, contact, .. } => {
+                let changed = matches!(contact, Some(ContactIdentity::Changed { .. }));
+                if let Some(peer) = self.peer_mut(id) {
+                    peer.key_changed = changed;
+                }
+            }
+            ChatEvent::EncryptionEnabled { id, .. } => {
+                if let Some(peer) = self.peer_mut(id) {
+                    peer.encrypted = true;
+                }
+            }
+            // Text we typed shows up when it has actually been sent
+            ChatEvent::MessageSent {
+                to: None,
+                text,
+                encrypted,
+            } => {
+                let marker = if *encrypted {
+                    format!(" {}🔒{}", Colors::GREEN, Colors::RESET)
+                } else {
+                    String::new()
+                };
+                self.push(&format!(
+                    "{}{}You:{} {}{}",
+                    Colors::BOLD,
+                    Colors::BRIGHT_GREEN,
+                    Colors::RESET,
+                    text,
+                    marker
+                ));
+                return;
+            }
+            ChatEvent::Transfer { event, .. } => match event {
+                TransferEvent::Started(status) | TransferEvent::Offered(status) => {
+                    self.transfers.insert(status.id, status.clone());
+                }
+                TransferEvent::Completed { id, .. }
+                | TransferEvent::Cancelled { id, .. }
+                | TransferEvent::Failed { id, .. } => {
+                    self.transfers.remove(id);
+                }
+                _ => {}
+            },
+            ChatEvent::TransferProgress { status, .. } => {
+                if status.state.is_finished() {
+                    self.transfers.remove(&status.id);
+                } else {
+                    self.transfers.insert(status.id, status.clone());
+                }
+            }
+            _ => {}
+        }
+        if let Some(text) = terminal::format_event(&event) {
+            self.push(&text);
+        }
+    }
+
+    fn peer_mut(&mut self, id: &str) -> Option<&mut PeerEntry> {
+        self.peers.iter_mut().find(|peer| peer.id == id)
+    }
+
+    /// Adds text colored with [`Colors`] to the message pane.
+    fn push(&mut self, text: &str) {
+        let lines = styled_lines(text);
+        // Keep the view where it is while scrolled up
+        if self.scroll > 0 {
+            self.scroll += lines.len();
+        }
+        self.lines.extend(lines);
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => return Action::Quit,
+            KeyCode::Char('u') if ctrl => self.input.clear_before(),
+            KeyCode::Char('w') if ctrl => self.input.delete_word(),
+            KeyCode::Char(c) => self.input.insert(c),
+            KeyCode::Enter => {
+                let line = self.input.submit();
+                if line.trim().is_empty() {
+                    return Action::None;
+                }
+                self.scroll = 0;
+                // Commands are echoed; text appears once it has been sent
+                if line.starts_with('/') {
+                    self.push(&format!("{}> {}{}", Colors::DIM, line, Colors::RESET));
+                }
+                return Action::Send(line);
+            }
+            KeyCode::Backspace => self.input.backspace(),
+            KeyCode::Delete => self.input.delete(),
+            KeyCode::Left => self.input.left(),
+            KeyCode::Right => self.input.right(),
+            KeyCode::Home => self.input.home(),
+            KeyCode::End if self.input.is_empty() => self.scroll = 0,
+            KeyCode::End => self.input.end(),
+            KeyCode::Up => self.input.previous(),
+            KeyCode::Down => self.input.next(),
+            KeyCode::PageUp => self.scroll += self.page.max(1),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(self.page.max(1)),
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status, input] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+        let [messages, peers] =
+            Layout::horizontal([Constraint::Min(20), Constraint::Length(PEERS_WIDTH)]).areas(main);
+
+        self.draw_messages(frame, messages);
+        self.draw_peers(frame, peers);
+        frame.render_widget(Paragraph::new(self.status_line()), status);
+        self.draw_input(frame, input);
+    }
+
+    fn draw_messages(&mut self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = self.lines.iter().cloned().collect();
+        let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
+        // Count wrapped rows so the newest messages sit at the bottom
+        let rows = paragraph.line_count(area.width.saturating_sub(2));
+        self.page = area.height.saturating_sub(2) as usize;
+        let bottom = rows.saturating_sub(self.page);
+        self.scroll = self.scroll.min(bottom);
+        let top = (bottom - self.scroll).min(u16::MAX as usize) as u16;
+
+        let title = if self.scroll > 0 {
+            format!(" Messages (↑{}, End to follow) ", self.scroll)
+        } else {
+            " Messages ".to_string()
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        frame.render_widget(paragraph.block(block).scroll((top, 0)), area);
+    }
+
+    fn draw_peers(&self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .peers
+            .iter()
+            .map(|peer| {
+                let (icon, color) = if peer.key_changed {
+                    ("⚠", Colors::BRIGHT_RED)
+                } else if peer.encrypted {
+                    ("🔒", Colors::GREEN)
+                } else {
+                    ("🔓", Colors::YELLOW)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(icon, theme(color)),
+                    Span::raw(" "),
+                    Span::styled(peer.name.clone(), theme(Colors::BRIGHT_CYAN)),
+                ]))
+            })
+            .collect();
+        let title = format!(" Peers ({}) ", self.peers.len());
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(list, area);
+    }
+
+    /// Returns the status bar: where we listen, who we talk to and how
+    /// transfers are going.
+    fn status_line(&self) -> Line<'static> {
+        let separator = || Span::styled(" │ ", theme(Colors::DIM));
+        let mut spans = vec![match self.listening_on {
+            Some(addr) => Span::styled(format!(" Listening on {}", addr), theme(Colors::GREEN)),
+            None => Span::styled(" Starting...", theme(Colors::YELLOW)),
+        }];
+
+        spans.push(separator());
+        spans.push(match self.peers.as_slice() {
+            [] => Span::styled("No peers", theme(Colors::DIM)),
+            [peer] => Span::styled(peer.name.clone(), theme(Colors::BRIGHT_CYAN)),
+            peers => Span::styled(format!("{} peers", peers.len()), theme(Colors::BRIGHT_CYAN)),
+        });
+        if !self.peers.is_empty() {
+            spans.push(separator());
+            spans.push(if self.peers.iter().any(|peer| peer.key_changed) {
+                Span::styled("⚠ identity changed", theme(Colors::BRIGHT_RED))
+            } else if self.peers.iter().all(|peer| peer.encrypted) {
+                Span::styled("🔒 encrypted", theme(Colors::GREEN))
+            } else {
+                Span::styled("🔓 not encrypted", theme(Colors::YELLOW))
+            });
+        }
+
+        if !self.transfers.is_empty() {
+            let size: u64 = self.transfers.values().map(|status| status.size).sum();
+            let done: u64 = self
+                .transfers
+                .values()
+                .map(|status| status.transferred)
+                .sum();
+            let percent = (done * 100).checked_div(size).unwrap_or(100);
+            spans.push(separator());
+            spans.push(Span::styled(
+                format!("{} transfers {}%", self.transfers.len(), percent),
+                theme(Colors::YELLOW),
+            ));
+        }
+
+        spans.push(separator());
+        spans.push(Span::styled(
+            "PgUp/PgDn scroll · Ctrl+C quit",
+            theme(Colors::GRAY),
+        ));
+        Line::from(spans)
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(Span::styled(
+            " You ",
+            theme(Colors::BRIGHT_GREEN).add_modifier(Modifier::BOLD),
+        ));
+        // Scroll long lines so the cursor stays visible
+        let width = area.width.saturating_sub(2) as usize;
+        let offset = (self.input.cursor + 1).saturating_sub(width);
+        let visible: String = self.input.text.chars().skip(offset).take(width).collect();
+        frame.render_widget(Paragraph::new(visible).block(block), area);
+        frame.set_cursor_position(Position::new(
+            area.x + 1 + (self.input.cursor - offset) as u16,
+            area.y + 1,
+        ));
+    }
+}
+
+/// The line being typed, with a cursor and the lines typed before.
+#[derive(Default)]
+struct InputLine {
+    text: String,
+    /// Cursor position in characters.
+    cursor: usize,
+    /// Earlier lines, oldest first.
+    history: VecDeque<String>,
+    /// The history entry shown, if any, and the line it replaced.
+    browsing: Option<(usize, String)>,
+}
+
+impl InputLine {
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Returns the byte offset of a character position.
+    fn offset(&self, cursor: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.text.len(), |(offset, _)| offset)
+    }
+
+    fn insert(&mut self, c: char) {
+        let offset = self.offset(self.cursor);
+        self.text.insert(offset, c);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let offset = self.offset(self.cursor);
+            self.text.remove(offset);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let offset = self.offset(self.cursor);
+            self.text.remove(offset);
+        }
+    }
+
+    fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    fn end(&mut self) {
+        self.cursor = self.text.chars().count();
+    }
+
+    /// Deletes everything before the cursor.
+    fn clear_before(&mut self) {
+        let offset = self.offset(self.cursor);
+        self.text.drain(..offset);
+        self.cursor = 0;
+    }
+
+    /// Deletes the word before the cursor and the spaces after it.
+    fn delete_word(&mut self) {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut start = self.cursor;
+        while start > 0 && chars[start - 1] == ' ' {
+            start -= 1;
+        }
+        while start > 0 && chars[start - 1] != ' ' {
+            start -= 1;
+        }
+        let (from, to) = (self.offset(start), self.offset(self.cursor));
+        self.text.drain(from..to);
+        self.cursor = start;
+    }
+
+    /// Shows the previous line from the history.
+    fn previous(&mut self) {
+        let index = match &self.browsing {
+            Some((0, _)) => return,
+            Some((index, _)) => index - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                let draft = std::mem::take(&mut self.text);
+                self.browsing = Some((self.history.len(), draft));
+                self.history.len() - 1
+            }
+        };
+        if let Some((browsing, _)) = &mut self.browsing {
+            *browsing = index;
+        }
+        self.text = self.history[index].clone();
+        self.end();
+    }
+
+    /// Shows the next line from the history, or the line being typed
+    /// before the history was opened.
+    fn next(&mut self) {
+        let Some((index, draft)) = self.browsing.take() else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.text = self.history[index + 1].clone();
+            self.browsing = Some((index + 1, draft));
+        } else {
+            self.text = draft;
+        }
+        self.end();
+    }
+
+    /// Takes the line and remembers it for ↑.
+    fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.browsing = None;
+        if !line.trim().is_empty() && self.history.back() != Some(&line) {
+            self.history.push_back(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.pop_front();
+            }
+        }
+        line
+    }
+}
+
+/// Returns the style of a [`Colors`] escape code.
+fn theme(code: &str) -> Style {
+    apply_codes(Style::default(), code)
+}
+
+/// Splits text colored with [`Colors`] escape codes into styled lines.
+///
+/// Styles carry over line breaks, as they do in a terminal.
+fn styled_lines(text: &str) -> Vec<Line<'static>> {
+    let mut style = Style::default();
+    text.split('\n')
+        .map(|line| {
+            let mut spans = Vec::new();
+            let mut rest = line;
+            while let Some(start) = rest.find("\x1b[") {
+                if start > 0 {
+                    spans.push(Span::styled(rest[..start].to_string(), style));
+                }
+                let codes = &rest[start..];
+                let end = codes.find('m').unwrap_or(codes.len() - 1);
+                style = apply_codes(style, &codes[..=end]);
+                rest = &codes[end + 1..];
+            }
+            if !rest.is_empty() {
+                spans.push(Span::styled(rest.to_string(), style));
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Applies an SGR escape sequence such as [`Colors::BRIGHT_GREEN`] to a
+/// style.
+fn apply_codes(style: Style, sequence: &str) -> Style {
+    let codes = sequence.trim_start_matches("\x1b[").trim_end_matches('m');
+    codes.split(';').fold(style, |style, code| match code {
+        "" | "0" => Style::default(),
+        "1" => style.add_modifier(Modifier::BOLD),
+        "2" => style.add_modifier(Modifier::DIM),
+        "3" => style.add_modifier(Modifier::ITALIC),
+        "4" => style.add_modifier(Modifier::UNDERLINED),
+        "31" => style.fg(Color::Red),
+        "32" => style.fg(Color::Green),
+        "33" => style.fg(Color::Yellow),
+        "34" => style.fg(Color::Blue),
+        "35" => style.fg(Color::Magenta),
+        "36" => style.fg(Color::Cyan),
+        "37" => style.fg(Color::White),
+        "90" => style.fg(Color::DarkGray),
+        "91" => style.fg(Color::LightRed),
+        "92" => style.fg(Color::LightGreen),
+        "93" => style.fg(Color::LightYellow),
+        "94" => style.fg(Color::LightBlue),
+        "95" => style.fg(Color::LightMagenta),
+        "96" => style.fg(Color::LightCyan),
+        _ => style,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::{TransferDirection, TransferState};
+    use std::time::Instant;
+
+    fn type_text(input: &mut InputLine, text: &str) {
+        text.chars().for_each(|c| input.insert(c));
+    }
+
+    fn text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn status(id: TransferId, state: TransferState) -> TransferStatus {
+        TransferStatus {
+            id,
+            name: "notes.txt".to_string(),
+            files: None,
+            direction: TransferDirection::Incoming,
+            state,
+            size: 10,
+            transferred: 5,
+            wire_bytes: 5,
+            deduplicated: false,
+            started_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_input_editing() {
+        let mut input = InputLine::default();
+        type_text(&mut input, "héllo");
+        input.left();
+        input.left();
+        input.backspace();
+        assert_eq!(input.text, "hélo");
+        assert_eq!(input.cursor, 2);
+
+        input.delete();
+        input.insert('x');
+        assert_eq!(input.text, "héxo");
+
+        input.home();
+        input.left();
+        input.delete();
+        assert_eq!(input.text, "éxo");
+        input.end();
+        input.right();
+        assert_eq!(input.cursor, 3);
+    }
+
+    #[test]
+    fn test_input_deletes_words() {
+        let mut input = InputLine::default();
+        type_text(&mut input, "/send some  file");
+        input.delete_word();
+        assert_eq!(input.text, "/send some  ");
+        input.delete_word();
+        assert_eq!(input.text, "/send ");
+
+        let mut input = InputLine::default();
+        type_text(&mut input, "hello world");
+        input.left();
+        input.left();
+        input.clear_before();
+        assert_eq!(input.text, "ld");
+        assert_eq!(input.cursor, 0);
+    }
+
+    #[test]
+    fn test_input_history() {
+        let mut input = InputLine::default();
+        for line in ["first", "second", "second", "  "] {
+            type_text(&mut input, line);
+            assert_eq!(input.submit(), line);
+        }
+        // Repeated and blank lines are not remembered
+        assert_eq!(input.history, ["first", "second"]);
+
+        type_text(&mut input, "draft");
+        input.previous();
+        assert_eq!(input.text, "second");
+        input.previous();
+        input.previous();
+        assert_eq!(input.text, "first");
+        assert_eq!(input.cursor, 5);
+
+        // Going past the newest line brings back what was being typed
+        input.next();
+        assert_eq!(input.text, "second");
+        input.next();
+        assert_eq!(input.text, "draft");
+        input.next();
+        assert_eq!(input.text, "draft");
+    }
+
+    #[test]
+    fn test_styled_lines() {
+        let colored = format!(
+            "{}{}You:{} hi\nsecond {}line",
+            Colors::BOLD,
+            Colors::BRIGHT_GREEN,
+            Colors::RESET,
+            Colors::RED
+        );
+        let lines = styled_lines(&colored);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(text(&lines[0]), "You: hi");
+        assert_eq!(
+            lines[0].spans[0].style,
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::LightGreen)
+        );
+        assert_eq!(lines[0].spans[1].style, Style::default());
+        assert_eq!(lines[1].spans[1].style, Style::default().fg(Color::Red));
+
+        // Styles carry over line breaks
+        let lines = styled_lines(&format!("{}one\ntwo", Colors::YELLOW));
+        assert_eq!(lines[1].spans[0].style, Style::default().fg(Color::Yellow));
+    }
+
+    #[test]
+    fn test_apply_codes() {
+        let style = apply_codes(Style::default(), "\x1b[1;4;36m");
+        assert_eq!(
+            style,
+            Style::default()
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+                .fg(Color::Cyan)
+        );
+        assert_eq!(apply_codes(style, "\x1b[0m"), Style::default());
+        // Unknown codes are ignored
+        assert_eq!(apply_codes(style, "\x1b[38m"), style);
+    }
+
+    #[test]
+    fn test_scrollback() {
+        let mut app = App {
+            page: 10,
+            ..App::default()
+        };
+        for i in 0..30 {
+            app.push(&format!("line {}", i));
+        }
+
+        app.handle_key(key(KeyCode::PageUp));
+        assert_eq!(app.scroll, 10);
+        // New messages do not move the view while scrolled up
+        app.push("one\ntwo");
+        assert_eq!(app.scroll, 12);
+        app.handle_key(key(KeyCode::PageDown));
+        assert_eq!(app.scroll, 2);
+
+        // End on an empty line jumps back to the newest
+        app.handle_key(key(KeyCode::End));
+        assert_eq!(app.scroll, 0);
+        app.push("three");
+        assert_eq!(app.scroll, 0);
+
+        for i in 0..MAX_LINES {
+            app.push(&i.to_string());
+        }
+        assert_eq!(app.lines.len(), MAX_LINES);
+        assert_eq!(text(&app.lines[0]), "0");
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut app = App::default();
+        for c in "/help".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.scroll = 3;
+        assert!(
+            matches!(app.handle_key(key(KeyCode::Enter)), Action::Send(line) if line == "/help")
+        );
+        // Commands are echoed and scroll to the newest
+        assert_eq!(app.scroll, 0);
+        assert_eq!(text(app.lines.back().unwrap()), "> /help");
+
+        for c in "hi".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        assert!(matches!(app.handle_key(key(KeyCode::Enter)), Action::Send(line) if line == "hi"));
+        assert_eq!(app.lines.len(), 1);
+        assert!(matches!(app.handle_key(key(KeyCode::Enter)), Action::None));
+
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert!(matches!(app.handle_key(ctrl_c), Action::Quit));
+    }
+
+    #[test]
+    fn test_events_update_peers_and_lines() {
+        let mut app = App::default();
+        let address: SocketAddr = "10.0.0.2:8080".parse().unwrap();
+        app.handle_event(ChatEvent::PeerConnected {
+            id: "1".to_string(),
+            address,
+            outgoing: true,
+        });
+        app.handle_event(ChatEvent::PeerRenamed {
+            id: "1".to_string(),
+            previous: address.to_string(),
+            name: "alice".to_string(),
+        });
+        app.handle_event(ChatEvent::EncryptionEnabled {
+            id: "1".to_string(),
+            name: "alice".to_string(),
+        });
+        assert_eq!(app.peers.len(), 1);
+        assert_eq!(app.peers[0].name, "alice");
+        assert!(app.peers[0].encrypted);
+
+        let lines = app.lines.len();
+        app.handle_event(ChatEvent::MessageSent {
+            to: None,
+            text: "hello".to_string(),
+            encrypted: true,
+        });
+        assert_eq!(app.lines.len(), lines + 1);
+        assert_eq!(text(app.lines.back().unwrap()), "You: hello 🔒");
+
+        app.handle_event(ChatEvent::PeerDisconnected {
+            id: "1".to_string(),
+            name: "alice".to_string(),
+            left: true,
+        });
+        assert!(app.peers.is_empty());
+    }
+
+    #[test]
+    fn test_events_track_unfinished_transfers() {
+        let mut app = App::default();
+        app.handle_event(ChatEvent::Transfer {
+            from: "alice".to_string(),
+            event: TransferEvent::Offered(status(7, TransferState::Waiting)),
+        });
+        assert!(app.transfers.contains_key(&7));
+
+        app.handle_event(ChatEvent::TransferProgress {
+            peer: "alice".to_string(),
+            status: status(7, TransferState::Active),
+        });
+        assert_eq!(app.transfers[&7].state, TransferState::Active);
+
+        app.handle_event(ChatEvent::TransferProgress {
+            peer: "alice".to_string(),
+            status: status(7, TransferState::Completed),
+        });
+        assert!(app.transfers.is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+use rust_p2p_chat::bandwidth::{describe_limit, BandwidthLimits, RateLimiter};
+use rust_p2p_chat::Config;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_unlimited_does_not_wait() {
+    let limiter = RateLimiter::new(None);
+    let start = Instant::now();
+    for _ in 0..100 {
+        limiter.acquire(1024 * 1024).await;
+    }
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_limit_throttles_throughput() {
+    // 64 KB at 256 KB/s should take about 250ms
+    let limiter = RateLimiter::new(Some(256));
+    let start = Instant::now();
+    for _ in 0..4 {
+        limiter.acquire(16 * 1024).await;
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn test_empty_acquire_never_waits() {
+    let limiter = RateLimiter::new(Some(1));
+    limiter.acquire(4096).await;
+
+    // Chat and control messages carry no chunk data
+    let start = Instant::now();
+    limiter.acquire(0).await;
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_set_limit_at_runtime() {
+    let limiter = RateLimiter::new(Some(100));
+    assert_eq!(limiter.limit(), Some(100));
+
+    limiter.set_limit(Some(0));
+    assert_eq!(limiter.limit(), None);
+
+    let start = Instant::now();
+    limiter.acquire(10 * 1024 * 1024).await;
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    limiter.set_limit(Some(2048));
+    assert_eq!(limiter.limit(), Some(2048));
+}
+
+#[test]
+fn test_limits_from_config() {
+    let config = Config {
+        max_upload_kbps: Some(512),
+        max_download_kbps: None,
+        ..Default::default()
+    };
+    let limits = BandwidthLimits::from_config(&config);
+    assert_eq!(limits.upload.limit(), Some(512));
+    assert_eq!(limits.download.limit(), None);
+    assert_eq!(limits.to_string(), "Upload: 512 KB/s, download: unlimited");
+    assert_eq!(describe_limit(Some(64)), "64 KB/s");
+}
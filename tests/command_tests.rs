@@ -1,7 +1,13 @@
 use rust_p2p_chat::commands::CommandHandler;
 use rust_p2p_chat::config::Config;
-use rust_p2p_chat::peer::PeerManager;
+use rust_p2p_chat::encryption::E2EEncryption;
+use rust_p2p_chat::peer::{Peer, PeerInfo, PeerManager};
 use rust_p2p_chat::protocol::Command;
+use rust_p2p_chat::transfer::TransferManager;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::{mpsc, Mutex, Notify};
 
 #[test]
 fn test_command_parsing_help() {
@@ -124,6 +130,149 @@ fn test_command_parsing_stats() {
     ));
 }
 
+#[test]
+fn test_command_parsing_transfer_control() {
+    assert!(matches!(
+        CommandHandler::parse_command("/transfers"),
+        Some(Command::ListTransfers)
+    ));
+    assert_eq!(
+        CommandHandler::parse_command("/cancel 12345"),
+        Some(Command::CancelTransfer(12345))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/pause #12345"),
+        Some(Command::PauseTransfer(12345))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/resume 12345"),
+        Some(Command::ResumeTransfer(12345))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/accept #12345"),
+        Some(Command::AcceptTransfer(12345))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/reject 12345"),
+        Some(Command::CancelTransfer(12345))
+    );
+
+    // Missing or malformed IDs
+    assert!(CommandHandler::parse_command("/cancel").is_none());
+    assert!(CommandHandler::parse_command("/pause abc").is_none());
+    assert!(CommandHandler::parse_command("/resume 1 2").is_none());
+}
+
+#[test]
+fn test_command_parsing_limit() {
+    assert_eq!(
+        CommandHandler::parse_command("/limit"),
+        Some(Command::ShowLimits)
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/limit up 500"),
+        Some(Command::SetUploadLimit(Some(500)))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/limit download 1024"),
+        Some(Command::SetDownloadLimit(Some(1024)))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/limit down off"),
+        Some(Command::SetDownloadLimit(None))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/limit upload 0"),
+        Some(Command::SetUploadLimit(None))
+    );
+
+    assert!(CommandHandler::parse_command("/limit up").is_none());
+    assert!(CommandHandler::parse_command("/limit sideways 10").is_none());
+    assert!(CommandHandler::parse_command("/limit up fast").is_none());
+}
+
+#[test]
+fn test_command_parsing_contacts() {
+    assert_eq!(
+        CommandHandler::parse_command("/contacts"),
+        Some(Command::ListContacts)
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/contacts list"),
+        Some(Command::ListContacts)
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/contacts add alice 10.0.0.1:8080"),
+        Some(Command::AddContact(
+            "alice".to_string(),
+            "10.0.0.1:8080".to_string(),
+            None
+        ))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/contact add bob 10.0.0.2:8080 aaaa:bbbb"),
+        Some(Command::AddContact(
+            "bob".to_string(),
+            "10.0.0.2:8080".to_string(),
+            Some("aaaa:bbbb".to_string())
+        ))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/contacts rm alice"),
+        Some(Command::RemoveContact("alice".to_string()))
+    );
+
+    assert!(CommandHandler::parse_command("/contacts add alice").is_none());
+    assert!(CommandHandler::parse_command("/contacts rm").is_none());
+    assert!(CommandHandler::parse_command("/contacts frobnicate").is_none());
+}
+
+#[test]
+fn test_command_parsing_connect_and_disconnect() {
+    assert_eq!(
+        CommandHandler::parse_command("/connect 192.168.1.100:8080"),
+        Some(Command::Connect("192.168.1.100:8080".to_string()))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/connect alice"),
+        Some(Command::Connect("alice".to_string()))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/disconnect 2"),
+        Some(Command::Disconnect("2".to_string()))
+    );
+
+    assert!(CommandHandler::parse_command("/connect").is_none());
+    assert!(CommandHandler::parse_command("/disconnect").is_none());
+    assert!(CommandHandler::parse_command("/disconnect a b").is_none());
+}
+
+#[test]
+fn test_command_parsing_discover() {
+    assert_eq!(
+        CommandHandler::parse_command("/discover"),
+        Some(Command::Discover)
+    );
+}
+
+#[test]
+fn test_command_parsing_private_message() {
+    assert_eq!(
+        CommandHandler::parse_command("/msg alice see you at 5"),
+        Some(Command::PrivateMessage(
+            "alice".to_string(),
+            "see you at 5".to_string()
+        ))
+    );
+    assert_eq!(
+        CommandHandler::parse_command("/pm 2 hi"),
+        Some(Command::PrivateMessage("2".to_string(), "hi".to_string()))
+    );
+
+    assert!(CommandHandler::parse_command("/msg").is_none());
+    assert!(CommandHandler::parse_command("/msg alice").is_none());
+}
+
 #[test]
 fn test_command_parsing_invalid() {
     assert!(CommandHandler::parse_command("hello").is_none());
@@ -263,6 +412,23 @@ async fn test_command_handler_send_file() {
     assert!(response.contains(&file_path));
 }
 
+#[tokio::test]
+async fn test_command_handler_show_limits() {
+    let config = Config {
+        max_upload_kbps: Some(250),
+        ..Default::default()
+    };
+    let mut handler = CommandHandler::new(config);
+    let peer_manager = PeerManager::new().0;
+
+    let response = handler
+        .handle_command(Command::ShowLimits, &peer_manager)
+        .await
+        .unwrap();
+    assert!(response.contains("Upload: 250 KB/s"));
+    assert!(response.contains("download: unlimited"));
+}
+
 #[tokio::test]
 async fn test_command_handler_stats() {
     let config = Config::default();
@@ -309,3 +475,85 @@ fn test_command_handler_new() {
     let _default_handler = CommandHandler::new(Config::default());
     // Similarly, just ensuring no panic
 }
+
+#[tokio::test]
+async fn test_command_handler_contacts() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut handler =
+        CommandHandler::new(Config::default()).with_contacts_path(dir.path().join("contacts.toml"));
+    let peer_manager = PeerManager::new().0;
+
+    let empty = handler
+        .handle_command(Command::ListContacts, &peer_manager)
+        .await
+        .unwrap();
+    assert!(empty.contains("No contacts"));
+
+    handler
+        .handle_command(
+            Command::AddContact("alice".to_string(), "10.0.0.1:8080".to_string(), None),
+            &peer_manager,
+        )
+        .await
+        .unwrap();
+    let list = handler
+        .handle_command(Command::ListContacts, &peer_manager)
+        .await
+        .unwrap();
+    assert!(list.contains("alice at 10.0.0.1:8080"));
+    assert!(list.contains("unverified"));
+    assert!(list.contains("last seen never"));
+
+    let removed = handler
+        .handle_command(Command::RemoveContact("alice".to_string()), &peer_manager)
+        .await
+        .unwrap();
+    assert!(removed.contains("Removed"));
+}
+
+#[tokio::test]
+async fn test_command_handler_transfers() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("notes.txt");
+    std::fs::write(&source, b"notes").unwrap();
+
+    let mut handler = CommandHandler::new(Config::default());
+    let (peer_manager, _) = PeerManager::new();
+    let (tx, _rx) = mpsc::channel(10);
+    let (bulk_tx, _bulk_rx) = mpsc::channel(10);
+    let peer = Peer {
+        info: PeerInfo {
+            id: "1".to_string(),
+            nickname: Some("Bob".to_string()),
+            address: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            connected_at: SystemTime::now(),
+        },
+        tx: tx.clone(),
+        bulk_tx,
+        encryption: Arc::new(Mutex::new(E2EEncryption::new().unwrap())),
+        transfers: Arc::new(TransferManager::new(10)),
+        shutdown: Arc::new(Notify::new()),
+    };
+    let (id, _) = peer.transfers.start_outgoing(&source, &tx).await.unwrap();
+    peer_manager.add_peer("1".to_string(), peer).await.unwrap();
+
+    let list = handler
+        .handle_command(Command::ListTransfers, &peer_manager)
+        .await
+        .unwrap();
+    assert!(list.contains(&format!("#{}", id)));
+    assert!(list.contains("notes.txt"));
+    assert!(list.contains("(Bob)"));
+
+    let cancelled = handler
+        .handle_command(Command::CancelTransfer(id), &peer_manager)
+        .await
+        .unwrap();
+    assert!(cancelled.contains("Cancelled"));
+
+    // Unknown transfers are an error rather than a made-up reply
+    assert!(handler
+        .handle_command(Command::PauseTransfer(id + 1), &peer_manager)
+        .await
+        .is_err());
+}
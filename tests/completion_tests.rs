@@ -0,0 +1,149 @@
+use rust_p2p_chat::commands::CommandHandler;
+use rust_p2p_chat::completion::Completions;
+use rust_p2p_chat::contacts::Contacts;
+use rust_p2p_chat::events::ChatEvent;
+use std::fs;
+use tempfile::TempDir;
+
+fn complete(completions: &Completions, line: &str) -> (usize, Vec<String>) {
+    completions.complete(line, line.len())
+}
+
+fn renamed(id: &str, name: &str) -> ChatEvent {
+    ChatEvent::PeerRenamed {
+        id: id.to_string(),
+        previous: format!("Peer {}", id),
+        name: name.to_string(),
+    }
+}
+
+#[test]
+fn test_complete_commands() {
+    let completions = Completions::new(None);
+
+    assert_eq!(
+        complete(&completions, "/he"),
+        (0, vec!["/help".to_string()])
+    );
+    assert_eq!(
+        complete(&completions, "/nick"),
+        (0, vec!["/nick".to_string(), "/nickname".to_string()])
+    );
+    assert_eq!(
+        complete(&completions, "/").1.len(),
+        CommandHandler::COMMANDS.len()
+    );
+    assert!(complete(&completions, "/xyz").1.is_empty());
+}
+
+#[test]
+fn test_listed_commands_parse() {
+    // Every completed command must be one the parser knows
+    for command in CommandHandler::COMMANDS {
+        let input = match *command {
+            "connect" | "disconnect" | "accept" | "cancel" | "reject" | "pause" | "resume" => {
+                format!("/{} 1", command)
+            }
+            "nick" | "nickname" | "send" | "file" => format!("/{} x", command),
+            "msg" | "pm" => format!("/{} bob hi", command),
+            _ => format!("/{}", command),
+        };
+        assert!(
+            CommandHandler::parse_command(&input).is_some(),
+            "{} does not parse",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_complete_nicknames() {
+    let mut completions = Completions::new(None);
+    completions.update(&renamed("1", "Bob"));
+    completions.update(&renamed("2", "Bert"));
+    completions.update(&renamed("3", "Alice Smith"));
+
+    assert_eq!(
+        complete(&completions, "/msg b"),
+        (5, vec!["Bert".to_string(), "Bob".to_string()])
+    );
+    assert_eq!(
+        complete(&completions, "/disconnect Bo"),
+        (12, vec!["Bob".to_string()])
+    );
+    // Names are also completed in messages
+    assert_eq!(
+        complete(&completions, "hi bo"),
+        (3, vec!["Bob".to_string()])
+    );
+    // Names with spaces cannot be used in commands
+    assert!(complete(&completions, "/msg Al").1.is_empty());
+    // The message of /msg is not completed
+    assert!(complete(&completions, "/msg Bob b").1.is_empty());
+
+    completions.update(&ChatEvent::PeerDisconnected {
+        id: "1".to_string(),
+        name: "Bob".to_string(),
+        left: true,
+    });
+    assert_eq!(complete(&completions, "/msg b").1, vec!["Bert"]);
+}
+
+#[test]
+fn test_complete_contacts() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("contacts.toml");
+    let completions = Completions::new(Some(path.clone()));
+    assert!(complete(&completions, "/connect a").1.is_empty());
+
+    // Contacts added later are completed too
+    let mut contacts = Contacts::load(&path);
+    contacts.add("alice", "10.0.0.1:8080", None).unwrap();
+    contacts.add("anna", "10.0.0.2:8080", None).unwrap();
+    contacts.add("bob", "10.0.0.3:8080", None).unwrap();
+
+    assert_eq!(
+        complete(&completions, "/connect a"),
+        (9, vec!["alice".to_string(), "anna".to_string()])
+    );
+    assert_eq!(complete(&completions, "/contacts rm b").1, vec!["bob"]);
+    assert_eq!(complete(&completions, "/contacts r").1, vec!["rm"]);
+    assert_eq!(complete(&completions, "/limit d").1, vec!["down"]);
+}
+
+#[test]
+fn test_complete_paths() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("photos")).unwrap();
+    fs::write(dir.path().join("photos").join("beach day.jpg"), b"jpg").unwrap();
+    fs::write(dir.path().join("notes.txt"), b"notes").unwrap();
+    fs::write(dir.path().join(".hidden"), b"hidden").unwrap();
+    let base = format!("{}/", dir.path().display());
+    let completions = Completions::new(None);
+
+    let line = format!("/send {}", base);
+    assert_eq!(
+        complete(&completions, &line),
+        (
+            6,
+            vec![format!("{}notes.txt", base), format!("{}photos/", base)]
+        )
+    );
+    let line = format!("/file {}photos/beach", base);
+    assert_eq!(
+        complete(&completions, &line).1,
+        vec![format!("{}photos/beach day.jpg", base)]
+    );
+    // Paths with spaces are completed as a whole
+    let line = format!("/send {}photos/beach d", base);
+    assert_eq!(complete(&completions, &line).0, 6);
+    assert_eq!(
+        complete(&completions, &line).1,
+        vec![format!("{}photos/beach day.jpg", base)]
+    );
+    let line = format!("/send {}.h", base);
+    assert_eq!(
+        complete(&completions, &line).1,
+        vec![format!("{}.hidden", base)]
+    );
+}
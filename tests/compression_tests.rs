@@ -0,0 +1,51 @@
+use rust_p2p_chat::compression;
+use rust_p2p_chat::protocol::Compression;
+
+#[test]
+fn test_compression_round_trip() {
+    let data = b"The quick brown fox jumps over the lazy dog. ".repeat(500);
+    let packed = compression::compress(Compression::Deflate, &data).unwrap();
+    assert!(packed.len() * 10 < data.len());
+
+    let unpacked = compression::decompress(Compression::Deflate, &packed, data.len()).unwrap();
+    assert_eq!(unpacked, data);
+}
+
+#[test]
+fn test_decompression_is_bounded() {
+    // A small payload that expands far beyond the allowed size
+    let bomb = compression::compress(Compression::Deflate, &vec![0u8; 1024 * 1024]).unwrap();
+    assert!(compression::decompress(Compression::Deflate, &bomb, 64 * 1024).is_err());
+}
+
+#[test]
+fn test_corrupt_data_rejected() {
+    let garbage = vec![0xFFu8; 64];
+    assert!(compression::decompress(Compression::Deflate, &garbage, 1024).is_err());
+}
+
+#[test]
+fn test_negotiation() {
+    assert_eq!(
+        compression::negotiate(&[Compression::Deflate], &[Compression::Deflate]),
+        Some(Compression::Deflate)
+    );
+    assert_eq!(compression::negotiate(&[], &[Compression::Deflate]), None);
+    assert_eq!(compression::negotiate(&[Compression::Deflate], &[]), None);
+}
+
+#[test]
+fn test_should_compress_media() {
+    let media = vec![
+        "jpg".to_string(),
+        "mp4".to_string(),
+        "txt".to_string(),
+        "wav".to_string(),
+    ];
+    assert!(!compression::should_compress("holiday.mp4", &media));
+    assert!(!compression::should_compress("PHOTO.JPG", &media));
+    assert!(compression::should_compress("readme.txt", &media));
+    assert!(compression::should_compress("sound.wav", &media));
+    assert!(compression::should_compress("main.rs", &media));
+    assert!(compression::should_compress("Makefile", &media));
+}
@@ -233,7 +233,7 @@ async fn test_concurrent_message_processing() {
         let mut stream = TcpStream::connect(addr).await.unwrap();
 
         // Send different types of messages concurrently
-        let messages = vec![
+        let messages = [
             "TEXT: Hello World\n",
             "FILE: image.png\n",
             "CMD: /help\n",
@@ -263,7 +263,7 @@ async fn test_concurrent_message_processing() {
 
     // Verify messages were processed
     let messages = processed_messages.lock().await;
-    assert!(messages.len() > 0);
+    assert!(!messages.is_empty());
 }
 
 #[tokio::test]
@@ -1,4 +1,5 @@
-use rust_p2p_chat::config::Config;
+use rust_p2p_chat::config::{Config, PortRange};
+use rust_p2p_chat::ChatError;
 use std::path::PathBuf;
 use tempfile::tempdir;
 
@@ -11,14 +12,21 @@ fn test_config_default_values() {
     assert_eq!(config.heartbeat_interval_secs, 30);
     assert_eq!(config.reconnect_attempts, 3);
     assert_eq!(config.reconnect_delay_secs, 5);
-    assert_eq!(config.enable_encryption, true);
+    assert_eq!(config.connect_timeout_secs, 10);
+    assert_eq!(config.handshake_timeout_secs, 15);
+    assert!(config.enable_encryption);
     assert_eq!(config.log_level, "info");
-    assert_eq!(config.save_history, true);
+    assert!(config.save_history);
     assert_eq!(config.max_file_size_mb, 100);
-    assert_eq!(config.auto_open_media, true);
+    assert!(config.auto_accept_transfers);
+    assert!(config.desktop_notifications);
+    assert!(config.auto_open_media);
     assert!(config.nickname.is_none());
     assert!(config.history_file.is_none());
     assert!(config.download_dir.is_none());
+    assert!(config.max_upload_kbps.is_none());
+    assert!(config.max_download_kbps.is_none());
+    assert!(!config.enable_discovery);
 
     // Check default media extensions
     let expected_extensions = vec![
@@ -36,32 +44,104 @@ fn test_config_with_custom_values() {
     let config = Config {
         nickname: Some("TestUser".to_string()),
         default_port: 9090,
+        bind_address: "::".to_string(),
         buffer_size: 4096,
         heartbeat_interval_secs: 60,
         reconnect_attempts: 5,
         reconnect_delay_secs: 10,
+        connect_timeout_secs: 3,
+        handshake_timeout_secs: 4,
         enable_encryption: false,
+        enable_compression: false,
         log_level: "debug".to_string(),
         save_history: false,
         history_file: Some(PathBuf::from("/tmp/test_history.json")),
         max_file_size_mb: 50,
+        auto_accept_transfers: true,
+        max_upload_kbps: Some(256),
+        max_download_kbps: None,
+        enable_discovery: true,
+        discovery_address: "239.255.1.1:9999".to_string(),
         download_dir: Some(PathBuf::from("/tmp/downloads")),
         auto_open_media: false,
         media_extensions: vec!["txt".to_string(), "pdf".to_string()],
+        desktop_notifications: false,
     };
 
     assert_eq!(config.nickname, Some("TestUser".to_string()));
     assert_eq!(config.default_port, 9090);
+    assert_eq!(config.bind_address, "::");
     assert_eq!(config.buffer_size, 4096);
     assert_eq!(config.heartbeat_interval_secs, 60);
     assert_eq!(config.reconnect_attempts, 5);
     assert_eq!(config.reconnect_delay_secs, 10);
-    assert_eq!(config.enable_encryption, false);
+    assert_eq!(config.connect_timeout_secs, 3);
+    assert_eq!(config.handshake_timeout_secs, 4);
+    assert!(!config.enable_encryption);
     assert_eq!(config.log_level, "debug");
-    assert_eq!(config.save_history, false);
+    assert!(!config.save_history);
     assert_eq!(config.max_file_size_mb, 50);
-    assert_eq!(config.auto_open_media, false);
+    assert_eq!(config.max_upload_kbps, Some(256));
+    assert_eq!(config.max_download_kbps, None);
+    assert!(config.enable_discovery);
+    assert_eq!(config.discovery_address, "239.255.1.1:9999");
+    assert!(!config.auto_open_media);
     assert_eq!(config.media_extensions, vec!["txt", "pdf"]);
+    assert!(!config.desktop_notifications);
+}
+
+#[test]
+fn test_config_listen_address() {
+    let mut config = Config::default();
+    assert_eq!(config.bind_address, "0.0.0.0");
+    assert_eq!(
+        config.listen_address(8080).unwrap().to_string(),
+        "0.0.0.0:8080"
+    );
+
+    config.bind_address = "::".to_string();
+    assert_eq!(
+        config.listen_address(8080).unwrap().to_string(),
+        "[::]:8080"
+    );
+
+    config.bind_address = "[fe80::1]".to_string();
+    assert_eq!(
+        config.listen_address(9000).unwrap().to_string(),
+        "[fe80::1]:9000"
+    );
+
+    config.bind_address = "192.168.1.5".to_string();
+    assert_eq!(
+        config.listen_address(9000).unwrap().to_string(),
+        "192.168.1.5:9000"
+    );
+
+    config.bind_address = "localhost:8080".to_string();
+    assert!(config.listen_address(8080).is_err());
+}
+
+#[test]
+fn test_port_range_parsing() {
+    let range: PortRange = "8080-8090".parse().unwrap();
+    assert_eq!(
+        range,
+        PortRange {
+            start: 8080,
+            end: 8090
+        }
+    );
+    assert_eq!(range.ports().next(), Some(8080));
+    assert_eq!(range.ports().last(), Some(8090));
+    assert_eq!(range.to_string(), "8080-8090");
+
+    let single: PortRange = "9000".parse().unwrap();
+    assert_eq!(single.ports().count(), 1);
+
+    assert!("8090-8080".parse::<PortRange>().is_err());
+    assert!("0-10".parse::<PortRange>().is_err());
+    assert!("8080-".parse::<PortRange>().is_err());
+    assert!("eighty".parse::<PortRange>().is_err());
 }
 
 #[test]
@@ -70,7 +150,7 @@ fn test_config_download_path_default() {
     let download_path = config.download_path();
 
     // Should return a valid path (either Downloads folder or current directory)
-    assert!(download_path.exists() || download_path == PathBuf::from("."));
+    assert!(download_path.exists() || download_path == std::path::Path::new("."));
 }
 
 #[test]
@@ -201,3 +281,77 @@ fn test_config_edge_cases() {
     assert_eq!(config.log_level, "");
     assert_eq!(config.nickname, Some("".to_string()));
 }
+
+#[test]
+fn test_config_without_new_fields_loads() {
+    // Config files written by older versions lack `enable_compression`
+    let mut value = toml::Value::try_from(Config::default()).unwrap();
+    value.as_table_mut().unwrap().remove("enable_compression");
+    let contents = toml::to_string(&value).unwrap();
+
+    let config: Config = toml::from_str(&contents).unwrap();
+    assert!(config.enable_compression);
+}
+
+#[test]
+fn test_config_validation() {
+    assert!(Config::default().validate().is_ok());
+
+    let invalid = [
+        Config {
+            nickname: Some("   ".to_string()),
+            ..Default::default()
+        },
+        Config {
+            nickname: Some("x".repeat(33)),
+            ..Default::default()
+        },
+        Config {
+            bind_address: "localhost".to_string(),
+            ..Default::default()
+        },
+        Config {
+            buffer_size: 10,
+            ..Default::default()
+        },
+        Config {
+            heartbeat_interval_secs: 0,
+            ..Default::default()
+        },
+        Config {
+            log_level: "verbose".to_string(),
+            ..Default::default()
+        },
+        Config {
+            max_file_size_mb: 0,
+            ..Default::default()
+        },
+        Config {
+            max_upload_kbps: Some(0),
+            ..Default::default()
+        },
+        Config {
+            discovery_address: "239.255.77.77".to_string(),
+            ..Default::default()
+        },
+        Config {
+            media_extensions: vec![".jpg".to_string()],
+            ..Default::default()
+        },
+    ];
+    for config in invalid {
+        assert!(
+            matches!(config.validate(), Err(ChatError::Configuration(_))),
+            "{:?} should be invalid",
+            config
+        );
+    }
+
+    // No reconnects need no delay
+    let config = Config {
+        reconnect_attempts: 0,
+        reconnect_delay_secs: 0,
+        ..Default::default()
+    };
+    assert!(config.validate().is_ok());
+}
@@ -0,0 +1,152 @@
+use rust_p2p_chat::contacts::{ContactIdentity, Contacts};
+use tempfile::TempDir;
+
+#[test]
+fn test_add_and_resolve_contact() {
+    let mut contacts = Contacts::in_memory();
+    contacts.add("alice", "192.168.1.100:8080", None).unwrap();
+
+    assert_eq!(contacts.len(), 1);
+    assert_eq!(contacts.resolve("alice"), "192.168.1.100:8080");
+    assert_eq!(contacts.resolve("ALICE"), "192.168.1.100:8080");
+    // Anything else is taken to be an address
+    assert_eq!(contacts.resolve("10.0.0.5:9000"), "10.0.0.5:9000");
+}
+
+#[test]
+fn test_contacts_persist() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("contacts.toml");
+
+    let mut contacts = Contacts::load(&path);
+    contacts
+        .add("bob", "10.0.0.2:8080", Some("aaaa:bbbb".to_string()))
+        .unwrap();
+    contacts.add("carol", "10.0.0.3:8080", None).unwrap();
+
+    let reloaded = Contacts::load(&path);
+    assert_eq!(reloaded.len(), 2);
+    let bob = reloaded.get("bob").unwrap();
+    assert_eq!(bob.address, "10.0.0.2:8080");
+    assert_eq!(bob.fingerprint.as_deref(), Some("aaaa:bbbb"));
+    let names: Vec<&String> = reloaded.iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["bob", "carol"]);
+}
+
+#[test]
+fn test_update_keeps_fingerprint() {
+    let mut contacts = Contacts::in_memory();
+    contacts
+        .add("bob", "10.0.0.2:8080", Some("aaaa:bbbb".to_string()))
+        .unwrap();
+    contacts.add("Bob", "10.0.0.9:8080", None).unwrap();
+
+    assert_eq!(contacts.len(), 1);
+    let bob = contacts.get("bob").unwrap();
+    assert_eq!(bob.address, "10.0.0.9:8080");
+    assert_eq!(bob.fingerprint.as_deref(), Some("aaaa:bbbb"));
+}
+
+#[test]
+fn test_remove_contact() {
+    let mut contacts = Contacts::in_memory();
+    contacts.add("alice", "192.168.1.100:8080", None).unwrap();
+
+    assert!(contacts.remove("Alice").unwrap());
+    assert!(!contacts.remove("alice").unwrap());
+    assert!(contacts.is_empty());
+}
+
+#[test]
+fn test_invalid_contacts_rejected() {
+    let mut contacts = Contacts::in_memory();
+    assert!(contacts.add("", "10.0.0.1:8080", None).is_err());
+    assert!(contacts.add("two words", "10.0.0.1:8080", None).is_err());
+    assert!(contacts.add("dave", "", None).is_err());
+    assert!(contacts.is_empty());
+}
+
+#[test]
+fn test_corrupt_contacts_file_is_ignored() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("contacts.toml");
+    std::fs::write(&path, "this is [not toml").unwrap();
+
+    let contacts = Contacts::load(&path);
+    assert!(contacts.is_empty());
+}
+
+#[test]
+fn test_record_seen_trusts_first_fingerprint() {
+    let mut contacts = Contacts::in_memory();
+    contacts.add("alice", "10.0.0.1:8080", None).unwrap();
+
+    // Unknown peers are not contacts
+    assert_eq!(
+        contacts
+            .record_seen(Some("10.0.0.7:5555"), "1111:2222")
+            .unwrap(),
+        None
+    );
+
+    assert_eq!(
+        contacts
+            .record_seen(Some("10.0.0.1:8080"), "1111:2222")
+            .unwrap(),
+        Some(ContactIdentity::Learned("alice".to_string()))
+    );
+    let alice = contacts.get("alice").unwrap();
+    assert_eq!(alice.fingerprint.as_deref(), Some("1111:2222"));
+    assert!(alice.last_seen.is_some());
+
+    // Recognised by fingerprint, even from another address
+    assert_eq!(
+        contacts
+            .record_seen(Some("10.0.0.1:41234"), "1111:2222")
+            .unwrap(),
+        Some(ContactIdentity::Verified("alice".to_string()))
+    );
+    assert_eq!(
+        contacts.record_seen(None, "1111:2222").unwrap(),
+        Some(ContactIdentity::Verified("alice".to_string()))
+    );
+}
+
+#[test]
+fn test_record_seen_learns_only_from_dialed_peers() {
+    let mut contacts = Contacts::in_memory();
+    contacts.add("alice", "10.0.0.1:8080", None).unwrap();
+
+    // A peer that connected to us is not matched by address
+    assert_eq!(contacts.record_seen(None, "1111:2222").unwrap(), None);
+    assert!(contacts.get("alice").unwrap().fingerprint.is_none());
+
+    assert_eq!(
+        contacts
+            .record_seen(Some("10.0.0.1:8080"), "1111:2222")
+            .unwrap(),
+        Some(ContactIdentity::Learned("alice".to_string()))
+    );
+}
+
+#[test]
+fn test_record_seen_detects_changed_key() {
+    let mut contacts = Contacts::in_memory();
+    contacts
+        .add("alice", "10.0.0.1:8080", Some("1111:2222".to_string()))
+        .unwrap();
+
+    assert_eq!(
+        contacts
+            .record_seen(Some("10.0.0.1:8080"), "9999:8888")
+            .unwrap(),
+        Some(ContactIdentity::Changed {
+            name: "alice".to_string(),
+            expected: "1111:2222".to_string(),
+        })
+    );
+    // The recorded fingerprint is not replaced
+    let alice = contacts.get("alice").unwrap();
+    assert_eq!(alice.fingerprint.as_deref(), Some("1111:2222"));
+    assert!(alice.last_seen.is_none());
+}
@@ -0,0 +1,132 @@
+use rust_p2p_chat::discovery::{Announcement, Discovery, PEER_TIMEOUT};
+use rust_p2p_chat::{ChatError, Config};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+fn announcement(nickname: Option<&str>, port: u16, fingerprint: &str) -> Announcement {
+    Announcement {
+        nickname: nickname.map(str::to_string),
+        port,
+        fingerprint: fingerprint.to_string(),
+    }
+}
+
+fn loopback() -> SocketAddr {
+    "127.0.0.1:0".parse().unwrap()
+}
+
+#[test]
+fn test_announcement_roundtrip() {
+    let original = announcement(Some("Alice"), 8080, "aaaa");
+    let packet = original.encode().unwrap();
+
+    assert_eq!(Announcement::decode(&packet), Some(original));
+    assert_eq!(Announcement::decode(b"hello"), None);
+    assert_eq!(Announcement::decode(&packet[..4]), None);
+}
+
+#[tokio::test]
+async fn test_discover_peer_on_loopback() {
+    let bob = Discovery::bind(loopback(), loopback(), announcement(None, 9001, "bbbb")).unwrap();
+    let alice = Discovery::bind(
+        loopback(),
+        bob.local_addr().unwrap(),
+        announcement(Some("Alice"), 9000, "aaaa"),
+    )
+    .unwrap();
+
+    alice.announce().await.unwrap();
+    let (peer, is_new) = timeout(Duration::from_secs(5), bob.receive())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert!(is_new);
+    assert_eq!(peer.nickname.as_deref(), Some("Alice"));
+    assert_eq!(peer.address, "127.0.0.1:9000".parse().unwrap());
+    assert_eq!(peer.fingerprint, "aaaa");
+
+    // A repeated announcement refreshes the entry instead of adding one
+    alice.announce().await.unwrap();
+    let (_, is_new) = timeout(Duration::from_secs(5), bob.receive())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!is_new);
+    assert_eq!(bob.peers().len(), 1);
+
+    assert_eq!(bob.find("alice").unwrap().address.port(), 9000);
+    assert_eq!(bob.find("aaaa").unwrap().display_name(), "Alice");
+    assert!(bob.find("carol").is_none());
+    assert!(PEER_TIMEOUT > Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_nickname_change_is_announced() {
+    let bob = Discovery::bind(loopback(), loopback(), announcement(None, 9001, "bbbb")).unwrap();
+    let alice = Discovery::bind(
+        loopback(),
+        bob.local_addr().unwrap(),
+        announcement(None, 9000, "aaaa"),
+    )
+    .unwrap();
+
+    alice.announce().await.unwrap();
+    let (peer, _) = timeout(Duration::from_secs(5), bob.receive())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(peer.display_name(), "127.0.0.1:9000");
+
+    alice.set_nickname(Some("Alice".to_string()));
+    alice.announce().await.unwrap();
+    let (peer, _) = timeout(Duration::from_secs(5), bob.receive())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(peer.display_name(), "Alice");
+}
+
+#[tokio::test]
+async fn test_ignores_own_and_foreign_packets() {
+    let node = Discovery::bind(loopback(), loopback(), announcement(None, 9000, "aaaa")).unwrap();
+    let target = node.local_addr().unwrap();
+    let sender = UdpSocket::bind(loopback()).await.unwrap();
+
+    let own = announcement(Some("Me"), 9000, "aaaa").encode().unwrap();
+    sender.send_to(&own, target).await.unwrap();
+    sender
+        .send_to(b"not an announcement", target)
+        .await
+        .unwrap();
+    let nickname = format!("Eve\u{7}{}", "x".repeat(100));
+    let sneaky = announcement(Some(&nickname), 9002, "eeee");
+    sender
+        .send_to(&sneaky.encode().unwrap(), target)
+        .await
+        .unwrap();
+
+    let (peer, _) = timeout(Duration::from_secs(5), node.receive())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(peer.fingerprint, "eeee");
+    // Control characters are dropped and long nicknames shortened
+    let nickname = peer.nickname.unwrap();
+    assert!(nickname.starts_with("Evex"));
+    assert_eq!(nickname.len(), 32);
+    assert_eq!(node.peers().len(), 1);
+}
+
+#[test]
+fn test_invalid_discovery_address() {
+    let config = Config {
+        enable_discovery: true,
+        discovery_address: "not an address".to_string(),
+        ..Default::default()
+    };
+    let result = Discovery::from_config(&config, announcement(None, 8080, "aaaa"));
+    assert!(matches!(result, Err(ChatError::Configuration(_))));
+}
@@ -1,5 +1,5 @@
 use base64::{engine::general_purpose, Engine as _};
-use rust_p2p_chat::encryption::{E2EEncryption, TlsConfig};
+use rust_p2p_chat::encryption::{fingerprint, safety_number, E2EEncryption, TlsConfig};
 use rust_p2p_chat::protocol::{EncryptionMessage, Message, MessageType};
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -71,7 +71,7 @@ async fn test_e2e_encryption_set_peer_public_key_invalid() {
     let mut encryption = E2EEncryption::new().unwrap();
 
     // Try to set invalid base64
-    let result = encryption.set_peer_public_key(&"invalid_base64!@#".to_string());
+    let result = encryption.set_peer_public_key("invalid_base64!@#");
     assert!(result.is_err());
 
     // Try to set valid base64 but invalid RSA key
@@ -85,11 +85,11 @@ async fn test_e2e_encryption_set_peer_public_key_empty() {
     let mut encryption = E2EEncryption::new().unwrap();
 
     // Try to set empty key
-    let result = encryption.set_peer_public_key(&"".to_string());
+    let result = encryption.set_peer_public_key("");
     assert!(result.is_err());
 
     // Try to set whitespace only
-    let result = encryption.set_peer_public_key(&"   ".to_string());
+    let result = encryption.set_peer_public_key("   ");
     assert!(result.is_err());
 }
 
@@ -148,7 +148,7 @@ async fn test_e2e_encryption_set_shared_key_invalid() {
     let mut encryption = E2EEncryption::new().unwrap();
 
     // Try to set invalid base64
-    let result = encryption.set_shared_key(&"invalid_base64!@#".to_string());
+    let result = encryption.set_shared_key("invalid_base64!@#");
     assert!(result.is_err());
 
     // Try to set valid base64 but can't decrypt (no private key match)
@@ -162,11 +162,11 @@ async fn test_e2e_encryption_set_shared_key_empty() {
     let mut encryption = E2EEncryption::new().unwrap();
 
     // Try to set empty key
-    let result = encryption.set_shared_key(&"".to_string());
+    let result = encryption.set_shared_key("");
     assert!(result.is_err());
 
     // Try to set whitespace only
-    let result = encryption.set_shared_key(&"   ".to_string());
+    let result = encryption.set_shared_key("   ");
     assert!(result.is_err());
 }
 
@@ -591,3 +591,187 @@ fn test_message_with_encryption() {
         _ => panic!("Expected EncryptedText variant"),
     }
 }
+
+#[test]
+fn test_identity_key_is_reused() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("keys").join("identity.pem");
+
+    let first = E2EEncryption::load_or_create_identity(&path).unwrap();
+    assert!(path.exists());
+    let second = E2EEncryption::load_or_create_identity(&path).unwrap();
+
+    let first = E2EEncryption::with_identity(first);
+    let second = E2EEncryption::with_identity(second);
+    assert_eq!(first.fingerprint().unwrap(), second.fingerprint().unwrap());
+}
+
+#[test]
+fn test_unreadable_identity_key_is_not_replaced() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("identity.pem");
+    let contents = [0xff, 0xfe, 0x00, 0x80];
+    std::fs::write(&path, contents).unwrap();
+
+    assert!(E2EEncryption::load_or_create_identity(&path).is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), contents);
+}
+
+#[test]
+fn test_fingerprint_format() {
+    let mut alice = E2EEncryption::new().unwrap();
+    let bob = E2EEncryption::new().unwrap();
+    let bob_key = bob.get_public_key_base64().unwrap();
+
+    let fp = fingerprint(&bob_key).unwrap();
+    assert_eq!(fp.len(), 39);
+    assert_eq!(fp.split(':').count(), 8);
+    assert_eq!(fp, bob.fingerprint().unwrap());
+    assert_ne!(fp, alice.fingerprint().unwrap());
+
+    assert!(alice.peer_fingerprint().is_none());
+    alice.set_peer_public_key(&bob_key).unwrap();
+    assert_eq!(alice.peer_fingerprint(), Some(fp));
+
+    assert!(fingerprint("not base64!").is_err());
+}
+
+/// Runs the handshake between two ends, with `first` picking the session key.
+fn handshake(first: &mut E2EEncryption, second: &mut E2EEncryption) {
+    first
+        .set_peer_public_key(&second.get_public_key_base64().unwrap())
+        .unwrap();
+    first
+        .set_peer_challenge(&second.challenge_base64())
+        .unwrap();
+    second
+        .set_peer_public_key(&first.get_public_key_base64().unwrap())
+        .unwrap();
+    second
+        .set_peer_challenge(&first.challenge_base64())
+        .unwrap();
+    let shared_key = first.generate_shared_key().unwrap();
+    second.set_shared_key(&shared_key).unwrap();
+}
+
+#[test]
+fn test_identity_proof_verifies_both_sides() {
+    let mut alice = E2EEncryption::new().unwrap();
+    let mut bob = E2EEncryption::new().unwrap();
+    handshake(&mut alice, &mut bob);
+    assert!(alice.is_ready() && !alice.is_verified());
+
+    let alice_proof = alice.identity_proof().unwrap();
+    let bob_proof = bob.identity_proof().unwrap();
+    bob.verify_identity_proof(&alice_proof).unwrap();
+    alice.verify_identity_proof(&bob_proof).unwrap();
+    assert!(alice.is_verified());
+    assert!(bob.is_verified());
+}
+
+#[test]
+fn test_identity_proof_rejects_replayed_public_key() {
+    let mut alice = E2EEncryption::new().unwrap();
+    let bob = E2EEncryption::new().unwrap();
+    let mut mallory = E2EEncryption::new().unwrap();
+
+    // Mallory announces Bob's public key but picks the session key herself
+    alice
+        .set_peer_public_key(&bob.get_public_key_base64().unwrap())
+        .unwrap();
+    alice
+        .set_peer_challenge(&mallory.challenge_base64())
+        .unwrap();
+    mallory
+        .set_peer_public_key(&alice.get_public_key_base64().unwrap())
+        .unwrap();
+    mallory
+        .set_peer_challenge(&alice.challenge_base64())
+        .unwrap();
+    let shared_key = mallory.generate_shared_key().unwrap();
+    alice.set_shared_key(&shared_key).unwrap();
+
+    let proof = mallory.identity_proof().unwrap();
+    assert!(alice.verify_identity_proof(&proof).is_err());
+    assert!(alice.is_ready());
+    assert!(!alice.is_verified());
+}
+
+#[test]
+fn test_identity_proof_is_bound_to_the_connection() {
+    let alice_key = E2EEncryption::generate_identity().unwrap();
+    let mut bob = E2EEncryption::new().unwrap();
+    let mut first = E2EEncryption::with_identity(alice_key.clone());
+    handshake(&mut bob, &mut first);
+    let recorded = bob.identity_proof().unwrap();
+
+    // Replaying the proof on a later connection fails: the challenge differs
+    let mut second = E2EEncryption::with_identity(alice_key);
+    let mut replayer = E2EEncryption::new().unwrap();
+    handshake(&mut replayer, &mut second);
+    second
+        .set_peer_public_key(&bob.get_public_key_base64().unwrap())
+        .unwrap();
+    assert!(second.verify_identity_proof(&recorded).is_err());
+    assert!(second.verify_identity_proof("not base64!").is_err());
+    assert!(!second.is_verified());
+
+    // Without a session key there is nothing to prove yet
+    assert!(E2EEncryption::new().unwrap().identity_proof().is_err());
+}
+
+#[test]
+fn test_identity_proof_with_shared_identity_key() {
+    let key = E2EEncryption::generate_identity().unwrap();
+    let mut first = E2EEncryption::with_identity(key.clone());
+    let mut second = E2EEncryption::with_identity(key);
+    first
+        .set_peer_public_key(&second.get_public_key_base64().unwrap())
+        .unwrap();
+    first
+        .set_peer_challenge(&second.challenge_base64())
+        .unwrap();
+    second
+        .set_peer_public_key(&first.get_public_key_base64().unwrap())
+        .unwrap();
+    second
+        .set_peer_challenge(&first.challenge_base64())
+        .unwrap();
+
+    // Exactly one side picks the session key
+    assert_ne!(
+        first.picks_session_key().unwrap(),
+        second.picks_session_key().unwrap()
+    );
+    handshake(&mut first, &mut second);
+
+    // A proof sent back to its signer does not verify
+    let proof = first.identity_proof().unwrap();
+    assert!(first.verify_identity_proof(&proof).is_err());
+    second.verify_identity_proof(&proof).unwrap();
+    first
+        .verify_identity_proof(&second.identity_proof().unwrap())
+        .unwrap();
+    assert!(first.is_verified());
+}
+
+#[test]
+fn test_safety_number() {
+    let alice = E2EEncryption::new().unwrap().fingerprint().unwrap();
+    let bob = E2EEncryption::new().unwrap().fingerprint().unwrap();
+    let carol = E2EEncryption::new().unwrap().fingerprint().unwrap();
+
+    // Both sides compute the same number
+    let number = safety_number(&alice, &bob);
+    assert_eq!(number, safety_number(&bob, &alice));
+
+    // Twelve groups of five digits
+    let groups: Vec<&str> = number.split(' ').collect();
+    assert_eq!(groups.len(), 12);
+    assert!(groups
+        .iter()
+        .all(|group| group.len() == 5 && group.chars().all(|c| c.is_ascii_digit())));
+
+    // A different key gives a different number
+    assert_ne!(number, safety_number(&alice, &carol));
+}
@@ -49,7 +49,7 @@ fn test_chat_error_display_io_timed_out() {
 
 #[test]
 fn test_chat_error_display_io_other() {
-    let io_error = io::Error::new(io::ErrorKind::Other, "Some other error");
+    let io_error = io::Error::other("Some other error");
     let chat_error = ChatError::Io(io_error);
 
     let display_text = format!("{}", chat_error);
@@ -91,7 +91,9 @@ fn test_chat_error_display_bind_failed_addr_in_use() {
     let chat_error = ChatError::BindFailed("127.0.0.1:8080".to_string(), io_error);
 
     let display_text = format!("{}", chat_error);
-    assert!(display_text.contains("Port 127.0.0.1:8080 is already in use - try a different port"));
+    assert!(
+        display_text.contains("Address 127.0.0.1:8080 is already in use - try a different port")
+    );
 }
 
 #[test]
@@ -100,19 +102,36 @@ fn test_chat_error_display_bind_failed_permission_denied() {
     let chat_error = ChatError::BindFailed("0.0.0.0:80".to_string(), io_error);
 
     let display_text = format!("{}", chat_error);
-    assert!(display_text
-        .contains("Cannot use port 0.0.0.0:80 - permission denied (try a port above 1024)"));
+    assert!(
+        display_text.contains("Cannot use 0.0.0.0:80 - permission denied (try a port above 1024)")
+    );
 }
 
 #[test]
 fn test_chat_error_display_bind_failed_other() {
-    let io_error = io::Error::new(io::ErrorKind::Other, "Some bind error");
+    let io_error = io::Error::other("Some bind error");
     let chat_error = ChatError::BindFailed("127.0.0.1:8080".to_string(), io_error);
 
     let display_text = format!("{}", chat_error);
     assert!(display_text.contains("Cannot start server on 127.0.0.1:8080"));
 }
 
+#[test]
+fn test_chat_error_display_ipv6_addresses() {
+    let io_error = io::Error::new(io::ErrorKind::AddrInUse, "Address already in use");
+    let chat_error = ChatError::BindFailed("[::]:8080".to_string(), io_error);
+    assert_eq!(
+        chat_error.to_string(),
+        "Address [::]:8080 is already in use - try a different port, or port 0 for any free one"
+    );
+
+    let io_error = io::Error::new(io::ErrorKind::ConnectionRefused, "Connection refused");
+    let chat_error = ChatError::ConnectFailed("[::1]:9000".to_string(), io_error);
+    assert!(chat_error
+        .to_string()
+        .starts_with("Cannot reach [::1]:9000"));
+}
+
 #[test]
 fn test_chat_error_display_connect_failed_connection_refused() {
     let io_error = io::Error::new(io::ErrorKind::ConnectionRefused, "Connection refused");
@@ -134,6 +153,19 @@ fn test_chat_error_display_connect_failed_timed_out() {
         .contains("Connection to 10.0.0.1:9000 timed out - check IP address and network"));
 }
 
+#[test]
+fn test_chat_error_display_handshake_timed_out() {
+    let io_error = io::Error::new(
+        io::ErrorKind::TimedOut,
+        "encryption handshake did not complete",
+    );
+    let chat_error = ChatError::ConnectFailed("10.0.0.1:9000".to_string(), io_error);
+
+    let display_text = format!("{}", chat_error);
+    assert!(display_text
+        .contains("Peer at 10.0.0.1:9000 did not complete the encryption handshake in time"));
+}
+
 #[test]
 fn test_chat_error_display_connect_failed_invalid_input() {
     let io_error = io::Error::new(io::ErrorKind::InvalidInput, "Invalid input");
@@ -141,7 +173,7 @@ fn test_chat_error_display_connect_failed_invalid_input() {
 
     let display_text = format!("{}", chat_error);
     assert!(display_text.contains(
-        "Invalid address 'invalid_address' - use format IP:PORT (e.g., 192.168.1.100:8080)"
+        "Invalid address 'invalid_address' - use format IP:PORT (e.g., 192.168.1.100:8080 or [::1]:8080)"
     ));
 }
 
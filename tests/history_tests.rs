@@ -0,0 +1,144 @@
+use rust_p2p_chat::history::{ChatHistory, HistoryEntry, MAX_ENTRIES};
+use rust_p2p_chat::Config;
+use tempfile::TempDir;
+
+#[test]
+fn test_history_is_kept_per_conversation() {
+    let mut history = ChatHistory::in_memory();
+    history
+        .push("alice", HistoryEntry::new("alice", "Hi"))
+        .unwrap();
+    history
+        .push("bob", HistoryEntry::new("You", "Hello Bob"))
+        .unwrap();
+    history
+        .push("alice", HistoryEntry::new("You", "Hi Alice"))
+        .unwrap();
+
+    let texts: Vec<&str> = history
+        .entries("alice")
+        .iter()
+        .map(|entry| entry.text.as_str())
+        .collect();
+    assert_eq!(texts, vec!["Hi", "Hi Alice"]);
+    assert_eq!(history.entries("bob").len(), 1);
+    assert!(history.entries("carol").is_empty());
+
+    let names: Vec<&String> = history.conversations().collect();
+    assert_eq!(names, vec!["alice", "bob"]);
+}
+
+#[test]
+fn test_history_persists() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("history").join("chat_history.json");
+
+    let mut history = ChatHistory::load(&path);
+    let mut entry = HistoryEntry::new("alice", "photo.png");
+    entry.is_file = true;
+    entry.encrypted = true;
+    history.push("alice", entry.clone()).unwrap();
+
+    let reloaded = ChatHistory::load(&path);
+    assert_eq!(reloaded.entries("alice"), &[entry]);
+
+    let mut reloaded = reloaded;
+    reloaded.clear("alice").unwrap();
+    assert!(ChatHistory::load(&path).entries("alice").is_empty());
+}
+
+#[test]
+fn test_history_keeps_latest_entries() {
+    let mut history = ChatHistory::in_memory();
+    for i in 0..MAX_ENTRIES + 5 {
+        history
+            .push("alice", HistoryEntry::new("alice", i.to_string()))
+            .unwrap();
+    }
+
+    let entries = history.entries("alice");
+    assert_eq!(entries.len(), MAX_ENTRIES);
+    assert_eq!(entries[0].text, "5");
+}
+
+#[test]
+fn test_corrupt_or_disabled_history() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("chat_history.json");
+    std::fs::write(&path, "not json").unwrap();
+    assert!(ChatHistory::load(&path).conversations().next().is_none());
+
+    // Nothing is written when history is turned off
+    let config = Config {
+        save_history: false,
+        history_file: Some(path.clone()),
+        ..Default::default()
+    };
+    let mut history = ChatHistory::load_for(&config);
+    history
+        .push("alice", HistoryEntry::new("alice", "Hi"))
+        .unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not json");
+}
+
+#[test]
+fn test_history_appends_messages() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("chat_history.json");
+
+    let mut history = ChatHistory::load(&path);
+    history
+        .push("alice", HistoryEntry::new("alice", "Hi"))
+        .unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    history
+        .push("bob", HistoryEntry::new("You", "Hello Bob"))
+        .unwrap();
+
+    // The file is only extended, one line per message
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.starts_with(&written));
+    assert_eq!(contents.lines().count(), 2);
+    assert_eq!(ChatHistory::load(&path).entries("bob").len(), 1);
+}
+
+#[test]
+fn test_history_file_is_compacted() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("chat_history.json");
+
+    let mut history = ChatHistory::load(&path);
+    for i in 0..3 * MAX_ENTRIES {
+        history
+            .push("alice", HistoryEntry::new("alice", i.to_string()))
+            .unwrap();
+    }
+
+    let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+    assert!(lines <= 2 * MAX_ENTRIES, "{} lines", lines);
+    let reloaded = ChatHistory::load(&path);
+    assert_eq!(reloaded.entries("alice"), history.entries("alice"));
+}
+
+#[test]
+fn test_history_reads_old_format() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("chat_history.json");
+    let old = HistoryEntry::new("alice", "From before");
+    let contents = serde_json::json!({ "conversations": { "alice": [old] } });
+    std::fs::write(&path, contents.to_string()).unwrap();
+
+    let mut history = ChatHistory::load(&path);
+    assert_eq!(history.entries("alice")[0].text, "From before");
+
+    // The first new message converts the file
+    history
+        .push("alice", HistoryEntry::new("You", "Hi again"))
+        .unwrap();
+    let texts: Vec<String> = ChatHistory::load(&path)
+        .entries("alice")
+        .iter()
+        .map(|entry| entry.text.clone())
+        .collect();
+    assert_eq!(texts, vec!["From before", "Hi again"]);
+}
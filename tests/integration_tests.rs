@@ -1,6 +1,10 @@
+use rust_p2p_chat::config::PortRange;
+use rust_p2p_chat::events::ChatEvent;
 use rust_p2p_chat::file_transfer::FileTransfer;
-use rust_p2p_chat::protocol::{Message, MessageType};
-use rust_p2p_chat::{Config, P2PChat};
+use rust_p2p_chat::protocol::{Command, Message, MessageType};
+use rust_p2p_chat::session::ChatSession;
+use rust_p2p_chat::transfer::{TransferEvent, TransferState};
+use rust_p2p_chat::{ChatError, Config, P2PChat};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
@@ -59,6 +63,476 @@ async fn test_full_chat_session_lifecycle() {
     let _ = timeout(Duration::from_millis(100), client_handle).await;
 }
 
+#[tokio::test]
+async fn test_ephemeral_port_is_reported() {
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        ..Default::default()
+    };
+    let chat = P2PChat::new(config).unwrap();
+    let mut local_addr = chat.local_addr();
+    assert_eq!(*local_addr.borrow(), None);
+
+    let session = ChatSession::start(chat, 0, None);
+
+    let addr = timeout(Duration::from_secs(5), local_addr.wait_for(Option::is_some))
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_ne!(addr.port(), 0);
+    TcpStream::connect(addr).await.unwrap();
+    drop(session);
+}
+
+/// Waits for the first event matching `pred`, skipping others.
+async fn next_event(session: &mut ChatSession, pred: impl Fn(&ChatEvent) -> bool) -> ChatEvent {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            let event = session.next_event().await.expect("session ended");
+            if pred(&event) {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("event not published in time")
+}
+
+#[tokio::test]
+async fn test_chat_session_events_and_messages() {
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        enable_encryption: false,
+        ..Default::default()
+    };
+
+    let mut listener = ChatSession::start(P2PChat::new(config.clone()).unwrap(), 0, None);
+    let addr = match next_event(&mut listener, |e| matches!(e, ChatEvent::Listening(_))).await {
+        ChatEvent::Listening(addr) => addr,
+        _ => unreachable!(),
+    };
+    assert_eq!(*listener.local_addr().borrow(), Some(addr));
+
+    let mut connector =
+        ChatSession::start(P2PChat::new(config).unwrap(), 0, Some(addr.to_string()));
+    next_event(&mut connector, |e| {
+        matches!(e, ChatEvent::PeerConnected { outgoing: true, .. })
+    })
+    .await;
+    next_event(&mut listener, |e| {
+        matches!(
+            e,
+            ChatEvent::PeerConnected {
+                outgoing: false,
+                ..
+            }
+        )
+    })
+    .await;
+
+    // Text from send_message is never taken for a command
+    connector.send_message("/not a command").unwrap();
+    match next_event(&mut connector, |e| {
+        matches!(e, ChatEvent::MessageSent { .. })
+    })
+    .await
+    {
+        ChatEvent::MessageSent {
+            to,
+            text,
+            encrypted,
+        } => {
+            assert_eq!(to, None);
+            assert_eq!(text, "/not a command");
+            assert!(!encrypted);
+        }
+        _ => unreachable!(),
+    }
+    match next_event(&mut listener, |e| {
+        matches!(e, ChatEvent::MessageReceived { .. })
+    })
+    .await
+    {
+        ChatEvent::MessageReceived {
+            text,
+            encrypted,
+            private,
+            ..
+        } => {
+            assert_eq!(text, "/not a command");
+            assert!(!encrypted);
+            assert!(!private);
+        }
+        _ => unreachable!(),
+    }
+
+    // Command output comes back as events too
+    connector.send_line("/transfers").unwrap();
+    next_event(
+        &mut connector,
+        |e| matches!(e, ChatEvent::Notice(text) if text == "No file transfers."),
+    )
+    .await;
+
+    timeout(Duration::from_secs(5), connector.close())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Closing says goodbye rather than just dropping the connection
+    next_event(&mut listener, |e| {
+        matches!(e, ChatEvent::PeerDisconnected { left: true, .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_shutdown_token_ends_session() {
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        enable_encryption: false,
+        ..Default::default()
+    };
+
+    let mut listener = ChatSession::start(P2PChat::new(config.clone()).unwrap(), 0, None);
+    let addr = match next_event(&mut listener, |e| matches!(e, ChatEvent::Listening(_))).await {
+        ChatEvent::Listening(addr) => addr,
+        _ => unreachable!(),
+    };
+
+    let chat = P2PChat::new(config).unwrap();
+    let shutdown = chat.shutdown_token();
+    let mut connector = ChatSession::start(chat, 0, Some(addr.to_string()));
+    next_event(&mut connector, |e| {
+        matches!(e, ChatEvent::PeerConnected { .. })
+    })
+    .await;
+    next_event(&mut listener, |e| {
+        matches!(e, ChatEvent::PeerConnected { .. })
+    })
+    .await;
+
+    shutdown.cancel();
+    timeout(Duration::from_secs(5), connector.wait())
+        .await
+        .unwrap()
+        .unwrap();
+    next_event(&mut listener, |e| {
+        matches!(e, ChatEvent::PeerDisconnected { left: true, .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_chat_session_transfer_offer_and_progress() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("notes.txt");
+    std::fs::write(&source, "Meeting notes").unwrap();
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        enable_encryption: false,
+        auto_open_media: false,
+        ..Default::default()
+    };
+
+    let receiver_config = Config {
+        auto_accept_transfers: false,
+        download_dir: Some(temp_dir.path().join("downloads")),
+        ..config.clone()
+    };
+    let mut receiver = ChatSession::start(P2PChat::new(receiver_config).unwrap(), 0, None);
+    let addr = match next_event(&mut receiver, |e| matches!(e, ChatEvent::Listening(_))).await {
+        ChatEvent::Listening(addr) => addr,
+        _ => unreachable!(),
+    };
+    let mut sender = ChatSession::start(P2PChat::new(config).unwrap(), 0, Some(addr.to_string()));
+    next_event(&mut sender, |e| {
+        matches!(e, ChatEvent::PeerConnected { .. })
+    })
+    .await;
+    next_event(&mut receiver, |e| {
+        matches!(e, ChatEvent::PeerConnected { .. })
+    })
+    .await;
+
+    sender.send_file(&source).unwrap();
+    let id = match next_event(&mut receiver, |e| {
+        matches!(
+            e,
+            ChatEvent::Transfer {
+                event: TransferEvent::Offered(_),
+                ..
+            }
+        )
+    })
+    .await
+    {
+        ChatEvent::Transfer {
+            event: TransferEvent::Offered(status),
+            ..
+        } => status.id,
+        _ => unreachable!(),
+    };
+
+    receiver.command(Command::AcceptTransfer(id)).unwrap();
+    match next_event(&mut receiver, |e| {
+        matches!(
+            e,
+            ChatEvent::Transfer {
+                event: TransferEvent::Completed { .. },
+                ..
+            }
+        )
+    })
+    .await
+    {
+        ChatEvent::Transfer {
+            event: TransferEvent::Completed { path, .. },
+            ..
+        } => assert_eq!(std::fs::read_to_string(path).unwrap(), "Meeting notes"),
+        _ => unreachable!(),
+    }
+
+    // Progress is published for both directions until the transfer is done;
+    // each peer numbers the transfer itself
+    next_event(&mut sender, |e| {
+        matches!(
+            e,
+            ChatEvent::TransferProgress { status, .. }
+                if status.name == "notes.txt" && status.state == TransferState::Completed
+        )
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_chat_session_config_update_applies_live() {
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        enable_encryption: false,
+        ..Default::default()
+    };
+    let mut listener = ChatSession::start(P2PChat::new(config.clone()).unwrap(), 0, None);
+    let addr = match next_event(&mut listener, |e| matches!(e, ChatEvent::Listening(_))).await {
+        ChatEvent::Listening(addr) => addr,
+        _ => unreachable!(),
+    };
+    let mut dialer = ChatSession::start(
+        P2PChat::new(config.clone()).unwrap(),
+        0,
+        Some(addr.to_string()),
+    );
+    next_event(&mut dialer, |e| {
+        matches!(e, ChatEvent::PeerConnected { .. })
+    })
+    .await;
+    next_event(&mut listener, |e| {
+        matches!(e, ChatEvent::PeerConnected { .. })
+    })
+    .await;
+
+    // The new nickname reaches the peer without restarting the session
+    dialer
+        .update_config(Config {
+            nickname: Some("Alice".to_string()),
+            ..config
+        })
+        .unwrap();
+    next_event(
+        &mut dialer,
+        |e| matches!(e, ChatEvent::Notice(text) if text.contains("Settings updated")),
+    )
+    .await;
+    match next_event(&mut listener, |e| {
+        matches!(e, ChatEvent::PeerRenamed { .. })
+    })
+    .await
+    {
+        ChatEvent::PeerRenamed { name, .. } => assert_eq!(name, "Alice"),
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn test_chat_session_reports_start_errors() {
+    let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = taken.local_addr().unwrap().port();
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        ..Default::default()
+    };
+
+    let session = ChatSession::start(P2PChat::new(config).unwrap(), port, None);
+    let result = timeout(Duration::from_secs(5), session.wait())
+        .await
+        .unwrap();
+    assert!(matches!(result, Err(ChatError::BindFailed(_, _))));
+}
+
+#[tokio::test]
+async fn test_port_range_skips_ports_in_use() {
+    let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = taken.local_addr().unwrap().port();
+    let range = PortRange {
+        start: port,
+        end: port.saturating_add(20),
+    };
+
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        ..Default::default()
+    };
+    let mut chat = P2PChat::new(config).unwrap().with_port_range(range);
+    let mut local_addr = chat.local_addr();
+    let handle = tokio::spawn(async move {
+        let _ = chat.start(port, None).await;
+    });
+
+    let addr = timeout(Duration::from_secs(5), local_addr.wait_for(Option::is_some))
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(addr.port() > port && addr.port() <= range.end);
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_port_range_exhausted() {
+    let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = taken.local_addr().unwrap().port();
+
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        ..Default::default()
+    };
+    let range = PortRange {
+        start: port,
+        end: port,
+    };
+    let mut chat = P2PChat::new(config).unwrap().with_port_range(range);
+
+    let error = chat.start(port, None).await.unwrap_err();
+    assert!(matches!(error, ChatError::BindFailed(_, _)));
+    assert!(error.to_string().contains("already in use"));
+}
+
+#[tokio::test]
+async fn test_relayed_messages_name_the_relay() {
+    let config = |nickname: &str| Config {
+        bind_address: "127.0.0.1".to_string(),
+        nickname: Some(nickname.to_string()),
+        ..Default::default()
+    };
+    let mut bob = ChatSession::start(P2PChat::new(config("Bob")).unwrap(), 0, None);
+    let addr = match next_event(&mut bob, |e| matches!(e, ChatEvent::Listening(_))).await {
+        ChatEvent::Listening(addr) => addr,
+        _ => unreachable!(),
+    };
+    let connect = |nickname| {
+        ChatSession::start(
+            P2PChat::new(config(nickname)).unwrap(),
+            0,
+            Some(addr.to_string()),
+        )
+    };
+    let mut alice = connect("Alice");
+    let mut carol = connect("Carol");
+    for session in [&mut alice, &mut carol] {
+        next_event(session, |e| {
+            matches!(e, ChatEvent::EncryptionEnabled { .. })
+        })
+        .await;
+    }
+
+    alice.send_message("hello mesh").unwrap();
+    match next_event(
+        &mut carol,
+        |e| matches!(e, ChatEvent::MessageReceived { text, .. } if text == "hello mesh"),
+    )
+    .await
+    {
+        ChatEvent::MessageReceived {
+            from, encrypted, ..
+        } => {
+            assert_eq!(from, "via Bob (from Alice, unverified)");
+            // Bob could read the message while relaying it
+            assert!(!encrypted);
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn test_simple_peer_gets_plain_lines() {
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        enable_encryption: false,
+        ..Default::default()
+    };
+    let mut session = ChatSession::start(P2PChat::new(config).unwrap(), 0, None);
+    let addr = match next_event(&mut session, |e| matches!(e, ChatEvent::Listening(_))).await {
+        ChatEvent::Listening(addr) => addr,
+        _ => unreachable!(),
+    };
+
+    // A simple peer, like P2PPeer, never announces its capabilities
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    next_event(&mut session, |e| {
+        matches!(e, ChatEvent::PeerConnected { .. })
+    })
+    .await;
+
+    session.send_message("hello simple peer").unwrap();
+    let mut received = Vec::new();
+    let mut buffer = [0; 1024];
+    let line = b"hello simple peer\n";
+    timeout(Duration::from_secs(5), async {
+        while !received.windows(line.len()).any(|window| window == line) {
+            let n = stream.read(&mut buffer).await.unwrap();
+            assert!(n > 0, "connection closed");
+            received.extend_from_slice(&buffer[..n]);
+        }
+    })
+    .await
+    .expect("text was not sent as a plain line");
+
+    stream.write_all(b"hi back\n").await.unwrap();
+    next_event(
+        &mut session,
+        |e| matches!(e, ChatEvent::MessageReceived { text, .. } if text == "hi back"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_stalled_handshake_is_dropped() {
+    let config = Config {
+        bind_address: "127.0.0.1".to_string(),
+        handshake_timeout_secs: 1,
+        ..Default::default()
+    };
+    let chat = P2PChat::new(config).unwrap();
+    let mut local_addr = chat.local_addr();
+    let session = ChatSession::start(chat, 0, None);
+    let addr = timeout(Duration::from_secs(5), local_addr.wait_for(Option::is_some))
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    // Connect but never answer the key exchange
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut received = Vec::new();
+    let closed = timeout(Duration::from_secs(5), stream.read_to_end(&mut received)).await;
+    assert!(
+        closed.is_ok(),
+        "peer with a stalled handshake was not dropped"
+    );
+    drop(session);
+}
+
 #[tokio::test]
 async fn test_message_protocol_integration() {
     // Test message creation and serialization in realistic scenarios
@@ -269,18 +743,28 @@ async fn test_configuration_persistence() {
     let original_config = Config {
         nickname: Some("TestUser".to_string()),
         default_port: 9999,
+        bind_address: "192.168.1.5".to_string(),
         buffer_size: 8192,
         heartbeat_interval_secs: 30,
         reconnect_attempts: 3,
         reconnect_delay_secs: 5,
+        connect_timeout_secs: 20,
+        handshake_timeout_secs: 30,
         enable_encryption: true,
+        enable_compression: true,
         log_level: "info".to_string(),
         save_history: true,
         history_file: Some(PathBuf::from("/custom/history.txt")),
         max_file_size_mb: 200,
+        auto_accept_transfers: false,
+        max_upload_kbps: Some(1024),
+        max_download_kbps: Some(2048),
+        enable_discovery: true,
+        discovery_address: "239.255.77.77:7777".to_string(),
         download_dir: Some(PathBuf::from("/custom/downloads")),
         auto_open_media: false,
         media_extensions: vec!["jpg".to_string(), "png".to_string()],
+        desktop_notifications: false,
     };
 
     // Serialize to TOML
@@ -299,6 +783,14 @@ async fn test_configuration_persistence() {
         original_config.max_file_size_mb,
         loaded_config.max_file_size_mb
     );
+    assert_eq!(
+        original_config.auto_accept_transfers,
+        loaded_config.auto_accept_transfers
+    );
+    assert_eq!(
+        original_config.desktop_notifications,
+        loaded_config.desktop_notifications
+    );
     assert_eq!(
         original_config.enable_encryption,
         loaded_config.enable_encryption
@@ -309,6 +801,12 @@ async fn test_configuration_persistence() {
     );
     assert_eq!(original_config.download_dir, loaded_config.download_dir);
     assert_eq!(original_config.history_file, loaded_config.history_file);
+    assert_eq!(loaded_config.max_upload_kbps, Some(1024));
+    assert_eq!(loaded_config.max_download_kbps, Some(2048));
+    assert!(loaded_config.enable_discovery);
+    assert_eq!(loaded_config.bind_address, "192.168.1.5");
+    assert_eq!(loaded_config.connect_timeout_secs, 20);
+    assert_eq!(loaded_config.handshake_timeout_secs, 30);
 }
 
 #[tokio::test]
@@ -529,13 +1027,13 @@ fn test_config_default_values() {
     assert!(
         download_path.to_string_lossy().contains("Downloads")
             || download_path.to_string_lossy().contains("downloads")
-            || download_path == PathBuf::from(".")
+            || download_path == std::path::Path::new(".")
             || download_path.to_string_lossy().contains("home")
     );
     if let Some(history_path) = config.history_path() {
         assert!(history_path
             .extension()
-            .map_or(false, |ext| ext == "json" || ext == "txt"));
+            .is_some_and(|ext| ext == "json" || ext == "txt"));
     }
 }
 
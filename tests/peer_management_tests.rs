@@ -1,8 +1,11 @@
-use rust_p2p_chat::peer::{PeerInfo, PeerManager};
+use rust_p2p_chat::encryption::E2EEncryption;
+use rust_p2p_chat::peer::{Peer, PeerInfo, PeerManager, MAX_NICKNAME_LEN};
+use rust_p2p_chat::protocol::{GossipMessage, Message, MessageType};
+use rust_p2p_chat::transfer::TransferManager;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::SystemTime;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex, Notify};
 
 #[tokio::test]
 async fn test_peer_manager_creation() {
@@ -157,7 +160,7 @@ async fn test_concurrent_peer_access() {
 
 #[tokio::test]
 async fn test_peer_info_with_different_addresses() {
-    let addresses = vec![
+    let addresses: [SocketAddr; 4] = [
         "127.0.0.1:8080".parse().unwrap(),
         "0.0.0.0:9000".parse().unwrap(),
         "192.168.1.100:3000".parse().unwrap(),
@@ -206,7 +209,7 @@ async fn test_peer_info_time_ordering() {
 async fn test_peer_with_special_characters() {
     let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
 
-    let special_names = vec![
+    let special_names = [
         "用户",          // Chinese characters
         "José María",    // Accented characters
         "User🎉",        // Emoji
@@ -329,3 +332,383 @@ async fn test_peer_edge_cases() {
     assert_eq!(peer_info.id.len(), 1000);
     assert_eq!(peer_info.id, long_id);
 }
+
+fn connected_peer(id: &str, port: u16) -> (Peer, mpsc::Receiver<Message>) {
+    let (tx, rx) = mpsc::channel(10);
+    let (bulk_tx, _bulk_rx) = mpsc::channel(4);
+    let peer = Peer {
+        info: PeerInfo {
+            id: id.to_string(),
+            nickname: None,
+            address: SocketAddr::from(([127, 0, 0, 1], port)),
+            connected_at: SystemTime::now(),
+        },
+        tx,
+        bulk_tx,
+        encryption: Arc::new(Mutex::new(E2EEncryption::new().unwrap())),
+        transfers: Arc::new(TransferManager::new(10)),
+        shutdown: Arc::new(Notify::new()),
+    };
+    (peer, rx)
+}
+
+#[tokio::test]
+async fn test_peer_manager_add_and_remove() {
+    let (manager, _receiver) = PeerManager::new();
+    let (first, _rx1) = connected_peer("1", 9001);
+    let (second, _rx2) = connected_peer("2", 9002);
+
+    manager.add_peer("1".to_string(), first).await.unwrap();
+    manager.add_peer("2".to_string(), second).await.unwrap();
+    assert_eq!(manager.peer_count().await, 2);
+
+    // Listed in connection order
+    let ids: Vec<String> = manager
+        .list_peers()
+        .await
+        .into_iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(ids, vec!["1", "2"]);
+
+    assert!(manager.remove_peer("1").await.is_some());
+    assert!(manager.get_peer("1").await.is_none());
+    assert!(manager.get_peer("2").await.is_some());
+    assert_eq!(manager.peer_count().await, 1);
+}
+
+#[tokio::test]
+async fn test_next_peer_id_is_unique() {
+    let (manager, _receiver) = PeerManager::new();
+    let first = manager.next_peer_id();
+    let second = manager.next_peer_id();
+    assert_ne!(first, second);
+    assert_eq!(first, "1");
+}
+
+#[tokio::test]
+async fn test_broadcast_text_reaches_every_peer() {
+    let (manager, _receiver) = PeerManager::new();
+    let mut receivers = Vec::new();
+    for (i, port) in [9001, 9002, 9003].into_iter().enumerate() {
+        let (peer, rx) = connected_peer(&i.to_string(), port);
+        manager.add_peer(i.to_string(), peer).await.unwrap();
+        receivers.push(rx);
+    }
+
+    let sent = manager.broadcast_text("hello team").await.unwrap();
+    assert_eq!(sent, 3);
+    for rx in receivers.iter_mut() {
+        let message = rx.try_recv().unwrap();
+        assert_eq!(
+            message.msg_type,
+            MessageType::Text("hello team".to_string())
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_broadcast_text_encrypts_per_peer() {
+    let (manager, _receiver) = PeerManager::new();
+    let (secure, mut secure_rx) = connected_peer("1", 9001);
+    let (plain, mut plain_rx) = connected_peer("2", 9002);
+
+    // Complete a handshake with the first peer only
+    let mut remote = E2EEncryption::new().unwrap();
+    handshake(&secure, &mut remote).await;
+
+    manager.add_peer("1".to_string(), secure).await.unwrap();
+    manager.add_peer("2".to_string(), plain).await.unwrap();
+    manager.broadcast_text("secret").await.unwrap();
+
+    match secure_rx.try_recv().unwrap().msg_type {
+        MessageType::EncryptedText(encrypted) => {
+            assert_eq!(remote.decrypt_message(&encrypted).unwrap(), "secret");
+        }
+        other => panic!("Expected encrypted text, got {:?}", other),
+    }
+    assert_eq!(
+        plain_rx.try_recv().unwrap().msg_type,
+        MessageType::Text("secret".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_mark_seen_detects_duplicates() {
+    let (manager, _receiver) = PeerManager::new();
+    assert!(manager.mark_seen(7));
+    assert!(!manager.mark_seen(7));
+    assert!(manager.mark_seen(8));
+
+    // Messages we broadcast count as seen, so echoes are dropped
+    let message = Message::new_text("hello".to_string());
+    let id = message.id;
+    manager.broadcast(message, None).await.unwrap();
+    assert!(!manager.mark_seen(id));
+}
+
+#[tokio::test]
+async fn test_broadcast_text_shares_message_id() {
+    let (manager, _receiver) = PeerManager::new();
+    let (first, mut first_rx) = connected_peer("1", 9001);
+    let (second, mut second_rx) = connected_peer("2", 9002);
+    manager.add_peer("1".to_string(), first).await.unwrap();
+    manager.add_peer("2".to_string(), second).await.unwrap();
+
+    manager.broadcast_text("hello").await.unwrap();
+    let id = first_rx.try_recv().unwrap().id;
+    assert_eq!(second_rx.try_recv().unwrap().id, id);
+    assert!(!manager.mark_seen(id));
+}
+
+#[tokio::test]
+async fn test_broadcast_relays_gossip_except_to_sender() {
+    let (manager, _receiver) = PeerManager::new();
+    let (sender, mut sender_rx) = connected_peer("1", 9001);
+    let (secure, mut secure_rx) = connected_peer("2", 9002);
+    let (plain, mut plain_rx) = connected_peer("3", 9003);
+
+    let mut remote = E2EEncryption::new().unwrap();
+    handshake(&secure, &mut remote).await;
+
+    manager.add_peer("1".to_string(), sender).await.unwrap();
+    manager.add_peer("2".to_string(), secure).await.unwrap();
+    manager.add_peer("3".to_string(), plain).await.unwrap();
+
+    let relay = Message {
+        id: 99,
+        timestamp: SystemTime::now(),
+        msg_type: MessageType::Gossip(GossipMessage {
+            origin: "Peer 1".to_string(),
+            ttl: 3,
+            payload: Box::new(MessageType::Text("hi all".to_string())),
+        }),
+    };
+    manager.broadcast(relay, Some("1")).await.unwrap();
+
+    assert!(sender_rx.try_recv().is_err());
+
+    // The payload is encrypted with the key of the hop it travels over
+    let message = secure_rx.try_recv().unwrap();
+    assert_eq!(message.id, 99);
+    match message.msg_type {
+        MessageType::Gossip(gossip) => {
+            assert_eq!(gossip.origin, "Peer 1");
+            assert_eq!(gossip.ttl, 3);
+            match *gossip.payload {
+                MessageType::EncryptedText(encrypted) => {
+                    assert_eq!(remote.decrypt_message(&encrypted).unwrap(), "hi all");
+                }
+                other => panic!("Expected encrypted payload, got {:?}", other),
+            }
+        }
+        other => panic!("Expected gossip, got {:?}", other),
+    }
+
+    match plain_rx.try_recv().unwrap().msg_type {
+        MessageType::Gossip(gossip) => {
+            assert_eq!(*gossip.payload, MessageType::Text("hi all".to_string()));
+        }
+        other => panic!("Expected gossip, got {:?}", other),
+    }
+}
+
+/// Completes an encryption handshake between `peer` and a simulated remote end.
+async fn secure(peer: &Peer) -> E2EEncryption {
+    let mut remote = E2EEncryption::new().unwrap();
+    handshake(peer, &mut remote).await;
+    remote
+}
+
+/// Exchanges keys and challenges with `remote`, sends it a session key and
+/// checks its identity proof, as the side with the greater key would.
+async fn handshake(peer: &Peer, remote: &mut E2EEncryption) {
+    let mut local = peer.encryption.lock().await;
+    local
+        .set_peer_public_key(&remote.get_public_key_base64().unwrap())
+        .unwrap();
+    local
+        .set_peer_challenge(&remote.challenge_base64())
+        .unwrap();
+    remote
+        .set_peer_public_key(&local.get_public_key_base64().unwrap())
+        .unwrap();
+    remote
+        .set_peer_challenge(&local.challenge_base64())
+        .unwrap();
+
+    let shared_key = local.generate_shared_key().unwrap();
+    remote.set_shared_key(&shared_key).unwrap();
+    local
+        .verify_identity_proof(&remote.identity_proof().unwrap())
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_find_peer_by_id_or_nickname() {
+    let (manager, _receiver) = PeerManager::new();
+    let (mut alice, _rx1) = connected_peer("1", 9001);
+    alice.info.nickname = Some("Alice".to_string());
+    let (bob, _rx2) = connected_peer("2", 9002);
+    manager.add_peer("1".to_string(), alice).await.unwrap();
+    manager.add_peer("2".to_string(), bob).await.unwrap();
+
+    assert_eq!(manager.find_peer("2").await.unwrap().info.id, "2");
+    assert_eq!(manager.find_peer("alice").await.unwrap().info.id, "1");
+    assert!(manager.find_peer("carol").await.is_none());
+}
+
+#[tokio::test]
+async fn test_split_recipient_matches_nicknames_with_spaces() {
+    let (manager, _receiver) = PeerManager::new();
+    let (mut bob, _rx1) = connected_peer("1", 9001);
+    bob.info.nickname = Some("Bob".to_string());
+    let (mut bob_smith, _rx2) = connected_peer("2", 9002);
+    bob_smith.info.nickname = Some("Bob Smith".to_string());
+    manager.add_peer("1".to_string(), bob).await.unwrap();
+    manager.add_peer("2".to_string(), bob_smith).await.unwrap();
+
+    let (peer, text) = manager.split_recipient("bob smith hi there").await.unwrap();
+    assert_eq!(peer.info.id, "2");
+    assert_eq!(text, "hi there");
+    let (peer, text) = manager.split_recipient("Bob Smithers").await.unwrap();
+    assert_eq!(peer.info.id, "1");
+    assert_eq!(text, "Smithers");
+    let (peer, text) = manager.split_recipient("2 Bob Smith").await.unwrap();
+    assert_eq!(peer.info.id, "2");
+    assert_eq!(text, "Bob Smith");
+    // The message must not be empty
+    let (peer, text) = manager.split_recipient("Bob Smith").await.unwrap();
+    assert_eq!(peer.info.id, "1");
+    assert_eq!(text, "Smith");
+    assert!(manager.split_recipient("Bob").await.is_none());
+    assert!(manager.split_recipient("carol hello").await.is_none());
+}
+
+#[tokio::test]
+async fn test_send_private_reaches_only_recipient() {
+    let (manager, _receiver) = PeerManager::new();
+    let (alice, mut alice_rx) = connected_peer("1", 9001);
+    let (bob, mut bob_rx) = connected_peer("2", 9002);
+    let remote = secure(&bob).await;
+    secure(&alice).await;
+    manager.add_peer("1".to_string(), alice).await.unwrap();
+    manager.add_peer("2".to_string(), bob).await.unwrap();
+
+    let peer = manager.send_private("2", "just for you").await.unwrap();
+    assert_eq!(peer.info.id, "2");
+
+    match bob_rx.try_recv().unwrap().msg_type {
+        MessageType::PrivateText(encrypted) => {
+            assert_eq!(remote.decrypt_message(&encrypted).unwrap(), "just for you");
+        }
+        other => panic!("Expected private text, got {:?}", other),
+    }
+    assert!(alice_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_send_private_requires_encryption() {
+    let (manager, _receiver) = PeerManager::new();
+    let (plain, mut plain_rx) = connected_peer("1", 9001);
+    manager.add_peer("1".to_string(), plain).await.unwrap();
+
+    assert!(manager.send_private("1", "secret").await.is_err());
+    assert!(manager.send_private("2", "secret").await.is_err());
+    assert!(plain_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_set_nickname_disambiguates_collisions() {
+    let (manager, _receiver) = PeerManager::new();
+    let (first, _rx1) = connected_peer("1", 9001);
+    let (second, _rx2) = connected_peer("2", 9002);
+    manager.add_peer("1".to_string(), first).await.unwrap();
+    manager.add_peer("2".to_string(), second).await.unwrap();
+
+    assert_eq!(
+        manager.set_nickname("1", "Alice").await,
+        Some("Alice".to_string())
+    );
+    assert_eq!(
+        manager.set_nickname("2", "alice").await,
+        Some("alice#2".to_string())
+    );
+    // Re-announcing the same nickname keeps it
+    assert_eq!(
+        manager.set_nickname("1", "Alice").await,
+        Some("Alice".to_string())
+    );
+
+    let peer = manager.get_peer("2").await.unwrap();
+    assert_eq!(peer.info.display_name(), "alice#2");
+    assert_eq!(manager.find_peer("alice#2").await.unwrap().info.id, "2");
+}
+
+#[tokio::test]
+async fn test_set_nickname_sanitizes_input() {
+    let (manager, _receiver) = PeerManager::new();
+    let (peer, _rx) = connected_peer("1", 9001);
+    manager.add_peer("1".to_string(), peer).await.unwrap();
+
+    assert_eq!(
+        manager.set_nickname("1", "  Bob\x1b[31m  ").await,
+        Some("Bob[31m".to_string())
+    );
+    assert_eq!(
+        manager
+            .set_nickname("1", &"x".repeat(100))
+            .await
+            .map(|nick| nick.len()),
+        Some(MAX_NICKNAME_LEN)
+    );
+    assert!(manager.set_nickname("1", " \n ").await.is_none());
+    assert!(manager.set_nickname("9", "Ghost").await.is_none());
+}
+
+#[tokio::test]
+async fn test_announce_nickname_reaches_peers() {
+    let (manager, _receiver) = PeerManager::new();
+    let (peer, mut rx) = connected_peer("1", 9001);
+    manager.add_peer("1".to_string(), peer).await.unwrap();
+    assert_eq!(manager.local_nickname(), None);
+
+    manager.announce_nickname("Carol").await.unwrap();
+    assert_eq!(manager.local_nickname(), Some("Carol".to_string()));
+    assert_eq!(
+        rx.try_recv().unwrap().msg_type,
+        MessageType::Nickname("Carol".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_disconnect_signals_connection() {
+    let (manager, _receiver) = PeerManager::new();
+    let (mut peer, _rx) = connected_peer("1", 9001);
+    peer.info.nickname = Some("Alice".to_string());
+    let shutdown = peer.shutdown.clone();
+    manager.add_peer("1".to_string(), peer).await.unwrap();
+
+    let peer = manager.disconnect("alice").await.unwrap();
+    assert_eq!(peer.info.id, "1");
+    assert_eq!(manager.peer_count().await, 0);
+    tokio::time::timeout(std::time::Duration::from_secs(1), shutdown.notified())
+        .await
+        .expect("connection handler was not notified");
+
+    assert!(manager.disconnect("alice").await.is_err());
+}
+
+#[test]
+fn test_peer_display_name() {
+    let mut info = PeerInfo {
+        id: "3".to_string(),
+        nickname: None,
+        address: "127.0.0.1:8080".parse().unwrap(),
+        connected_at: SystemTime::now(),
+    };
+    assert_eq!(info.display_name(), "Peer 3");
+
+    info.nickname = Some("Alice".to_string());
+    assert_eq!(info.display_name(), "Alice");
+}
@@ -1,5 +1,7 @@
 use rust_p2p_chat::protocol::{
-    Command, EncryptionMessage, FileInfo, Message, MessageType, StatusUpdate,
+    Capabilities, Command, Compression, EncryptionMessage, FileInfo, Frame, FrameDecoder,
+    GossipMessage, Message, MessageType, StatusUpdate, TransferHeader, TransferMessage,
+    FRAME_MARKER, GOSSIP_TTL, MAX_LINE_LEN,
 };
 use std::time::SystemTime;
 
@@ -138,6 +140,17 @@ fn test_message_heartbeat_serialization() {
     assert!(matches!(deserialized.msg_type, MessageType::Heartbeat));
 }
 
+#[test]
+fn test_message_goodbye_serialization() {
+    let original = Message::new_goodbye();
+
+    let serialized = original.serialize().unwrap();
+    let deserialized = Message::deserialize(&serialized).unwrap();
+
+    assert_eq!(original.id, deserialized.id);
+    assert!(matches!(deserialized.msg_type, MessageType::Goodbye));
+}
+
 #[test]
 fn test_message_acknowledgment_serialization() {
     let msg_id = 12345u64;
@@ -277,3 +290,171 @@ fn test_message_id_uniqueness() {
         assert!(ids.insert(msg.id), "Duplicate ID found: {}", msg.id);
     }
 }
+
+#[test]
+fn test_transfer_message_serialization() {
+    let messages = vec![
+        TransferMessage::Start(TransferHeader {
+            id: 12345,
+            name: "video.mp4".to_string(),
+            size: 3,
+            hash: "abc".to_string(),
+        }),
+        TransferMessage::Chunk {
+            id: 12345,
+            offset: 0,
+            data: vec![1, 2, 3],
+        },
+        TransferMessage::CompressedChunk {
+            id: 12345,
+            offset: 3,
+            algorithm: Compression::Deflate,
+            data: vec![4, 5],
+        },
+        TransferMessage::Accept { id: 12345 },
+        TransferMessage::AlreadyHave { id: 12345 },
+        TransferMessage::Complete { id: 12345 },
+        TransferMessage::Pause {
+            id: 12345,
+            from_sender: true,
+        },
+        TransferMessage::Resume {
+            id: 12345,
+            from_sender: false,
+        },
+        TransferMessage::Cancel {
+            id: 12345,
+            from_sender: true,
+        },
+    ];
+
+    for transfer_msg in messages {
+        assert_eq!(transfer_msg.transfer_id(), 12345);
+        let original = Message::new_transfer(transfer_msg.clone());
+        let deserialized = Message::deserialize(&original.serialize().unwrap()).unwrap();
+        assert_eq!(deserialized.msg_type, MessageType::Transfer(transfer_msg));
+    }
+}
+
+#[test]
+fn test_chunk_len_counts_only_file_data() {
+    let chunk = Message::new_transfer(TransferMessage::Chunk {
+        id: 1,
+        offset: 0,
+        data: vec![0; 100],
+    });
+    let compressed = Message::new_transfer(TransferMessage::CompressedChunk {
+        id: 1,
+        offset: 100,
+        algorithm: Compression::Deflate,
+        data: vec![0; 10],
+    });
+    assert_eq!(chunk.chunk_len(), 100);
+    assert_eq!(compressed.chunk_len(), 10);
+    assert_eq!(
+        Message::new_transfer(TransferMessage::Complete { id: 1 }).chunk_len(),
+        0
+    );
+    assert_eq!(Message::new_text("hello".to_string()).chunk_len(), 0);
+}
+
+#[test]
+fn test_capabilities_serialization() {
+    let caps = Capabilities {
+        compression: vec![Compression::Deflate],
+    };
+    let original = Message::new_capabilities(caps.clone());
+    let deserialized = Message::deserialize(&original.serialize().unwrap()).unwrap();
+    assert_eq!(deserialized.msg_type, MessageType::Capabilities(caps));
+}
+
+#[test]
+fn test_frame_round_trip() {
+    let original = Message::new_text("framed".to_string());
+    let frame = original.to_frame().unwrap();
+    assert_eq!(frame[0], FRAME_MARKER);
+
+    let mut decoder = FrameDecoder::new();
+    decoder.extend(&frame);
+    match decoder.next_frame().unwrap() {
+        Some(Frame::Message(message)) => assert_eq!(message.id, original.id),
+        other => panic!("Expected message frame, got {:?}", other),
+    }
+    assert!(decoder.next_frame().unwrap().is_none());
+}
+
+#[test]
+fn test_frame_decoder_partial_input() {
+    let frame = Message::new_heartbeat().to_frame().unwrap();
+    let mut decoder = FrameDecoder::new();
+
+    // Feed one byte at a time; the frame only appears once complete
+    for (i, byte) in frame.iter().enumerate() {
+        decoder.extend(&[*byte]);
+        let result = decoder.next_frame().unwrap();
+        if i + 1 < frame.len() {
+            assert!(result.is_none());
+        } else {
+            assert!(matches!(result, Some(Frame::Message(_))));
+        }
+    }
+}
+
+#[test]
+fn test_frame_decoder_mixed_text_and_frames() {
+    let mut input = b"hello\nworld\n".to_vec();
+    input.extend(Message::new_heartbeat().to_frame().unwrap());
+    input.extend(b"after\n");
+
+    let mut decoder = FrameDecoder::new();
+    decoder.extend(&input);
+
+    assert!(matches!(decoder.next_frame().unwrap(), Some(Frame::Text(t)) if t == "hello"));
+    assert!(matches!(decoder.next_frame().unwrap(), Some(Frame::Text(t)) if t == "world"));
+    assert!(matches!(
+        decoder.next_frame().unwrap(),
+        Some(Frame::Message(_))
+    ));
+    assert!(matches!(decoder.next_frame().unwrap(), Some(Frame::Text(t)) if t == "after"));
+    assert!(decoder.next_frame().unwrap().is_none());
+}
+
+#[test]
+fn test_frame_decoder_rejects_oversized_frame() {
+    let mut decoder = FrameDecoder::new();
+    decoder.extend(&[FRAME_MARKER, 0xFF, 0xFF, 0xFF, 0xFF]);
+    assert!(decoder.next_frame().is_err());
+}
+
+#[test]
+fn test_frame_decoder_rejects_endless_line() {
+    let mut decoder = FrameDecoder::new();
+    decoder.extend(&vec![b'a'; MAX_LINE_LEN]);
+    assert!(decoder.next_frame().unwrap().is_none());
+    decoder.extend(b"a");
+    assert!(decoder.next_frame().is_err());
+
+    // A line at the limit is still accepted
+    let mut decoder = FrameDecoder::new();
+    decoder.extend(&vec![b'a'; MAX_LINE_LEN]);
+    decoder.extend(b"\n");
+    assert!(
+        matches!(decoder.next_frame().unwrap(), Some(Frame::Text(t)) if t.len() == MAX_LINE_LEN)
+    );
+}
+
+#[test]
+fn test_gossip_serialization_keeps_id() {
+    let original = Message {
+        id: 42,
+        timestamp: SystemTime::now(),
+        msg_type: MessageType::Gossip(GossipMessage {
+            origin: "Peer 1".to_string(),
+            ttl: GOSSIP_TTL - 1,
+            payload: Box::new(MessageType::EncryptedText("c2VjcmV0".to_string())),
+        }),
+    };
+    let deserialized = Message::deserialize(&original.serialize().unwrap()).unwrap();
+    assert_eq!(deserialized.id, 42);
+    assert_eq!(deserialized.msg_type, original.msg_type);
+}
@@ -0,0 +1,1156 @@
+use rust_p2p_chat::content_cache::ContentCache;
+use rust_p2p_chat::protocol::{
+    Compression, DirectoryManifest, ManifestEntry, Message, MessageType, TransferHeader,
+    TransferMessage,
+};
+use rust_p2p_chat::transfer::{TransferDirection, TransferEvent, TransferManager, TransferState};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tokio::sync::mpsc;
+
+fn transfer_payload(message: Message) -> TransferMessage {
+    match message.msg_type {
+        MessageType::Transfer(msg) => msg,
+        other => panic!("Expected transfer message, got {:?}", other),
+    }
+}
+
+/// Delivers every queued transfer message from `rx` to the receiving manager.
+async fn pump(
+    rx: &mut mpsc::Receiver<Message>,
+    receiver: &TransferManager,
+    download_dir: &Path,
+    reply_tx: &mpsc::Sender<Message>,
+) -> Vec<TransferEvent> {
+    let mut events = Vec::new();
+    while let Ok(message) = rx.try_recv() {
+        let event = receiver
+            .handle_message(transfer_payload(message), download_dir, reply_tx)
+            .await
+            .unwrap();
+        events.push(event);
+    }
+    events
+}
+
+/// Hands the sender's offer to the receiver and its reply back to the sender.
+async fn offer(
+    sender: &TransferManager,
+    receiver: &TransferManager,
+    rx: &mut mpsc::Receiver<Message>,
+    download_dir: &Path,
+) -> TransferEvent {
+    let (reply_tx, mut reply_rx) = mpsc::channel(10);
+    let start = transfer_payload(rx.recv().await.unwrap());
+    let event = receiver
+        .handle_message(start, download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let reply = transfer_payload(reply_rx.recv().await.unwrap());
+    sender
+        .handle_message(reply, download_dir, &reply_tx)
+        .await
+        .unwrap();
+    event
+}
+
+/// Returns the local ID the receiver gave an offer.
+fn offer_id(event: &TransferEvent) -> u32 {
+    match event {
+        TransferEvent::Started(status) | TransferEvent::Offered(status) => status.id,
+        other => panic!("Expected an offer, got {:?}", other),
+    }
+}
+
+/// Where the receiver keeps the partial data of incoming transfer `id`.
+fn part_path(download_dir: &Path, name: &str, id: u32) -> PathBuf {
+    download_dir.join(format!(".{}.{}.part", name, id))
+}
+
+/// Lists the partial downloads left in `dir`.
+fn partial_files(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().ends_with(".part"))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Simulates the peer accepting an outgoing transfer.
+async fn accept(sender: &TransferManager, id: u32) {
+    let (tx, _rx) = mpsc::channel(10);
+    sender
+        .handle_message(TransferMessage::Accept { id }, Path::new("."), &tx)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_chunked_transfer_round_trip() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("data.bin");
+    let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&source, &content).unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+
+    let sender = Arc::new(TransferManager::new(10));
+    let receiver = TransferManager::new(10);
+    let (tx, mut rx) = mpsc::channel(1000);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let (id, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+    let started = offer(&sender, &receiver, &mut rx, &download_dir).await;
+    assert!(matches!(started, TransferEvent::Started(_)));
+    let state = sender.stream_outgoing(id, tx.clone()).await.unwrap();
+    assert_eq!(state, TransferState::Completed);
+
+    let events = pump(&mut rx, &receiver, &download_dir, &reply_tx).await;
+    match events.last() {
+        Some(TransferEvent::Completed { path, .. }) => {
+            assert_eq!(std::fs::read(path).unwrap(), content);
+            assert_eq!(path, &download_dir.join("data.bin"));
+        }
+        other => panic!("Expected completion, got {:?}", other),
+    }
+    assert!(partial_files(&download_dir).is_empty());
+
+    let status = receiver.status(offer_id(&started)).await.unwrap();
+    assert_eq!(status.direction, TransferDirection::Incoming);
+    assert_eq!(status.state, TransferState::Completed);
+    assert_eq!(status.transferred, content.len() as u64);
+}
+
+#[tokio::test]
+async fn test_offer_waits_to_be_accepted() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10).with_auto_accept(false);
+    let (reply_tx, mut reply_rx) = mpsc::channel(100);
+
+    let header = TransferHeader {
+        id: 5150,
+        name: "photo.png".to_string(),
+        size: 10,
+        hash: "unused".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let id = match event {
+        TransferEvent::Offered(status) => {
+            assert_eq!(status.state, TransferState::Waiting);
+            status.id
+        }
+        other => panic!("Expected an offer, got {:?}", other),
+    };
+    // Nothing is asked for or written until the user accepts
+    assert!(reply_rx.try_recv().is_err());
+    assert!(!download_dir.exists());
+
+    let name = receiver.accept(id, &reply_tx).await.unwrap();
+    assert_eq!(name, "photo.png");
+    assert!(part_path(&download_dir, "photo.png", id).exists());
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Accept { id: 5150 }
+    );
+    assert_eq!(
+        receiver.status(id).await.unwrap().state,
+        TransferState::Active
+    );
+    assert!(receiver.accept(id, &reply_tx).await.is_err());
+    assert!(receiver.accept(u32::MAX, &reply_tx).await.is_err());
+
+    // Rejecting is cancelling the offer
+    let header = TransferHeader {
+        id: 5151,
+        name: "other.png".to_string(),
+        size: 10,
+        hash: "unused".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let other = offer_id(&event);
+    receiver.cancel(other, &reply_tx).await.unwrap();
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Cancel {
+            id: 5151,
+            from_sender: false
+        }
+    );
+    assert_eq!(
+        partial_files(&download_dir),
+        vec![part_path(&download_dir, "photo.png", id)]
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_removes_partial_file() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10);
+    let (reply_tx, mut reply_rx) = mpsc::channel(100);
+
+    let header = TransferHeader {
+        id: 4242,
+        name: "big.iso".to_string(),
+        size: 10,
+        hash: "unused".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let id = offer_id(&event);
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Accept { id: 4242 }
+    );
+    receiver
+        .handle_message(
+            TransferMessage::Chunk {
+                id: 4242,
+                offset: 0,
+                data: vec![1, 2, 3],
+            },
+            &download_dir,
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+    assert!(part_path(&download_dir, "big.iso", id).exists());
+
+    // Local cancel notifies the peer and deletes the partial file
+    let name = receiver.cancel(id, &reply_tx).await.unwrap();
+    assert_eq!(name, "big.iso");
+    assert!(partial_files(&download_dir).is_empty());
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Cancel {
+            id: 4242,
+            from_sender: false
+        }
+    );
+
+    // Late chunks for the cancelled transfer are ignored
+    let event = receiver
+        .handle_message(
+            TransferMessage::Chunk {
+                id: 4242,
+                offset: 3,
+                data: vec![4],
+            },
+            &download_dir,
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+    assert!(matches!(event, TransferEvent::Ignored));
+    assert_eq!(
+        receiver.status(id).await.unwrap().state,
+        TransferState::Cancelled
+    );
+}
+
+#[tokio::test]
+async fn test_remote_cancel_removes_partial_file() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let header = TransferHeader {
+        id: 777,
+        name: "notes.txt".to_string(),
+        size: 5,
+        hash: "unused".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let id = offer_id(&event);
+    let event = receiver
+        .handle_message(
+            TransferMessage::Cancel {
+                id: 777,
+                from_sender: true,
+            },
+            &download_dir,
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+
+    assert!(matches!(event, TransferEvent::Cancelled { id: cancelled, .. } if cancelled == id));
+    assert!(partial_files(&download_dir).is_empty());
+}
+
+#[tokio::test]
+async fn test_pause_and_resume_outgoing() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("paused.bin");
+    std::fs::write(&source, vec![7u8; 300_000]).unwrap();
+
+    let sender = Arc::new(TransferManager::new(10));
+    let (tx, mut rx) = mpsc::channel(1000);
+    let (id, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+    rx.recv().await.unwrap(); // Start
+    accept(&sender, id).await;
+
+    let (control_tx, mut control_rx) = mpsc::channel(10);
+    sender.pause(id, &control_tx).await.unwrap();
+    assert_eq!(
+        transfer_payload(control_rx.recv().await.unwrap()),
+        TransferMessage::Pause {
+            id,
+            from_sender: true
+        }
+    );
+    assert_eq!(
+        sender.status(id).await.unwrap().state,
+        TransferState::Paused
+    );
+
+    let stream = {
+        let sender = sender.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move { sender.stream_outgoing(id, tx).await })
+    };
+
+    // No chunks while paused
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(rx.try_recv().is_err());
+
+    sender.resume(id, &control_tx).await.unwrap();
+    let state = stream.await.unwrap().unwrap();
+    assert_eq!(state, TransferState::Completed);
+    assert!(rx.try_recv().is_ok());
+
+    // Pausing a finished transfer is an error
+    assert!(sender.pause(id, &control_tx).await.is_err());
+}
+
+#[tokio::test]
+async fn test_cancel_stops_outgoing_stream() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("cancelled.bin");
+    std::fs::write(&source, vec![1u8; 300_000]).unwrap();
+
+    let sender = Arc::new(TransferManager::new(10));
+    let (tx, _rx) = mpsc::channel(1000);
+    let (id, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+
+    sender.cancel(id, &tx).await.unwrap();
+    let state = sender.stream_outgoing(id, tx.clone()).await.unwrap();
+    assert_eq!(state, TransferState::Cancelled);
+    assert!(sender.summary().await.contains("cancelled"));
+}
+
+#[tokio::test]
+async fn test_incoming_name_traversal_is_stripped() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let header = TransferHeader {
+        id: 99,
+        name: "../../escape.txt".to_string(),
+        size: 1,
+        hash: "unused".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+
+    assert!(matches!(event, TransferEvent::Started(ref s) if s.name == "escape.txt"));
+    let id = offer_id(&event);
+    assert!(part_path(&download_dir, "escape.txt", id).exists());
+    assert!(partial_files(temp_dir.path()).is_empty());
+}
+
+#[tokio::test]
+async fn test_incoming_hash_mismatch_fails() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10);
+    let (reply_tx, mut reply_rx) = mpsc::channel(100);
+
+    let header = TransferHeader {
+        id: 5,
+        name: "corrupt.txt".to_string(),
+        size: 2,
+        hash: "0000".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let id = offer_id(&event);
+    let chunk = TransferMessage::Chunk {
+        id: 5,
+        offset: 0,
+        data: vec![1, 2],
+    };
+    receiver
+        .handle_message(chunk, &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let event = receiver
+        .handle_message(
+            TransferMessage::Complete { id: 5 },
+            &download_dir,
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+
+    assert!(matches!(event, TransferEvent::Failed { id: failed, .. } if failed == id));
+    assert!(!download_dir.join("corrupt.txt").exists());
+    assert!(partial_files(&download_dir).is_empty());
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Accept { id: 5 }
+    );
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Cancel {
+            id: 5,
+            from_sender: false
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_incoming_size_limit_rejected() {
+    let temp_dir = tempdir().unwrap();
+    let receiver = TransferManager::new(1);
+    let (reply_tx, mut reply_rx) = mpsc::channel(100);
+
+    let header = TransferHeader {
+        id: 6,
+        name: "huge.bin".to_string(),
+        size: 2 * 1024 * 1024,
+        hash: "unused".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), temp_dir.path(), &reply_tx)
+        .await
+        .unwrap();
+
+    assert!(
+        matches!(event, TransferEvent::Failed { ref reason, .. } if reason.contains("too large"))
+    );
+    assert!(reply_rx.try_recv().is_ok());
+}
+
+#[tokio::test]
+async fn test_directory_transfer_round_trip() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("album");
+    std::fs::create_dir_all(source.join("2024/summer")).unwrap();
+    std::fs::write(source.join("cover.jpg"), vec![3u8; 100_000]).unwrap();
+    std::fs::write(source.join("2024/notes.txt"), b"hello").unwrap();
+    std::fs::write(source.join("2024/summer/empty.txt"), b"").unwrap();
+    std::fs::write(source.join("2024/summer/beach.png"), vec![9u8; 70_000]).unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+
+    let sender = Arc::new(TransferManager::new(10));
+    let receiver = TransferManager::new(10);
+    let (tx, mut rx) = mpsc::channel(1000);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let (id, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+    let started = offer(&sender, &receiver, &mut rx, &download_dir).await;
+    assert!(matches!(started, TransferEvent::Started(s) if s.files == Some(4)));
+    let state = sender.stream_outgoing(id, tx.clone()).await.unwrap();
+    assert_eq!(state, TransferState::Completed);
+    assert_eq!(sender.status(id).await.unwrap().files, Some(4));
+
+    let events = pump(&mut rx, &receiver, &download_dir, &reply_tx).await;
+    match events.last() {
+        Some(TransferEvent::Completed { path, .. }) => {
+            assert_eq!(path, &download_dir.join("album"));
+        }
+        other => panic!("Expected completion, got {:?}", other),
+    }
+
+    let received = download_dir.join("album");
+    for relative in [
+        "cover.jpg",
+        "2024/notes.txt",
+        "2024/summer/empty.txt",
+        "2024/summer/beach.png",
+    ] {
+        assert_eq!(
+            std::fs::read(received.join(relative)).unwrap(),
+            std::fs::read(source.join(relative)).unwrap(),
+            "{} differs",
+            relative
+        );
+    }
+    assert!(partial_files(&download_dir).is_empty());
+}
+
+#[tokio::test]
+async fn test_directory_manifest_traversal_rejected() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10);
+    let (reply_tx, mut reply_rx) = mpsc::channel(100);
+
+    for (id, path) in [(1, "../escape.txt"), (2, "/etc/passwd"), (3, "a/../../b")] {
+        let manifest = DirectoryManifest {
+            id,
+            name: "evil".to_string(),
+            entries: vec![ManifestEntry {
+                path: path.to_string(),
+                size: 1,
+                hash: "unused".to_string(),
+            }],
+        };
+        let event = receiver
+            .handle_message(
+                TransferMessage::StartDirectory(manifest),
+                &download_dir,
+                &reply_tx,
+            )
+            .await
+            .unwrap();
+
+        assert!(
+            matches!(event, TransferEvent::Failed { .. }),
+            "{} accepted",
+            path
+        );
+        assert_eq!(
+            transfer_payload(reply_rx.try_recv().unwrap()),
+            TransferMessage::Cancel {
+                id,
+                from_sender: false
+            }
+        );
+    }
+    assert!(!temp_dir.path().join("escape.txt").exists());
+    assert!(partial_files(&download_dir).is_empty());
+}
+
+#[tokio::test]
+async fn test_directory_hash_mismatch_discards_tree() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let manifest = DirectoryManifest {
+        id: 8,
+        name: "docs".to_string(),
+        entries: vec![
+            ManifestEntry {
+                path: "a.txt".to_string(),
+                size: 2,
+                hash: "0000".to_string(),
+            },
+            ManifestEntry {
+                path: "sub/b.txt".to_string(),
+                size: 1,
+                hash: "0000".to_string(),
+            },
+        ],
+    };
+    let event = receiver
+        .handle_message(
+            TransferMessage::StartDirectory(manifest),
+            &download_dir,
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+    let id = offer_id(&event);
+    let chunk = TransferMessage::Chunk {
+        id: 8,
+        offset: 0,
+        data: vec![1, 2, 3],
+    };
+    receiver
+        .handle_message(chunk, &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    assert!(part_path(&download_dir, "docs", id)
+        .join("sub/b.txt")
+        .exists());
+
+    let event = receiver
+        .handle_message(
+            TransferMessage::Complete { id: 8 },
+            &download_dir,
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+
+    assert!(matches!(event, TransferEvent::Failed { id: failed, .. } if failed == id));
+    assert!(!download_dir.join("docs").exists());
+    assert!(partial_files(&download_dir).is_empty());
+}
+
+#[tokio::test]
+async fn test_compressed_transfer_round_trip() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("server.log");
+    let content = "GET /index.html 200 OK\n".repeat(20_000).into_bytes();
+    std::fs::write(&source, &content).unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+
+    let sender = Arc::new(TransferManager::new(10));
+    sender.set_compression(Some(Compression::Deflate));
+    let receiver = TransferManager::new(10);
+    let (tx, mut rx) = mpsc::channel(1000);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let (id, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+    let started = offer(&sender, &receiver, &mut rx, &download_dir).await;
+    sender.stream_outgoing(id, tx.clone()).await.unwrap();
+
+    let mut messages = Vec::new();
+    while let Ok(message) = rx.try_recv() {
+        messages.push(transfer_payload(message));
+    }
+    assert!(messages
+        .iter()
+        .any(|m| matches!(m, TransferMessage::CompressedChunk { .. })));
+    assert!(!messages
+        .iter()
+        .any(|m| matches!(m, TransferMessage::Chunk { .. })));
+
+    for message in messages {
+        receiver
+            .handle_message(message, &download_dir, &reply_tx)
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        std::fs::read(download_dir.join("server.log")).unwrap(),
+        content
+    );
+
+    let sent = sender.status(id).await.unwrap();
+    let received = receiver.status(offer_id(&started)).await.unwrap();
+    assert!(sent.compression_ratio().unwrap() > 10.0);
+    assert_eq!(sent.wire_bytes, received.wire_bytes);
+    assert!(sender.summary().await.contains("x compressed"));
+}
+
+#[tokio::test]
+async fn test_media_files_are_not_compressed() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("photo.jpg");
+    std::fs::write(&source, vec![0u8; 100_000]).unwrap();
+
+    let sender = Arc::new(TransferManager::new(10).with_media_extensions(vec!["jpg".to_string()]));
+    sender.set_compression(Some(Compression::Deflate));
+    let (tx, mut rx) = mpsc::channel(1000);
+
+    let (id, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+    accept(&sender, id).await;
+    sender.stream_outgoing(id, tx.clone()).await.unwrap();
+
+    while let Ok(message) = rx.try_recv() {
+        assert!(!matches!(
+            transfer_payload(message),
+            TransferMessage::CompressedChunk { .. }
+        ));
+    }
+    assert_eq!(sender.status(id).await.unwrap().compression_ratio(), None);
+}
+
+#[tokio::test]
+async fn test_receiver_accepts_unknown_content() {
+    let temp_dir = tempdir().unwrap();
+    let receiver = TransferManager::new(10);
+    let (reply_tx, mut reply_rx) = mpsc::channel(10);
+
+    let header = TransferHeader {
+        id: 31,
+        name: "new.bin".to_string(),
+        size: 4,
+        hash: "not-present".to_string(),
+    };
+    receiver
+        .handle_message(TransferMessage::Start(header), temp_dir.path(), &reply_tx)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Accept { id: 31 }
+    );
+}
+
+#[tokio::test]
+async fn test_repeated_send_is_deduplicated() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("artifact.tar");
+    let content: Vec<u8> = (0..150_000u32).map(|i| (i % 199) as u8).collect();
+    std::fs::write(&source, &content).unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+
+    let sender = Arc::new(TransferManager::new(10));
+    let receiver = TransferManager::new(10);
+    let (tx, mut rx) = mpsc::channel(1000);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    // First send transfers the data
+    let (first, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+    offer(&sender, &receiver, &mut rx, &download_dir).await;
+    sender.stream_outgoing(first, tx.clone()).await.unwrap();
+    pump(&mut rx, &receiver, &download_dir, &reply_tx).await;
+
+    // Second send of the same content under another name is answered
+    // with AlreadyHave and no chunks are streamed
+    let renamed = temp_dir.path().join("artifact-copy.tar");
+    std::fs::copy(&source, &renamed).unwrap();
+    let (second, _) = sender.start_outgoing(&renamed, &tx).await.unwrap();
+    let event = offer(&sender, &receiver, &mut rx, &download_dir).await;
+    match event {
+        TransferEvent::Completed {
+            path, deduplicated, ..
+        } => {
+            assert!(deduplicated);
+            assert_eq!(path, download_dir.join("artifact-copy.tar"));
+            assert_eq!(std::fs::read(path).unwrap(), content);
+        }
+        other => panic!("Expected deduplicated completion, got {:?}", other),
+    }
+
+    let state = sender.stream_outgoing(second, tx.clone()).await.unwrap();
+    assert_eq!(state, TransferState::Completed);
+    assert!(rx.try_recv().is_err());
+    let status = sender.status(second).await.unwrap();
+    assert!(status.deduplicated);
+    assert_eq!(status.wire_bytes, 0);
+}
+
+#[tokio::test]
+async fn test_content_cache_finds_files_outside_downloads() {
+    let temp_dir = tempdir().unwrap();
+    let cache_path = temp_dir.path().join("cache.json");
+    let elsewhere = temp_dir.path().join("builds/app.bin");
+    std::fs::create_dir_all(elsewhere.parent().unwrap()).unwrap();
+    std::fs::write(&elsewhere, b"binary build output").unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+
+    // Sending a file records it in the cache
+    let (tx, _rx) = mpsc::channel(10);
+    let sender = TransferManager::new(10).with_content_cache(ContentCache::load(&cache_path));
+    sender.start_outgoing(&elsewhere, &tx).await.unwrap();
+    assert_eq!(ContentCache::load(&cache_path).len(), 1);
+
+    // A later offer of the same content is satisfied from the cache
+    let receiver = TransferManager::new(10).with_content_cache(ContentCache::load(&cache_path));
+    let (reply_tx, mut reply_rx) = mpsc::channel(10);
+    let header = TransferHeader {
+        id: 55,
+        name: "app.bin".to_string(),
+        size: 19,
+        hash: rust_p2p_chat::file_transfer::FileTransfer::hash_file(&elsewhere)
+            .await
+            .unwrap(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        event,
+        TransferEvent::Completed {
+            deduplicated: true,
+            ..
+        }
+    ));
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::AlreadyHave { id: 55 }
+    );
+    assert_eq!(
+        std::fs::read(download_dir.join("app.bin")).unwrap(),
+        b"binary build output"
+    );
+}
+
+#[tokio::test]
+async fn test_content_cache_drops_missing_files_on_load() {
+    let temp_dir = tempdir().unwrap();
+    let cache_path = temp_dir.path().join("cache.json");
+    let kept = temp_dir.path().join("kept.txt");
+    std::fs::write(&kept, b"still here").unwrap();
+
+    let mut cache = ContentCache::load(&cache_path);
+    cache.insert("kept".to_string(), kept.clone());
+    cache.insert("gone".to_string(), temp_dir.path().join("gone.txt"));
+    cache.snapshot().unwrap().save().await.unwrap();
+
+    let cache = ContentCache::load(&cache_path);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.get("kept"), Some(&kept));
+}
+
+#[tokio::test]
+async fn test_older_content_cache_snapshot_does_not_overwrite_newer() {
+    let temp_dir = tempdir().unwrap();
+    let cache_path = temp_dir.path().join("cache.json");
+    let first = temp_dir.path().join("first.txt");
+    let second = temp_dir.path().join("second.txt");
+    std::fs::write(&first, b"first").unwrap();
+    std::fs::write(&second, b"second").unwrap();
+
+    let mut cache = ContentCache::load(&cache_path);
+    cache.insert("first".to_string(), first);
+    let older = cache.snapshot().unwrap();
+    cache.insert("second".to_string(), second);
+    let newer = cache.snapshot().unwrap();
+    assert!(cache.snapshot().is_none());
+
+    newer.save().await.unwrap();
+    older.save().await.unwrap();
+    assert_eq!(ContentCache::load(&cache_path).len(), 2);
+}
+
+#[tokio::test]
+async fn test_stale_cache_entry_is_ignored() {
+    let temp_dir = tempdir().unwrap();
+    let stale = temp_dir.path().join("changed.txt");
+    std::fs::write(&stale, b"new contents").unwrap();
+
+    let mut cache = ContentCache::in_memory();
+    cache.insert("old-hash".to_string(), stale.clone());
+    let receiver = TransferManager::new(10).with_content_cache(cache);
+    let (reply_tx, mut reply_rx) = mpsc::channel(10);
+
+    let header = TransferHeader {
+        id: 56,
+        name: "changed.txt".to_string(),
+        size: 12,
+        hash: "old-hash".to_string(),
+    };
+    let event = receiver
+        .handle_message(
+            TransferMessage::Start(header),
+            &temp_dir.path().join("downloads"),
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+
+    assert!(matches!(event, TransferEvent::Started(_)));
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Accept { id: 56 }
+    );
+}
+
+#[tokio::test]
+async fn test_offer_is_deduplicated_only_after_accept() {
+    let temp_dir = tempdir().unwrap();
+    let known = temp_dir.path().join("known.txt");
+    std::fs::write(&known, b"private notes").unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+
+    let mut cache = ContentCache::in_memory();
+    let hash = rust_p2p_chat::file_transfer::FileTransfer::hash_file(&known)
+        .await
+        .unwrap();
+    cache.insert(hash.clone(), known.clone());
+    let receiver = TransferManager::new(10)
+        .with_content_cache(cache)
+        .with_auto_accept(false);
+    let (reply_tx, mut reply_rx) = mpsc::channel(10);
+
+    let header = TransferHeader {
+        id: 57,
+        name: "probe.txt".to_string(),
+        size: 13,
+        hash,
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+
+    // The offer does not reveal that the content is known
+    assert!(matches!(event, TransferEvent::Offered(_)));
+    assert!(reply_rx.try_recv().is_err());
+    assert!(!download_dir.join("probe.txt").exists());
+
+    let id = offer_id(&event);
+    receiver.accept(id, &reply_tx).await.unwrap();
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::AlreadyHave { id: 57 }
+    );
+    assert_eq!(
+        std::fs::read(download_dir.join("probe.txt")).unwrap(),
+        b"private notes"
+    );
+    assert!(partial_files(&download_dir).is_empty());
+    let status = receiver.status(id).await.unwrap();
+    assert_eq!(status.state, TransferState::Completed);
+    assert!(status.deduplicated);
+}
+
+#[tokio::test]
+async fn test_offer_may_reuse_id_of_outgoing_transfer() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("mine.txt");
+    std::fs::write(&source, b"mine").unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let manager = TransferManager::new(10);
+    let (tx, mut rx) = mpsc::channel(10);
+
+    // Both peers picked the same ID for the files they send each other
+    let (outgoing, _) = manager.start_outgoing(&source, &tx).await.unwrap();
+    rx.recv().await.unwrap(); // Start
+    let header = TransferHeader {
+        id: outgoing,
+        name: "theirs.txt".to_string(),
+        size: 6,
+        hash: "unused".to_string(),
+    };
+    let event = manager
+        .handle_message(TransferMessage::Start(header.clone()), &download_dir, &tx)
+        .await
+        .unwrap();
+    let incoming = offer_id(&event);
+    assert_ne!(incoming, outgoing);
+    assert_eq!(
+        transfer_payload(rx.try_recv().unwrap()),
+        TransferMessage::Accept { id: outgoing }
+    );
+
+    // The same offer again while it is still running is rejected
+    let event = manager
+        .handle_message(TransferMessage::Start(header), &download_dir, &tx)
+        .await
+        .unwrap();
+    assert!(matches!(event, TransferEvent::Failed { .. }));
+    rx.try_recv().unwrap(); // Cancel
+
+    // The peer cancelling what it receives only stops our upload
+    let event = manager
+        .handle_message(
+            TransferMessage::Cancel {
+                id: outgoing,
+                from_sender: false,
+            },
+            &download_dir,
+            &tx,
+        )
+        .await
+        .unwrap();
+    assert!(matches!(event, TransferEvent::Cancelled { id, .. } if id == outgoing));
+    assert_eq!(
+        manager.status(incoming).await.unwrap().state,
+        TransferState::Active
+    );
+
+    // Cancelling our download refers to it by the peer's ID
+    manager.cancel(incoming, &tx).await.unwrap();
+    assert_eq!(
+        transfer_payload(rx.try_recv().unwrap()),
+        TransferMessage::Cancel {
+            id: outgoing,
+            from_sender: false
+        }
+    );
+    assert!(partial_files(&download_dir).is_empty());
+}
+
+#[tokio::test]
+async fn test_same_name_offers_do_not_collide() {
+    let temp_dir = tempdir().unwrap();
+    let first = temp_dir.path().join("a/same.txt");
+    let second = temp_dir.path().join("b/same.txt");
+    std::fs::create_dir_all(first.parent().unwrap()).unwrap();
+    std::fs::create_dir_all(second.parent().unwrap()).unwrap();
+    std::fs::write(&first, b"first").unwrap();
+    std::fs::write(&second, b"second").unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+
+    let sender = Arc::new(TransferManager::new(10));
+    let receiver = TransferManager::new(10);
+    let (tx, mut rx) = mpsc::channel(1000);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let (first_id, _) = sender.start_outgoing(&first, &tx).await.unwrap();
+    offer(&sender, &receiver, &mut rx, &download_dir).await;
+    let (second_id, _) = sender.start_outgoing(&second, &tx).await.unwrap();
+    offer(&sender, &receiver, &mut rx, &download_dir).await;
+    assert_eq!(partial_files(&download_dir).len(), 2);
+
+    sender.stream_outgoing(first_id, tx.clone()).await.unwrap();
+    sender.stream_outgoing(second_id, tx.clone()).await.unwrap();
+    let events = pump(&mut rx, &receiver, &download_dir, &reply_tx).await;
+    let paths: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            TransferEvent::Completed { path, .. } => Some(path.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            download_dir.join("same.txt"),
+            download_dir.join("same (1).txt")
+        ]
+    );
+    assert_eq!(std::fs::read(&paths[0]).unwrap(), b"first");
+    assert_eq!(std::fs::read(&paths[1]).unwrap(), b"second");
+    assert!(partial_files(&download_dir).is_empty());
+}
+
+#[tokio::test]
+async fn test_download_does_not_replace_existing_directory() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("docs");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(source.join("new.txt"), b"new").unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    std::fs::create_dir_all(download_dir.join("docs")).unwrap();
+    std::fs::write(download_dir.join("docs/old.txt"), b"old").unwrap();
+
+    let sender = Arc::new(TransferManager::new(10));
+    let receiver = TransferManager::new(10);
+    let (tx, mut rx) = mpsc::channel(1000);
+    let (reply_tx, _reply_rx) = mpsc::channel(100);
+
+    let (id, _) = sender.start_outgoing(&source, &tx).await.unwrap();
+    offer(&sender, &receiver, &mut rx, &download_dir).await;
+    sender.stream_outgoing(id, tx.clone()).await.unwrap();
+    let events = pump(&mut rx, &receiver, &download_dir, &reply_tx).await;
+
+    match events.last() {
+        Some(TransferEvent::Completed { path, .. }) => {
+            assert_eq!(path, &download_dir.join("docs (1)"));
+        }
+        other => panic!("Expected completion, got {:?}", other),
+    }
+    assert_eq!(
+        std::fs::read(download_dir.join("docs (1)/new.txt")).unwrap(),
+        b"new"
+    );
+    assert_eq!(
+        std::fs::read(download_dir.join("docs/old.txt")).unwrap(),
+        b"old"
+    );
+    assert!(!download_dir.join("docs/new.txt").exists());
+}
+
+#[tokio::test]
+async fn test_remote_resume_does_not_accept_offer() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10).with_auto_accept(false);
+    let (reply_tx, mut reply_rx) = mpsc::channel(100);
+
+    let header = TransferHeader {
+        id: 77,
+        name: "sneaky.txt".to_string(),
+        size: 3,
+        hash: "unused".to_string(),
+    };
+    let event = receiver
+        .handle_message(TransferMessage::Start(header), &download_dir, &reply_tx)
+        .await
+        .unwrap();
+    let id = offer_id(&event);
+
+    // The sender cannot skip /accept by pausing or resuming the offer
+    for control in [
+        TransferMessage::Resume {
+            id: 77,
+            from_sender: true,
+        },
+        TransferMessage::Pause {
+            id: 77,
+            from_sender: true,
+        },
+    ] {
+        let event = receiver
+            .handle_message(control, &download_dir, &reply_tx)
+            .await
+            .unwrap();
+        assert!(matches!(event, TransferEvent::Ignored));
+    }
+    let messages = [
+        TransferMessage::Chunk {
+            id: 77,
+            offset: 0,
+            data: vec![1, 2, 3],
+        },
+        TransferMessage::Complete { id: 77 },
+    ];
+    for message in messages {
+        let event = receiver
+            .handle_message(message, &download_dir, &reply_tx)
+            .await
+            .unwrap();
+        assert!(matches!(event, TransferEvent::Ignored));
+    }
+    assert_eq!(
+        receiver.status(id).await.unwrap().state,
+        TransferState::Waiting
+    );
+    assert!(receiver.resume(id, &reply_tx).await.is_err());
+    assert!(!download_dir.exists());
+    assert!(reply_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_directory_manifest_size_overflow_rejected() {
+    let temp_dir = tempdir().unwrap();
+    let download_dir = temp_dir.path().join("downloads");
+    let receiver = TransferManager::new(10);
+    let (reply_tx, mut reply_rx) = mpsc::channel(100);
+
+    // The sizes wrap around to a total of 1 byte if added unchecked
+    let entry = |path: &str, size| ManifestEntry {
+        path: path.to_string(),
+        size,
+        hash: "unused".to_string(),
+    };
+    let manifest = DirectoryManifest {
+        id: 9,
+        name: "huge".to_string(),
+        entries: vec![entry("a", u64::MAX), entry("b", 2)],
+    };
+    assert!(manifest.total_size().is_err());
+    let event = receiver
+        .handle_message(
+            TransferMessage::StartDirectory(manifest),
+            &download_dir,
+            &reply_tx,
+        )
+        .await
+        .unwrap();
+
+    assert!(matches!(event, TransferEvent::Failed { .. }));
+    assert_eq!(
+        transfer_payload(reply_rx.try_recv().unwrap()),
+        TransferMessage::Cancel {
+            id: 9,
+            from_sender: false
+        }
+    );
+    assert!(!download_dir.exists());
+}